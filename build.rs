@@ -0,0 +1,23 @@
+use std::process::Command;
+
+fn main() {
+    // Exposed to `cloak version` so deployed binaries can be traced back to a build.
+    println!(
+        "cargo:rustc-env=CLOAK_BUILD_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .filter(|hash| !hash.is_empty());
+    if let Some(hash) = commit {
+        println!("cargo:rustc-env=CLOAK_GIT_COMMIT={hash}");
+    }
+
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -1,67 +1,213 @@
-use anyhow::{Context, Result};
+use crate::config::jetbrains;
+use crate::config::project::ProjectConfig;
+use crate::core::mover;
+use crate::utils::output::trace;
+use anyhow::{Context, Result, bail};
 use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 const SETTINGS_FILE: &str = "settings.json";
-const EXCLUDE_KEY: &str = "files.exclude";
+/// `.cloak/ide-empty-sections.json`: for each settings file (relative to the
+/// root), the exclude sections that held an empty object the user wrote before
+/// cloak added a key to them. Emptying one again leaves it in place.
+const EMPTY_SECTIONS_FILE: &str = "ide-empty-sections.json";
+/// Settings cloak adds its `**/<target>` keys to: the file tree, then search and quick-open.
+const EXCLUDE_SECTIONS: &[&str] = &["files.exclude", "search.exclude"];
 
-/// IDE directories whose `settings.json` we manage.
-/// .vscode settings are always created; others only if the directory already exists.
+/// IDE directories whose `settings.json` we manage, each only if the directory
+/// already exists, unless `ide_dirs` in `.cloak/cloak.toml` replaces them.
 const IDE_DIRS: &[&str] = &[".vscode", ".cursor"];
 
-/// Add a target to `files.exclude` in all relevant IDE settings files.
-pub fn add_ide_exclude(root: &Path, target: &str) -> Result<()> {
+/// Knobs for how IDE settings files are edited.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdeOptions {
+    /// Back up a `settings.json` that cannot be parsed (or is not an object)
+    /// to `settings.json.cloak-bak` and start from an empty object instead of failing.
+    pub force: bool,
+}
+
+/// The IDE directories configured for `root`. A `cloak.toml` that cannot be read
+/// falls back to the built-in list; the commands that load it report the error.
+fn ide_dirs(root: &Path) -> Vec<String> {
+    ProjectConfig::load(root)
+        .ok()
+        .and_then(|config| config.ide_dirs)
+        .unwrap_or_else(|| IDE_DIRS.iter().map(|dir| dir.to_string()).collect())
+}
+
+/// Every IDE settings file cloak may edit, whether or not it exists yet, plus
+/// the JetBrains modules it would add excludes to.
+pub fn settings_files(root: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = ide_dirs(root)
+        .iter()
+        .map(|dir| root.join(dir).join(SETTINGS_FILE))
+        .collect();
+    files.extend(jetbrains::module_files(root));
+    files
+}
+
+/// The IDE directories present at `root`, whose settings `add_ide_exclude` edits.
+/// `.idea` counts when it holds a module rooted at the project.
+pub fn present_dirs(root: &Path) -> Vec<String> {
+    let mut dirs: Vec<String> = ide_dirs(root)
+        .into_iter()
+        .filter(|dir| root.join(dir).exists())
+        .collect();
+    if !jetbrains::module_files(root).is_empty() {
+        dirs.push(jetbrains::IDEA_DIR.to_string());
+    }
+    dirs
+}
+
+/// Add a target to `files.exclude` and `search.exclude` in all relevant IDE
+/// settings files, and as an `excludeFolder` in the JetBrains project model.
+///
+/// Only the managed keys are written: comments, formatting and the order of
+/// everything else in the file are left as they were.
+pub fn add_ide_exclude(root: &Path, target: &str, opts: &IdeOptions) -> Result<()> {
     let exclude_key = format!("**/{target}");
 
-    for ide_dir in IDE_DIRS {
-        let dir_path = root.join(ide_dir);
+    for ide_dir in ide_dirs(root) {
+        let dir_path = root.join(&ide_dir);
         let settings_path = dir_path.join(SETTINGS_FILE);
 
-        // For .vscode, always create if needed. For others, only write if the dir exists.
-        if *ide_dir != ".vscode" && !dir_path.exists() {
+        // Never create an IDE directory the project doesn't already use.
+        if !dir_path.exists() {
             continue;
         }
 
-        let mut settings = load_or_create_settings(&settings_path)?;
-
-        let exclude = settings
-            .entry(EXCLUDE_KEY)
-            .or_insert_with(|| Value::Object(Map::new()));
-
-        if let Value::Object(map) = exclude {
-            map.insert(exclude_key.clone(), Value::Bool(true));
+        let content = read_settings(&settings_path, opts.force)?;
+        let mut edited = content.clone();
+        let mut found_empty = Vec::new();
+        for section in EXCLUDE_SECTIONS {
+            if is_empty_section(&edited, section)? {
+                found_empty.push(section.to_string());
+            }
+            edited = insert_exclude(&edited, section, &exclude_key)
+                .with_context(|| format!("failed to edit {}", settings_path.display()))?;
+        }
+        if edited != content || !settings_path.exists() {
+            trace!("ide: exclude {exclude_key} in {}", settings_path.display());
+            write_settings(&settings_path, &edited)?;
+        }
+        if !found_empty.is_empty() {
+            let mut kept = load_empty_sections(root)?;
+            kept.entry(format!("{ide_dir}/{SETTINGS_FILE}"))
+                .or_default()
+                .extend(found_empty);
+            save_empty_sections(root, &kept)?;
         }
-
-        save_settings(&settings_path, &settings)?;
     }
 
-    Ok(())
+    jetbrains::add_exclude(root, target)
 }
 
-/// Remove a target from `files.exclude` in all relevant IDE settings files.
+/// Remove a target from `files.exclude`, `search.exclude` and the JetBrains
+/// project model, undoing exactly what `add_ide_exclude` wrote.
 pub fn remove_ide_exclude(root: &Path, target: &str) -> Result<()> {
     let exclude_key = format!("**/{target}");
+    let mut kept = load_empty_sections(root)?;
+    let mut restored = false;
 
-    for ide_dir in IDE_DIRS {
-        let settings_path = root.join(ide_dir).join(SETTINGS_FILE);
+    for ide_dir in ide_dirs(root) {
+        let settings_path = root.join(&ide_dir).join(SETTINGS_FILE);
 
         if !settings_path.exists() {
             continue;
         }
 
-        let mut settings = load_or_create_settings(&settings_path)?;
-
-        if let Some(Value::Object(map)) = settings.get_mut(EXCLUDE_KEY) {
+        let file = format!("{ide_dir}/{SETTINGS_FILE}");
+        let content = read_settings(&settings_path, false)?;
+        let mut edited = content.clone();
+        for section in EXCLUDE_SECTIONS {
+            let keep_empty = kept.get(&file).is_some_and(|s| s.contains(*section));
             // Remove both the glob-prefixed key and any legacy bare key
-            map.remove(&exclude_key);
-            map.remove(target);
+            let before = edited;
+            edited = remove_excludes(&before, section, &[&exclude_key, target], keep_empty)
+                .with_context(|| format!("failed to edit {}", settings_path.display()))?;
+            // Once it is back to the user's empty object there is nothing left to remember.
+            if keep_empty && edited != before && is_empty_section(&edited, section)? {
+                if let Some(sections) = kept.get_mut(&file) {
+                    sections.remove(*section);
+                }
+                restored = true;
+            }
+        }
+        if edited != content {
+            trace!("ide: drop {exclude_key} from {}", settings_path.display());
+            write_settings(&settings_path, &edited)?;
         }
+    }
+    if restored {
+        kept.retain(|_, sections| !sections.is_empty());
+        save_empty_sections(root, &kept)?;
+    }
+
+    jetbrains::remove_exclude(root, target)
+}
 
-        save_settings(&settings_path, &settings)?;
+/// For each IDE directory that has a `settings.json`, report whether `target` is
+/// excluded from both the file tree and search; `.idea` is listed when it has
+/// project modules, all of which must exclude it.
+pub fn exclude_status(root: &Path, target: &str) -> Result<Vec<(String, bool)>> {
+    let exclude_key = format!("**/{target}");
+    let mut status = Vec::new();
+
+    for ide_dir in ide_dirs(root) {
+        let settings_path = root.join(&ide_dir).join(SETTINGS_FILE);
+        if !settings_path.exists() {
+            continue;
+        }
+
+        let settings = load_or_create_settings(&settings_path)?;
+        let excluded = EXCLUDE_SECTIONS.iter().all(|section| {
+            matches!(
+                settings.get(*section),
+                Some(Value::Object(map)) if map.get(&exclude_key) == Some(&Value::Bool(true))
+            )
+        });
+        status.push((ide_dir, excluded));
+    }
+    if let Some(excluded) = jetbrains::is_excluded(root, target)? {
+        status.push((jetbrains::IDEA_DIR.to_string(), excluded));
     }
 
-    Ok(())
+    Ok(status)
+}
+
+/// Names excluded in `files.exclude` or `search.exclude` with cloak's `**/<name>`
+/// key form, or by a JetBrains `excludeFolder`, keyed by the file (relative to
+/// `root`) that excludes them. Files that cannot be parsed are skipped.
+pub fn excluded_names(root: &Path) -> Vec<(String, String)> {
+    let mut names = Vec::new();
+    for ide_dir in ide_dirs(root) {
+        let settings_path = root.join(&ide_dir).join(SETTINGS_FILE);
+        if !settings_path.exists() {
+            continue;
+        }
+        let Ok(settings) = load_or_create_settings(&settings_path) else {
+            continue;
+        };
+        let mut found = std::collections::BTreeSet::new();
+        for section in EXCLUDE_SECTIONS {
+            let Some(Value::Object(map)) = settings.get(*section) else {
+                continue;
+            };
+            for (key, value) in map {
+                if let Some(name) = key.strip_prefix("**/")
+                    && *value == Value::Bool(true)
+                {
+                    found.insert(name.to_string());
+                }
+            }
+        }
+        let label = format!("{ide_dir}/{SETTINGS_FILE}");
+        names.extend(found.into_iter().map(|name| (label.clone(), name)));
+    }
+    names.extend(jetbrains::excluded_names(root));
+    names
 }
 
 fn load_or_create_settings(path: &Path) -> Result<Map<String, Value>> {
@@ -72,33 +218,487 @@ fn load_or_create_settings(path: &Path) -> Result<Map<String, Value>> {
     let content =
         fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
 
-    // Strip single-line comments (// ...) and block comments (/* ... */) for JSONC support.
-    let stripped = strip_jsonc_comments(&content);
+    parse_settings(&content).with_context(|| {
+        format!(
+            "failed to parse {} (use --force-ide to back it up and start fresh)",
+            path.display()
+        )
+    })
+}
+
+/// The text of `path`, checked to parse as a settings object; `{}` when it does
+/// not exist. With `force`, an unusable file is moved aside to the first free
+/// `settings.json.cloak-bak[.N]` and `{}` returned in its place.
+fn read_settings(path: &Path, force: bool) -> Result<String> {
+    if !path.exists() {
+        return Ok("{}".to_string());
+    }
+
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+
+    match parse_settings(&content) {
+        Ok(_) => Ok(content),
+        Err(e) if force => {
+            let backup = mover::backup_path(path);
+            fs::rename(path, &backup).with_context(|| {
+                format!(
+                    "failed to back up {} -> {}",
+                    path.display(),
+                    backup.display()
+                )
+            })?;
+            eprintln!(
+                "Warning: {} is unusable ({e}); backed up to {}",
+                path.display(),
+                backup.display()
+            );
+            Ok("{}".to_string())
+        }
+        Err(e) => Err(e).with_context(|| {
+            format!(
+                "failed to parse {} (use --force-ide to back it up and start fresh)",
+                path.display()
+            )
+        }),
+    }
+}
 
-    let value: Value = serde_json::from_str(&stripped)
-        .with_context(|| format!("failed to parse {}", path.display()))?;
+/// Parse JSONC settings. The top level must be an object: anything else would
+/// be silently discarded when we write our excludes back.
+fn parse_settings(content: &str) -> Result<Map<String, Value>> {
+    // Strip single-line comments (// ...) and block comments (/* ... */) for JSONC support.
+    let stripped = strip_trailing_commas(&strip_jsonc_comments(content));
 
-    match value {
+    match serde_json::from_str(&stripped)? {
         Value::Object(map) => Ok(map),
-        _ => Ok(Map::new()),
+        _ => bail!("top-level value is not a JSON object"),
     }
 }
 
-fn save_settings(path: &Path, settings: &Map<String, Value>) -> Result<()> {
+fn write_settings(path: &Path, content: &str) -> Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("failed to create directory: {}", parent.display()))?;
     }
 
-    let content = serde_json::to_string_pretty(&Value::Object(settings.clone()))
-        .context("failed to serialize settings")?;
-
     fs::write(path, content.as_bytes())
         .with_context(|| format!("failed to write {}", path.display()))?;
 
     Ok(())
 }
 
+/// `content` with `"<key>": true` in its `section` object (`files.exclude`, ...),
+/// which is added after the last setting if missing. A section that is not an
+/// object is left alone.
+fn insert_exclude(content: &str, section: &str, key: &str) -> Result<String> {
+    let root = root_object(content)?;
+    let unit = indent_unit(content, &root);
+    let Some(member) = root.members.iter().rev().find(|m| m.key == section) else {
+        let indent = member_indent(content, &root, &unit);
+        let eol = line_ending(content);
+        let value = format!(
+            "{{{eol}{indent}{unit}{}: true{eol}{indent}}}",
+            json_key(key)
+        );
+        return Ok(insert_member(content, &root, section, &value, &unit));
+    };
+    let Some(exclude) = section_object(content, member)? else {
+        return Ok(content.to_string());
+    };
+
+    let mut edited = content.to_string();
+    match exclude.members.iter().rev().find(|m| m.key == key) {
+        Some(m) if &content[m.value_start..m.value_end] == "true" => {}
+        Some(m) => edited.replace_range(m.value_start..m.value_end, "true"),
+        None => edited = insert_member(content, &exclude, key, "true", &unit),
+    }
+    Ok(edited)
+}
+
+/// `content` without `keys` in its `section` object. If that empties the object,
+/// the section goes too unless `keep_empty` (the user had written it empty); an
+/// empty one with none of `keys` is never touched.
+fn remove_excludes(
+    content: &str,
+    section: &str,
+    keys: &[&str],
+    keep_empty: bool,
+) -> Result<String> {
+    let mut edited = content.to_string();
+    let mut removed = false;
+
+    for key in keys {
+        loop {
+            let root = root_object(&edited)?;
+            let Some(member) = root.members.iter().rev().find(|m| m.key == section) else {
+                return Ok(edited);
+            };
+            let Some(exclude) = section_object(&edited, member)? else {
+                return Ok(edited);
+            };
+            let Some(index) = exclude.members.iter().rposition(|m| m.key == *key) else {
+                break;
+            };
+            edited = remove_member(&edited, &exclude, index);
+            removed = true;
+        }
+    }
+
+    let root = root_object(&edited)?;
+    if removed
+        && !keep_empty
+        && let Some(index) = root.members.iter().rposition(|m| m.key == section)
+    {
+        let emptied = section_object(&edited, &root.members[index])?
+            .is_some_and(|exclude| exclude.members.is_empty());
+        if emptied {
+            edited = remove_member(&edited, &root, index);
+        }
+    }
+    Ok(edited)
+}
+
+/// Whether `content` has a `section` holding an empty object.
+fn is_empty_section(content: &str, section: &str) -> Result<bool> {
+    let root = root_object(content)?;
+    let Some(member) = root.members.iter().rev().find(|m| m.key == section) else {
+        return Ok(false);
+    };
+    Ok(section_object(content, member)?.is_some_and(|exclude| exclude.members.is_empty()))
+}
+
+fn empty_sections_path(root: &Path) -> PathBuf {
+    root.join(mover::CLOAK_DIR).join(EMPTY_SECTIONS_FILE)
+}
+
+/// The recorded empty sections, by settings file; none when the file does not exist.
+fn load_empty_sections(root: &Path) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let path = empty_sections_path(root);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Write the record, removing the file once nothing is left in it.
+fn save_empty_sections(root: &Path, kept: &BTreeMap<String, BTreeSet<String>>) -> Result<()> {
+    let path = empty_sections_path(root);
+    if kept.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("failed to remove {}", path.display()))?;
+        }
+        return Ok(());
+    }
+    let mut content = serde_json::to_string_pretty(kept).context("failed to serialize")?;
+    content.push('\n');
+    write_settings(&path, &content)
+}
+
+/// One `"key": value` member of a JSONC object, as byte offsets into the text.
+struct Member {
+    key: String,
+    key_start: usize,
+    value_start: usize,
+    value_end: usize,
+    /// The comma after the value, if there is one.
+    comma: Option<usize>,
+}
+
+/// A JSONC object: the offsets of its braces and its members in order.
+struct Object {
+    open: usize,
+    close: usize,
+    members: Vec<Member>,
+}
+
+fn root_object(content: &str) -> Result<Object> {
+    let open = skip_trivia(content.as_bytes(), 0);
+    if content.as_bytes().get(open) != Some(&b'{') {
+        bail!("top-level value is not a JSON object");
+    }
+    parse_object(content, open)
+}
+
+/// The object `member` holds, or `None` if its value is something else.
+fn section_object(content: &str, member: &Member) -> Result<Option<Object>> {
+    if content.as_bytes()[member.value_start] != b'{' {
+        return Ok(None);
+    }
+    parse_object(content, member.value_start).map(Some)
+}
+
+/// Scan the object whose `{` is at `open`, recording where each member sits.
+fn parse_object(content: &str, open: usize) -> Result<Object> {
+    let bytes = content.as_bytes();
+    let mut members = Vec::new();
+    let mut i = skip_trivia(bytes, open + 1);
+
+    loop {
+        match bytes.get(i) {
+            Some(b'}') => {
+                return Ok(Object {
+                    open,
+                    close: i,
+                    members,
+                });
+            }
+            Some(b'"') => {
+                let key_start = i;
+                let key_end = skip_string(bytes, i)?;
+                let key: String = serde_json::from_str(&content[key_start..key_end])?;
+                i = skip_trivia(bytes, key_end);
+                if bytes.get(i) != Some(&b':') {
+                    bail!("expected ':' after {key:?}");
+                }
+                let value_start = skip_trivia(bytes, i + 1);
+                let value_end = skip_value(bytes, value_start)?;
+                i = skip_trivia(bytes, value_end);
+                let comma = (bytes.get(i) == Some(&b',')).then_some(i);
+                if comma.is_some() {
+                    i = skip_trivia(bytes, i + 1);
+                } else if bytes.get(i) != Some(&b'}') {
+                    bail!("expected ',' or '}}' after the value of {key:?}");
+                }
+                members.push(Member {
+                    key,
+                    key_start,
+                    value_start,
+                    value_end,
+                    comma,
+                });
+            }
+            _ => bail!("unexpected content at byte {i}"),
+        }
+    }
+}
+
+/// Skip whitespace and comments from `i`.
+fn skip_trivia(bytes: &[u8], mut i: usize) -> usize {
+    loop {
+        while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        if bytes[i..].starts_with(b"//") {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+        } else if bytes[i..].starts_with(b"/*") {
+            i = match bytes[i + 2..].windows(2).position(|w| w == b"*/") {
+                Some(at) => i + 2 + at + 2,
+                None => bytes.len(),
+            };
+        } else {
+            return i;
+        }
+    }
+}
+
+/// The offset just past the string literal starting at `i`.
+fn skip_string(bytes: &[u8], mut i: usize) -> Result<usize> {
+    i += 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Ok(i + 1),
+            _ => i += 1,
+        }
+    }
+    bail!("unterminated string")
+}
+
+/// The offset just past the value starting at `i`.
+fn skip_value(bytes: &[u8], mut i: usize) -> Result<usize> {
+    match bytes.get(i) {
+        Some(b'"') => skip_string(bytes, i),
+        Some(b'{' | b'[') => {
+            let mut depth = 0usize;
+            loop {
+                i = skip_trivia(bytes, i);
+                match bytes.get(i) {
+                    Some(b'"') => i = skip_string(bytes, i)?,
+                    Some(b'{' | b'[') => {
+                        depth += 1;
+                        i += 1;
+                    }
+                    Some(b'}' | b']') => {
+                        depth -= 1;
+                        i += 1;
+                        if depth == 0 {
+                            return Ok(i);
+                        }
+                    }
+                    Some(_) => i += 1,
+                    None => bail!("unterminated object or array"),
+                }
+            }
+        }
+        Some(_) => {
+            while bytes
+                .get(i)
+                .is_some_and(|b| !b.is_ascii_whitespace() && !b",}]/".contains(b))
+            {
+                i += 1;
+            }
+            Ok(i)
+        }
+        None => bail!("missing value"),
+    }
+}
+
+/// `content` with `"key": value` added after the last member of `object`. A
+/// member on its own line goes on a new line below it with the same indentation.
+fn insert_member(content: &str, object: &Object, key: &str, value: &str, unit: &str) -> String {
+    let indent = member_indent(content, object, unit);
+    let eol = line_ending(content);
+    let member = format!("{}: {value}", json_key(key));
+    let mut edited = content.to_string();
+
+    match object.members.last() {
+        Some(last) => {
+            let end = last.comma.map_or(last.value_end, |c| c + 1);
+            match content[end..object.close].find('\n') {
+                Some(newline) => {
+                    let trailing = if last.comma.is_some() { "," } else { "" };
+                    edited.insert_str(
+                        end + newline + 1,
+                        &format!("{indent}{member}{trailing}{eol}"),
+                    );
+                    if last.comma.is_none() {
+                        edited.insert(last.value_end, ',');
+                    }
+                }
+                None => match last.comma {
+                    Some(comma) => edited.insert_str(comma + 1, &format!(" {member},")),
+                    None => edited.insert_str(last.value_end, &format!(", {member}")),
+                },
+            }
+        }
+        None => match content[object.open + 1..object.close].find('\n') {
+            Some(newline) => edited.insert_str(
+                object.open + 1 + newline + 1,
+                &format!("{indent}{member}{eol}"),
+            ),
+            None => {
+                let outer = line_indent(content, object.open);
+                edited.insert_str(
+                    object.open + 1,
+                    &format!("{eol}{indent}{member}{eol}{outer}"),
+                );
+            }
+        },
+    }
+    edited
+}
+
+/// `content` without the member at `index` of `object`. A member on lines of its
+/// own is removed with those lines, so this undoes `insert_member` byte for byte.
+fn remove_member(content: &str, object: &Object, index: usize) -> String {
+    let bytes = content.as_bytes();
+    let member = &object.members[index];
+    let end = member.comma.map_or(member.value_end, |c| c + 1);
+    let line_start = content[..member.key_start].rfind('\n').map_or(0, |i| i + 1);
+    let before_blank = content[line_start..member.key_start]
+        .bytes()
+        .all(|b| b == b' ' || b == b'\t');
+    let line_end = content[end..]
+        .find('\n')
+        .filter(|&newline| content[end..end + newline].trim().is_empty());
+    let previous_comma = index.checked_sub(1).and_then(|i| object.members[i].comma);
+
+    // The only member, laid out the way `insert_member` fills `{}`: back to `{}`.
+    let outer = line_indent(content, object.open);
+    let eol = line_ending(content);
+    if object.members.len() == 1
+        && line_start > object.open
+        && content[object.open + 1..line_start] == *eol
+        && content[end..object.close] == format!("{eol}{outer}")
+    {
+        let mut edited = content.to_string();
+        edited.replace_range(object.open + 1..object.close, "");
+        return edited;
+    }
+
+    let mut ranges = Vec::new();
+    match (before_blank, line_end, previous_comma) {
+        (true, Some(newline), previous_comma) => {
+            ranges.push(line_start..end + newline + 1);
+            if member.comma.is_none()
+                && let Some(comma) = previous_comma
+            {
+                ranges.push(comma..comma + 1);
+            }
+        }
+        (_, _, Some(comma)) => match member.comma {
+            Some(own) => ranges.push(comma + 1..own + 1),
+            None => ranges.push(comma..member.value_end),
+        },
+        (_, _, None) => {
+            let mut end = end;
+            if member.comma.is_some() {
+                while bytes.get(end).is_some_and(|b| *b == b' ' || *b == b'\t') {
+                    end += 1;
+                }
+            }
+            ranges.push(member.key_start..end);
+        }
+    }
+
+    let mut edited = content.to_string();
+    // Later ranges first, so earlier offsets stay valid.
+    ranges.sort_by_key(|range| std::cmp::Reverse(range.start));
+    for range in ranges {
+        edited.replace_range(range, "");
+    }
+    edited
+}
+
+/// The indentation for a new member of `object`: that of its last member if it
+/// starts its own line, otherwise one `unit` deeper than the line holding `{`.
+fn member_indent(content: &str, object: &Object, unit: &str) -> String {
+    if let Some(last) = object.members.last() {
+        let line_start = content[..last.key_start].rfind('\n').map_or(0, |i| i + 1);
+        let before = &content[line_start..last.key_start];
+        if before.bytes().all(|b| b == b' ' || b == b'\t') {
+            return before.to_string();
+        }
+    }
+    format!("{}{unit}", line_indent(content, object.open))
+}
+
+/// One level of indentation as the file uses it, judged by the first setting.
+fn indent_unit(content: &str, root: &Object) -> String {
+    root.members
+        .first()
+        .map(|first| line_indent(content, first.key_start))
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or_else(|| "  ".to_string())
+}
+
+/// The leading whitespace of the line holding byte `at`.
+fn line_indent(content: &str, at: usize) -> String {
+    let line_start = content[..at].rfind('\n').map_or(0, |i| i + 1);
+    content[line_start..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+fn line_ending(content: &str) -> &'static str {
+    if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+fn json_key(key: &str) -> String {
+    Value::String(key.to_string()).to_string()
+}
+
 /// Minimal JSONC comment stripper that handles `//` and `/* */` comments
 /// while respecting string literals.
 fn strip_jsonc_comments(input: &str) -> String {
@@ -158,6 +758,41 @@ fn strip_jsonc_comments(input: &str) -> String {
     out
 }
 
+/// Drop commas that directly precede a closing `}` or `]` (allowed in JSONC),
+/// ignoring string contents. Expects comments to be stripped already.
+fn strip_trailing_commas(input: &str) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    let mut out = String::with_capacity(input.len());
+    let mut in_string = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if in_string {
+            out.push(c);
+            if c == '\\' && i + 1 < chars.len() {
+                out.push(chars[i + 1]);
+                i += 1;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+            out.push(c);
+        } else if c == ',' {
+            let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+            if !matches!(next, Some('}') | Some(']')) {
+                out.push(c);
+            }
+        } else {
+            out.push(c);
+        }
+        i += 1;
+    }
+
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -192,6 +827,44 @@ mod tests {
         assert_eq!(parsed["v"], 1);
     }
 
+    #[test]
+    fn parse_settings_accepts_trailing_commas_and_rejects_non_objects() {
+        let map = parse_settings("{\n  \"a\": [1, 2,],\n  \"b\": \"x,}\",\n}\n")
+            .expect("trailing commas should parse");
+        assert_eq!(map["b"], "x,}");
+        assert!(parse_settings("[1, 2]").is_err());
+    }
+
+    #[test]
+    fn force_backs_up_malformed_settings_and_writes_fresh_excludes() {
+        let root = make_temp_dir("ide-force");
+        let vscode = root.join(".vscode");
+        fs::create_dir_all(&vscode).expect("create .vscode failed");
+        let malformed = "{ \"editor.tabSize\": 2 \"oops\" }";
+        fs::write(vscode.join("settings.json"), malformed).expect("write settings failed");
+
+        assert!(add_ide_exclude(&root, ".idea", &IdeOptions::default()).is_err());
+        assert_eq!(
+            fs::read_to_string(vscode.join("settings.json")).expect("read failed"),
+            malformed,
+            "default mode must leave the broken file alone"
+        );
+
+        add_ide_exclude(&root, ".idea", &IdeOptions { force: true }).expect("forced add failed");
+
+        assert_eq!(
+            fs::read_to_string(vscode.join("settings.json.cloak-bak")).expect("backup missing"),
+            malformed
+        );
+        let fresh: Value = serde_json::from_str(
+            &fs::read_to_string(vscode.join("settings.json")).expect("read failed"),
+        )
+        .expect("fresh settings should be valid JSON");
+        assert_eq!(fresh["files.exclude"]["**/.idea"], true);
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
     #[test]
     fn add_and_remove_ide_exclude_round_trip() {
         let root = make_temp_dir("ide-roundtrip");
@@ -208,11 +881,11 @@ mod tests {
         .expect("write vscode settings failed");
         fs::write(
             cursor.join("settings.json"),
-            "{\n  // comment\n  \"foo\": 1\n}\n",
+            "{\n  // comment\n  \"foo\": 1,\n  \"search.exclude\": { \"**/dist\": true }\n}\n",
         )
         .expect("write cursor settings failed");
 
-        add_ide_exclude(&root, ".cursor").expect("add_ide_exclude failed");
+        add_ide_exclude(&root, ".cursor", &IdeOptions::default()).expect("add_ide_exclude failed");
 
         let vscode_json: Value = serde_json::from_str(
             &fs::read_to_string(vscode.join("settings.json")).expect("read vscode settings failed"),
@@ -220,18 +893,138 @@ mod tests {
         .expect("parse vscode settings failed");
         assert_eq!(vscode_json["files.exclude"]["**/.cursor"], true);
 
-        let cursor_json: Value = serde_json::from_str(
+        let cursor_json = parse_settings(
             &fs::read_to_string(cursor.join("settings.json")).expect("read cursor settings failed"),
         )
         .expect("parse cursor settings failed");
         assert_eq!(cursor_json["files.exclude"]["**/.cursor"], true);
+        assert_eq!(cursor_json["search.exclude"]["**/.cursor"], true);
+        assert_eq!(cursor_json["search.exclude"]["**/dist"], true);
+        assert_eq!(vscode_json["search.exclude"]["**/.cursor"], true);
 
         remove_ide_exclude(&root, ".cursor").expect("remove_ide_exclude failed");
         let vscode_after: Value = serde_json::from_str(
             &fs::read_to_string(vscode.join("settings.json")).expect("read vscode settings failed"),
         )
         .expect("parse vscode settings failed");
-        assert!(vscode_after["files.exclude"]["**/.cursor"].is_null());
+        assert!(
+            vscode_after.get("files.exclude").is_none(),
+            "{vscode_after}"
+        );
+        assert!(
+            vscode_after.get("search.exclude").is_none(),
+            "{vscode_after}"
+        );
+        assert_eq!(vscode_after["editor.tabSize"], 2);
+        let cursor_after = parse_settings(
+            &fs::read_to_string(cursor.join("settings.json")).expect("read cursor settings failed"),
+        )
+        .expect("parse cursor settings failed");
+        assert!(!cursor_after.contains_key("files.exclude"));
+        let search = cursor_after["search.exclude"]
+            .as_object()
+            .expect("the user's search.exclude should stay");
+        assert!(!search.contains_key("**/.cursor"), "{search:?}");
+        assert_eq!(search["**/dist"], true);
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn removing_last_exclude_drops_the_empty_object_only_if_cloak_emptied_it() {
+        let root = make_temp_dir("ide-empty-exclude");
+        let vscode = root.join(".vscode");
+        fs::create_dir_all(&vscode).expect("create .vscode failed");
+        let settings_path = vscode.join("settings.json");
+        fs::write(&settings_path, "{\n  \"editor.tabSize\": 2\n}\n").expect("write failed");
+
+        add_ide_exclude(&root, ".claude", &IdeOptions::default()).expect("add failed");
+        add_ide_exclude(&root, ".codex", &IdeOptions::default()).expect("add failed");
+
+        remove_ide_exclude(&root, ".claude").expect("remove failed");
+        let json: Value = serde_json::from_str(&fs::read_to_string(&settings_path).unwrap())
+            .expect("parse failed");
+        assert_eq!(json["files.exclude"]["**/.codex"], true);
+
+        remove_ide_exclude(&root, ".codex").expect("remove failed");
+        let json: Value = serde_json::from_str(&fs::read_to_string(&settings_path).unwrap())
+            .expect("parse failed");
+        assert!(json.get("files.exclude").is_none(), "{json}");
+        assert_eq!(json["editor.tabSize"], 2);
+
+        // An empty object the user wrote is left alone.
+        fs::write(&settings_path, "{\"files.exclude\": {}}").expect("write failed");
+        remove_ide_exclude(&root, ".codex").expect("remove failed");
+        let json: Value = serde_json::from_str(&fs::read_to_string(&settings_path).unwrap())
+            .expect("parse failed");
+        assert!(json["files.exclude"].is_object());
+
+        // ...and survives a key being added to it and removed again.
+        let original = "{\n  \"files.exclude\": {},\n  \"editor.tabSize\": 2\n}\n";
+        fs::write(&settings_path, original).expect("write failed");
+        add_ide_exclude(&root, ".codex", &IdeOptions::default()).expect("add failed");
+        add_ide_exclude(&root, ".claude", &IdeOptions::default()).expect("add failed");
+        let json: Value = serde_json::from_str(&fs::read_to_string(&settings_path).unwrap())
+            .expect("parse failed");
+        assert_eq!(json["files.exclude"]["**/.codex"], true);
+        remove_ide_exclude(&root, ".codex").expect("remove failed");
+        remove_ide_exclude(&root, ".claude").expect("remove failed");
+        let json: Value = serde_json::from_str(&fs::read_to_string(&settings_path).unwrap())
+            .expect("parse failed");
+        assert_eq!(json["files.exclude"], serde_json::json!({}), "{json}");
+        assert_eq!(
+            fs::read_to_string(&settings_path).expect("read failed"),
+            original
+        );
+        assert!(!empty_sections_path(&root).exists());
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn edits_keep_comments_and_order_and_round_trip_byte_identical() {
+        let root = make_temp_dir("ide-preserve");
+        let vscode = root.join(".vscode");
+        fs::create_dir_all(&vscode).expect("create .vscode failed");
+        let settings_path = vscode.join("settings.json");
+
+        let originals = [
+            "{\n    // Team formatting rules\n    \"editor.tabSize\": 4,\n    \"a.setting\": [1, 2] // why\n}\n",
+            "{\n\t\"zeta\": 1,\n\t\"files.exclude\": {\n\t\t/* build output */\n\t\t\"**/dist\": true,\n\t},\n\t\"alpha\": 2\n}",
+            "{\"files.exclude\": {\"**/out\": true}, \"b\": 1}",
+            "{\r\n  \"x\": \"// not a comment\"\r\n}\r\n",
+            "{}",
+        ];
+        for original in originals {
+            fs::write(&settings_path, original).expect("write failed");
+
+            add_ide_exclude(&root, ".cursor", &IdeOptions::default()).expect("add failed");
+            let added = fs::read_to_string(&settings_path).expect("read failed");
+            let parsed = parse_settings(&added).expect("edited settings must parse");
+            assert_eq!(parsed["files.exclude"]["**/.cursor"], true, "{added}");
+            assert_eq!(parsed["search.exclude"]["**/.cursor"], true, "{added}");
+            let kept = parse_settings(original).expect("original must parse");
+            for key in kept
+                .keys()
+                .filter(|k| !EXCLUDE_SECTIONS.contains(&k.as_str()))
+            {
+                assert_eq!(parsed[key], kept[key], "{added}");
+            }
+            for comment in ["// Team formatting rules", "// why", "/* build output */"] {
+                assert_eq!(
+                    added.contains(comment),
+                    original.contains(comment),
+                    "{added}"
+                );
+            }
+
+            remove_ide_exclude(&root, ".cursor").expect("remove failed");
+            assert_eq!(
+                fs::read_to_string(&settings_path).expect("read failed"),
+                original,
+                "added:\n{added}"
+            );
+        }
 
         fs::remove_dir_all(root).expect("cleanup failed");
     }
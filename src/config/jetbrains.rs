@@ -0,0 +1,424 @@
+use crate::utils::output::trace;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The JetBrains project directory; nothing is edited unless it already exists.
+pub const IDEA_DIR: &str = ".idea";
+const MODULES_XML: &str = "modules.xml";
+/// The content root of a module whose `.iml` lives in the project root or in `.idea`.
+const CONTENT_URL: &str = "file://$MODULE_DIR$";
+
+/// The `.iml` files of the modules rooted at the project root, i.e. those with a
+/// `<content url="file://$MODULE_DIR$">` entry and living in the root or `.idea`.
+///
+/// Modules are read from `.idea/modules.xml`, falling back to `.idea/*.iml`. A
+/// `.idea` that is a symlink (hidden by cloak itself) is left alone.
+pub fn module_files(root: &Path) -> Vec<PathBuf> {
+    let idea = root.join(IDEA_DIR);
+    if !idea.symlink_metadata().is_ok_and(|m| m.is_dir()) {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<PathBuf> = match fs::read_to_string(idea.join(MODULES_XML)) {
+        Ok(xml) => tags(&xml)
+            .into_iter()
+            .filter(|tag| tag.name == "module" && !tag.closing)
+            .filter_map(|tag| tag.attr("filepath").map(str::to_string))
+            .map(|path| PathBuf::from(path.replace("$PROJECT_DIR$", &root.to_string_lossy())))
+            .collect(),
+        Err(_) => fs::read_dir(&idea)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "iml"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+    candidates.sort();
+    candidates.dedup();
+
+    candidates
+        .into_iter()
+        .filter(|path| path.parent() == Some(root) || path.parent() == Some(idea.as_path()))
+        .filter(|path| {
+            fs::read_to_string(path).is_ok_and(|xml| content_root(&tags(&xml)).is_some())
+        })
+        .collect()
+}
+
+/// Add an `excludeFolder` for `target` to every project-root module.
+pub fn add_exclude(root: &Path, target: &str) -> Result<()> {
+    for path in module_files(root) {
+        edit(&path, |xml| insert_exclude(xml, target))?;
+    }
+    Ok(())
+}
+
+/// Remove the `excludeFolder` for `target` from every project-root module.
+pub fn remove_exclude(root: &Path, target: &str) -> Result<()> {
+    for path in module_files(root) {
+        edit(&path, |xml| remove_exclude_folder(xml, target))?;
+    }
+    Ok(())
+}
+
+/// Whether every project-root module excludes `target`; `None` without any module.
+pub fn is_excluded(root: &Path, target: &str) -> Result<Option<bool>> {
+    let modules = module_files(root);
+    if modules.is_empty() {
+        return Ok(None);
+    }
+    for path in modules {
+        let xml = read(&path)?;
+        if !excluded_names_in(&xml).iter().any(|name| name == target) {
+            return Ok(Some(false));
+        }
+    }
+    Ok(Some(true))
+}
+
+/// Top-level names excluded in each project-root module, keyed by the module's
+/// path relative to `root`.
+pub fn excluded_names(root: &Path) -> Vec<(String, String)> {
+    let mut names = Vec::new();
+    for path in module_files(root) {
+        let Ok(xml) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let label = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned();
+        names.extend(
+            excluded_names_in(&xml)
+                .into_iter()
+                .map(|name| (label.clone(), name)),
+        );
+    }
+    names
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// Apply `change` to the module at `path`, writing it back only if it changed.
+fn edit(path: &Path, change: impl Fn(&str) -> String) -> Result<()> {
+    let xml = read(path)?;
+    let edited = change(&xml);
+    if edited != xml {
+        trace!("ide: update excludeFolder entries in {}", path.display());
+        fs::write(path, edited).with_context(|| format!("failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn exclude_url(target: &str) -> String {
+    format!("{CONTENT_URL}/{target}")
+}
+
+/// `xml` with an `excludeFolder` for `target` as the last child of the project
+/// content root, on a line of its own. Modules without that root are unchanged.
+fn insert_exclude(xml: &str, target: &str) -> String {
+    let tags = tags(xml);
+    let Some(content) = content_root(&tags) else {
+        return xml.to_string();
+    };
+    if excluded_names_in(xml).iter().any(|name| name == target) {
+        return xml.to_string();
+    }
+
+    let open = &tags[content];
+    let eol = if xml.contains("\r\n") { "\r\n" } else { "\n" };
+    let indent = line_indent(xml, open.start);
+    let folder = format!("<excludeFolder url=\"{}\" />", escape(&exclude_url(target)));
+
+    let mut edited = xml.to_string();
+    if open.self_closing {
+        let tag = xml[open.start..open.end]
+            .trim_end_matches('>')
+            .trim_end_matches('/')
+            .trim_end();
+        edited.replace_range(
+            open.start..open.end,
+            &format!("{tag}>{eol}{indent}  {folder}{eol}{indent}</content>"),
+        );
+        return edited;
+    }
+
+    let Some(close) = closing_tag(&tags, content) else {
+        return xml.to_string();
+    };
+    let close_line = line_start(xml, close.start);
+    if xml[close_line..close.start].trim().is_empty() {
+        edited.insert_str(close_line, &format!("{indent}  {folder}{eol}"));
+    } else {
+        edited.insert_str(close.start, &folder);
+    }
+    edited
+}
+
+/// `xml` without the `excludeFolder` for `target`, undoing `insert_exclude`: a
+/// content root left with no children goes back to a self-closing tag.
+fn remove_exclude_folder(xml: &str, target: &str) -> String {
+    let url = exclude_url(target);
+    let mut edited = xml.to_string();
+
+    loop {
+        let parsed = tags(&edited);
+        let Some(content) = content_root(&parsed) else {
+            return edited;
+        };
+        let Some(close) = closing_tag(&parsed, content) else {
+            return edited;
+        };
+        let Some(folder) = parsed[content + 1..]
+            .iter()
+            .take_while(|tag| tag.start < close.start)
+            .find(|tag| tag.name == "excludeFolder" && tag.attr("url") == Some(url.as_str()))
+        else {
+            break;
+        };
+
+        let start = line_start(&edited, folder.start);
+        let line_end = edited[folder.end..]
+            .find('\n')
+            .filter(|&newline| edited[folder.end..folder.end + newline].trim().is_empty());
+        match line_end {
+            Some(newline) if edited[start..folder.start].trim().is_empty() => {
+                edited.replace_range(start..folder.end + newline + 1, "")
+            }
+            _ => edited.replace_range(folder.start..folder.end, ""),
+        }
+
+        let parsed = tags(&edited);
+        if let Some(content) = content_root(&parsed)
+            && let Some(close) = closing_tag(&parsed, content)
+        {
+            let open = &parsed[content];
+            if !open.self_closing && edited[open.end..close.start].trim().is_empty() {
+                let tag = edited[open.start..open.end]
+                    .trim_end_matches('>')
+                    .to_string();
+                edited.replace_range(open.start..close.end, &format!("{tag} />"));
+            }
+        }
+    }
+    edited
+}
+
+/// Names directly under the project root that the content root excludes.
+fn excluded_names_in(xml: &str) -> Vec<String> {
+    let tags = tags(xml);
+    let Some(content) = content_root(&tags) else {
+        return Vec::new();
+    };
+    let end = closing_tag(&tags, content).map_or(xml.len(), |close| close.start);
+    let prefix = format!("{CONTENT_URL}/");
+
+    tags[content + 1..]
+        .iter()
+        .take_while(|tag| tag.start < end)
+        .filter(|tag| tag.name == "excludeFolder" && !tag.closing)
+        .filter_map(|tag| tag.attr("url")?.strip_prefix(&prefix).map(str::to_string))
+        .filter(|name| !name.is_empty() && !name.contains('/'))
+        .collect()
+}
+
+/// One XML tag: where it sits in the text and what it says.
+struct Tag {
+    name: String,
+    start: usize,
+    /// Just past the closing `>`.
+    end: usize,
+    closing: bool,
+    self_closing: bool,
+    attrs: Vec<(String, String)>,
+}
+
+impl Tag {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The index of the `<content>` tag for the project root, if the module has one.
+fn content_root(tags: &[Tag]) -> Option<usize> {
+    tags.iter().position(|tag| {
+        tag.name == "content" && !tag.closing && tag.attr("url") == Some(CONTENT_URL)
+    })
+}
+
+/// The `</content>` that closes the (non-self-closing) tag at `open`.
+fn closing_tag(tags: &[Tag], open: usize) -> Option<&Tag> {
+    if tags[open].self_closing {
+        return None;
+    }
+    tags[open + 1..]
+        .iter()
+        .find(|tag| tag.closing && tag.name == tags[open].name)
+}
+
+/// Every tag in `xml`, skipping comments, CDATA, declarations and processing
+/// instructions. Enough of XML for the files JetBrains writes.
+fn tags(xml: &str) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    let mut i = 0;
+
+    while let Some(offset) = xml[i..].find('<') {
+        let start = i + offset;
+        let rest = &xml[start..];
+        let skip_to = |end: &str| {
+            rest.find(end)
+                .map_or(xml.len(), |at| start + at + end.len())
+        };
+        if rest.starts_with("<!--") {
+            i = skip_to("-->");
+            continue;
+        }
+        if rest.starts_with("<![CDATA[") {
+            i = skip_to("]]>");
+            continue;
+        }
+        if rest.starts_with("<?") || rest.starts_with("<!") {
+            i = skip_to(">");
+            continue;
+        }
+
+        let Some(end) = tag_end(xml, start) else {
+            break;
+        };
+        let inner = &xml[start + 1..end - 1];
+        let closing = inner.starts_with('/');
+        let self_closing = inner.ends_with('/');
+        let body = inner.trim_start_matches('/').trim_end_matches('/');
+        let name_len = body.find(char::is_whitespace).unwrap_or(body.len());
+        tags.push(Tag {
+            name: body[..name_len].to_string(),
+            start,
+            end,
+            closing,
+            self_closing,
+            attrs: attributes(&body[name_len..]),
+        });
+        i = end;
+    }
+    tags
+}
+
+/// Just past the `>` ending the tag at `start`, ignoring `>` inside quoted values.
+fn tag_end(xml: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (offset, c) in xml[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(start + offset + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn attributes(text: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start();
+        let Some(eq) = rest.find('=') else {
+            return attrs;
+        };
+        let name = rest[..eq].trim().to_string();
+        rest = rest[eq + 1..].trim_start();
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            return attrs;
+        };
+        let Some(close) = rest[1..].find(quote) else {
+            return attrs;
+        };
+        attrs.push((name, unescape(&rest[1..1 + close])));
+        rest = &rest[close + 2..];
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn line_start(text: &str, at: usize) -> usize {
+    text[..at].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// The leading whitespace of the line holding byte `at`.
+fn line_indent(text: &str, at: usize) -> String {
+    text[line_start(text, at)..]
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<module type="WEB_MODULE" version="4">
+  <component name="NewModuleRootManager">
+    <content url="file://$MODULE_DIR$">
+      <!-- <excludeFolder url="file://$MODULE_DIR$/commented" /> -->
+      <excludeFolder url="file://$MODULE_DIR$/dist" />
+    </content>
+    <orderEntry type="sourceFolder" forTests="false" />
+  </component>
+</module>
+"#;
+
+    #[test]
+    fn exclude_folder_round_trips_byte_identical() {
+        let added = insert_exclude(MODULE, ".cursor");
+        assert!(
+            added.contains(
+                "      <excludeFolder url=\"file://$MODULE_DIR$/.cursor\" />\n    </content>"
+            ),
+            "{added}"
+        );
+        assert_eq!(excluded_names_in(&added), vec!["dist", ".cursor"]);
+        assert_eq!(insert_exclude(&added, ".cursor"), added);
+        assert_eq!(remove_exclude_folder(&added, ".cursor"), MODULE);
+
+        let empty = MODULE.replace(
+            "<content url=\"file://$MODULE_DIR$\">\n      <!-- <excludeFolder url=\"file://$MODULE_DIR$/commented\" /> -->\n      <excludeFolder url=\"file://$MODULE_DIR$/dist\" />\n    </content>",
+            "<content url=\"file://$MODULE_DIR$\" />",
+        );
+        let added = insert_exclude(&empty, ".cursor");
+        assert_eq!(excluded_names_in(&added), vec![".cursor"]);
+        assert_eq!(remove_exclude_folder(&added, ".cursor"), empty);
+    }
+
+    #[test]
+    fn modules_without_a_project_content_root_are_left_alone() {
+        let xml =
+            "<module><component><content url=\"file://$MODULE_DIR$/sub\" /></component></module>";
+        assert_eq!(insert_exclude(xml, ".cursor"), xml);
+        assert!(excluded_names_in(xml).is_empty());
+    }
+}
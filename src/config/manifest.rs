@@ -0,0 +1,139 @@
+use crate::utils::toml::{self, Value};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::Path;
+
+const PACKAGE_JSON: &str = "package.json";
+const PYPROJECT: &str = "pyproject.toml";
+const PYPROJECT_TABLE: &str = "tool.cloak";
+
+/// Targets declared in the project's own manifests: the `cloak` array in
+/// `package.json` and `targets` under `[tool.cloak]` in `pyproject.toml`.
+///
+/// Both are read when present; duplicates keep their first position. Declaring
+/// nothing in either is an error so `hide --from-manifest` never silently no-ops.
+pub fn targets(root: &Path) -> Result<Vec<String>> {
+    let mut found: Option<Vec<String>> = None;
+
+    let package = root.join(PACKAGE_JSON);
+    if package.exists() {
+        let content = fs::read_to_string(&package)
+            .with_context(|| format!("failed to read {}", package.display()))?;
+        if let Some(list) = package_json_targets(&content)
+            .with_context(|| format!("failed to parse {}", package.display()))?
+        {
+            found.get_or_insert_with(Vec::new).extend(list);
+        }
+    }
+
+    let pyproject = root.join(PYPROJECT);
+    if pyproject.exists() {
+        let content = fs::read_to_string(&pyproject)
+            .with_context(|| format!("failed to read {}", pyproject.display()))?;
+        if let Some(list) = pyproject_targets(&content)
+            .with_context(|| format!("failed to parse {}", pyproject.display()))?
+        {
+            found.get_or_insert_with(Vec::new).extend(list);
+        }
+    }
+
+    let Some(mut list) = found else {
+        bail!(
+            "no cloak targets declared: add a `cloak` array to {PACKAGE_JSON} \
+             or `targets` under [{PYPROJECT_TABLE}] in {PYPROJECT}"
+        );
+    };
+    let mut seen = std::collections::HashSet::new();
+    list.retain(|target| seen.insert(target.clone()));
+    Ok(list)
+}
+
+fn package_json_targets(content: &str) -> Result<Option<Vec<String>>> {
+    let doc: serde_json::Value = serde_json::from_str(content)?;
+    let Some(value) = doc.get("cloak") else {
+        return Ok(None);
+    };
+    let Some(items) = value.as_array() else {
+        bail!("`cloak` must be an array of target names");
+    };
+    items
+        .iter()
+        .map(|item| match item.as_str() {
+            Some(name) => Ok(name.to_string()),
+            None => bail!("`cloak` must only contain strings, found {item}"),
+        })
+        .collect::<Result<_>>()
+        .map(Some)
+}
+
+/// Only the `[tool.cloak]` table is parsed: the rest of a pyproject may use TOML
+/// that cloak's small parser does not support, and none of it concerns us.
+fn pyproject_targets(content: &str) -> Result<Option<Vec<String>>> {
+    let Some(section) = table_section(content, PYPROJECT_TABLE) else {
+        return Ok(None);
+    };
+    let doc = toml::parse(&section)?;
+    let Some(value) = doc.get("targets") else {
+        return Ok(None);
+    };
+    let Value::Array(items) = value else {
+        bail!("`{PYPROJECT_TABLE}.targets` must be an array of target names");
+    };
+    items
+        .iter()
+        .map(|item| match item.as_str() {
+            Some(name) => Ok(name.to_string()),
+            None => bail!("`{PYPROJECT_TABLE}.targets` must only contain strings"),
+        })
+        .collect::<Result<_>>()
+        .map(Some)
+}
+
+/// The body of `[table]`: every line after its header up to the next header.
+fn table_section(content: &str, table: &str) -> Option<String> {
+    let is_header = |line: &str| {
+        let line = line.trim();
+        line.starts_with('[') && line.ends_with(']') && !line.contains(['"', '\''])
+    };
+
+    let mut lines = content.lines();
+    lines
+        .by_ref()
+        .find(|line| is_header(line) && line.trim().trim_matches(['[', ']']).trim() == table)?;
+    let body: Vec<&str> = lines.take_while(|line| !is_header(line)).collect();
+    Some(body.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pyproject_reads_only_the_cloak_table() {
+        let content = r#"
+[project]
+name = "demo"
+version = "1.0"
+requires-python = ">=3.10"
+classifiers = { unsupported = "inline table" }
+
+[tool.cloak]
+targets = [
+  ".cursor",
+  ".claude",
+]
+
+[[tool.other.items]]
+weight = 0.5
+"#;
+        assert_eq!(
+            pyproject_targets(content).expect("parse failed"),
+            Some(vec![".cursor".to_string(), ".claude".to_string()])
+        );
+        assert_eq!(
+            pyproject_targets("[project]\nname = \"x\"\n").unwrap(),
+            None
+        );
+        assert!(pyproject_targets("[tool.cloak]\ntargets = \".cursor\"\n").is_err());
+    }
+}
@@ -1 +1,6 @@
 pub mod ide;
+pub mod jetbrains;
+pub mod manifest;
+pub mod project;
+pub mod tool_cmd;
+pub mod tool_env;
@@ -0,0 +1,171 @@
+use crate::utils::toml;
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const CONFIG_FILE: &str = "cloak.toml";
+
+/// Top-level settings `load` understands; anything else is reported.
+const KEYS: &[&str] = &[
+    "snapshot_retention",
+    "gitignore",
+    "manage_gitignore",
+    "local_ignore",
+    "ide_exclude_cloak",
+    "known_dotfiles",
+    "tidy_exclude",
+    "ide_dirs",
+];
+/// Tables whose keys are free-form names.
+const TABLES: &[&str] = &["tool_commands"];
+
+/// Per-project settings loaded from `.cloak/cloak.toml`.
+///
+/// Every field has a default so a missing file behaves exactly like an empty one.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectConfig {
+    /// How many snapshots to keep per target (`None` keeps all of them).
+    pub snapshot_retention: Option<usize>,
+    /// `[tool_commands]`: target → command whose absence from `PATH` marks it stale.
+    /// An empty string opts the target out of the check.
+    pub tool_commands: BTreeMap<String, String>,
+    /// `gitignore = false` (or its alias `manage_gitignore = false`): never add or
+    /// remove per-target `.gitignore` rules, as if every `hide`/`unhide`/`tidy`
+    /// were run with `--no-gitignore`.
+    pub skip_gitignore: bool,
+    /// `local_ignore = true`: add per-target rules to `.git/info/exclude` instead of
+    /// `.gitignore`, as if every `hide`/`tidy` were run with `--local-ignore`.
+    pub local_ignore: bool,
+    /// `ide_exclude_cloak = false`: leave `.cloak` itself out of IDE `files.exclude`.
+    pub skip_ide_cloak_exclude: bool,
+    /// `known_dotfiles = [...]`: extra names `tidy` offers to hide, after the built-in list.
+    pub known_dotfiles: Vec<String>,
+    /// `tidy_exclude = [...]`: names `tidy` never hides, as if each were passed to
+    /// `--except`.
+    pub tidy_exclude: Vec<String>,
+    /// `ide_dirs = [...]`: IDE directories whose `settings.json` cloak manages,
+    /// replacing the built-in `.vscode` and `.cursor` (`None` keeps those).
+    pub ide_dirs: Option<Vec<String>>,
+}
+
+impl ProjectConfig {
+    /// Load `.cloak/cloak.toml`, falling back to defaults when it does not exist.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(".cloak").join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let doc =
+            toml::parse(&content).with_context(|| format!("failed to parse {}", path.display()))?;
+
+        if let Some(key) = doc.keys().find(|key| {
+            !KEYS.contains(key)
+                && !TABLES
+                    .iter()
+                    .any(|table| key.strip_prefix(table).is_some_and(|k| k.starts_with('.')))
+        }) {
+            bail!(
+                "{}: unknown setting `{key}` (expected one of: {})",
+                path.display(),
+                KEYS.join(", ")
+            );
+        }
+
+        let mut config = Self::default();
+
+        if let Some(value) = doc.get("snapshot_retention") {
+            let Some(n) = value.as_integer().filter(|n| *n > 0) else {
+                bail!(
+                    "{}: `snapshot_retention` must be a positive integer",
+                    path.display()
+                );
+            };
+            config.snapshot_retention = Some(n as usize);
+        }
+
+        let mut manage_gitignore = None;
+        for key in ["gitignore", "manage_gitignore"] {
+            let Some(value) = doc.get(key) else {
+                continue;
+            };
+            let toml::Value::Boolean(enabled) = value else {
+                bail!("{}: `{key}` must be true or false", path.display());
+            };
+            if manage_gitignore.is_some_and(|set| set != *enabled) {
+                bail!(
+                    "{}: `gitignore` and `manage_gitignore` disagree; set only one",
+                    path.display()
+                );
+            }
+            manage_gitignore = Some(*enabled);
+        }
+        config.skip_gitignore = manage_gitignore == Some(false);
+
+        if let Some(value) = doc.get("local_ignore") {
+            let toml::Value::Boolean(enabled) = value else {
+                bail!("{}: `local_ignore` must be true or false", path.display());
+            };
+            config.local_ignore = *enabled;
+        }
+
+        if let Some(value) = doc.get("ide_exclude_cloak") {
+            let toml::Value::Boolean(enabled) = value else {
+                bail!(
+                    "{}: `ide_exclude_cloak` must be true or false",
+                    path.display()
+                );
+            };
+            config.skip_ide_cloak_exclude = !enabled;
+        }
+
+        if let Some(value) = doc.get("known_dotfiles") {
+            config.known_dotfiles = string_list(value)
+                .with_context(|| format!("{}: `known_dotfiles`", path.display()))?;
+        }
+
+        if let Some(value) = doc.get("tidy_exclude") {
+            config.tidy_exclude = string_list(value)
+                .with_context(|| format!("{}: `tidy_exclude`", path.display()))?;
+        }
+
+        if let Some(value) = doc.get("ide_dirs") {
+            config.ide_dirs = Some(
+                string_list(value).with_context(|| format!("{}: `ide_dirs`", path.display()))?,
+            );
+        }
+
+        for (target, value) in doc.table("tool_commands") {
+            let Some(command) = value.as_str() else {
+                bail!(
+                    "{}: `tool_commands.{target}` must be a string",
+                    path.display()
+                );
+            };
+            config
+                .tool_commands
+                .insert(target.to_string(), command.to_string());
+        }
+
+        Ok(config)
+    }
+}
+
+/// An array of top-level names, e.g. `[".mytool", ".foo"]`.
+fn string_list(value: &toml::Value) -> Result<Vec<String>> {
+    let toml::Value::Array(items) = value else {
+        bail!("must be an array of names");
+    };
+    items
+        .iter()
+        .map(|item| match item.as_str() {
+            Some(name) if !name.is_empty() && !name.contains(['/', '\\']) && name != ".." => {
+                Ok(name.to_string())
+            }
+            _ => bail!("must only contain top-level names"),
+        })
+        .collect()
+}
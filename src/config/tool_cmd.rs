@@ -0,0 +1,116 @@
+use crate::config::project::ProjectConfig;
+use std::ffi::OsStr;
+use std::path::Path;
+
+/// Commands whose presence on `PATH` suggests the tool behind a known dotfile is
+/// still installed. Targets without a reliable CLI (extensions, IDE-only tools)
+/// are left out rather than guessed at.
+const TOOL_COMMANDS: &[(&str, &str)] = &[
+    (".cursor", "cursor"),
+    (".vscode", "code"),
+    (".windsurf", "windsurf"),
+    (".trae", "trae"),
+    (".zed", "zed"),
+    (".claude", "claude"),
+    (".codex", "codex"),
+    (".gemini", "gemini"),
+    (".amazonq", "q"),
+    (".augment", "auggie"),
+    (".codebuddy", "codebuddy"),
+    (".kimi", "kimi"),
+];
+
+/// The command that indicates `target`'s tool is installed, if one is known.
+///
+/// `[tool_commands]` in `.cloak/cloak.toml` overrides the built-in table; an empty
+/// string there disables the check for that target.
+pub fn command_for<'a>(target: &str, config: &'a ProjectConfig) -> Option<&'a str> {
+    let command = match config.tool_commands.get(target) {
+        Some(command) => command.as_str(),
+        None => TOOL_COMMANDS
+            .iter()
+            .find(|(name, _)| *name == target)
+            .map(|(_, command)| *command)?,
+    };
+    (!command.is_empty()).then_some(command)
+}
+
+/// Whether `command` resolves to an executable in the process's `PATH`.
+pub fn is_installed(command: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| on_path(command, &path))
+}
+
+fn on_path(command: &str, path: &OsStr) -> bool {
+    std::env::split_paths(path).any(|dir| {
+        candidates(command)
+            .iter()
+            .any(|name| is_executable(&dir.join(name)))
+    })
+}
+
+#[cfg(windows)]
+fn candidates(command: &str) -> Vec<String> {
+    let exts = std::env::var("PATHEXT").unwrap_or_else(|_| ".EXE;.CMD;.BAT".to_string());
+    std::iter::once(command.to_string())
+        .chain(exts.split(';').map(|ext| format!("{command}{ext}")))
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn candidates(command: &str) -> Vec<String> {
+    vec![command.to_string()]
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn config_overrides_and_disables_builtin_commands() {
+        let mut config = ProjectConfig::default();
+        assert_eq!(command_for(".claude", &config), Some("claude"));
+        assert_eq!(command_for(".cline", &config), None);
+
+        config
+            .tool_commands
+            .insert(".claude".into(), "claude-dev".into());
+        config.tool_commands.insert(".vscode".into(), String::new());
+        config.tool_commands.insert(".cline".into(), "cline".into());
+        assert_eq!(command_for(".claude", &config), Some("claude-dev"));
+        assert_eq!(command_for(".vscode", &config), None);
+        assert_eq!(command_for(".cline", &config), Some("cline"));
+    }
+
+    #[test]
+    fn on_path_finds_only_executables() {
+        let dir = std::env::temp_dir().join(format!("cloak-tool-cmd-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("failed to create temp dir");
+        let tool = dir.join(candidates("fake-tool")[0].as_str());
+        fs::write(&tool, "#!/bin/sh\n").expect("failed to write fake tool");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).expect("chmod failed");
+            fs::write(dir.join("not-executable"), "").expect("failed to write file");
+            assert!(!on_path("not-executable", dir.as_os_str()));
+        }
+
+        assert!(on_path("fake-tool", dir.as_os_str()));
+        assert!(!on_path("missing-tool", dir.as_os_str()));
+
+        fs::remove_dir_all(dir).expect("cleanup failed");
+    }
+}
@@ -0,0 +1,110 @@
+use crate::core::mover;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ENV_FILE: &str = "env";
+
+/// Tools that can be told where their config lives through an environment variable.
+/// Useful for tools that resolve symlinks and then refuse the storage path.
+const TOOL_ENV_VARS: &[(&str, &str)] =
+    &[(".claude", "CLAUDE_CONFIG_DIR"), (".codex", "CODEX_HOME")];
+
+/// Path to `.cloak/env`, the sourceable file written by `hide --env-detect`.
+pub fn env_file(root: &Path) -> PathBuf {
+    root.join(mover::CLOAK_DIR).join(ENV_FILE)
+}
+
+/// The environment variable that relocates `target`'s tool, if we know one.
+pub fn var_for(target: &str) -> Option<&'static str> {
+    TOOL_ENV_VARS
+        .iter()
+        .find(|(name, _)| *name == target)
+        .map(|(_, var)| *var)
+}
+
+/// The variable and value that point `target`'s tool at its storage entry.
+///
+/// The value is the fully resolved storage path, so it works for tools that
+/// refuse to follow the ghost link.
+pub fn export_for(root: &Path, target: &str) -> Option<(&'static str, PathBuf)> {
+    let var = var_for(target)?;
+    let stored = mover::storage_dir(root).join(target);
+    let value = stored.canonicalize().unwrap_or(stored);
+    Some((var, value))
+}
+
+/// A POSIX shell `export` line with the value single-quoted.
+pub fn export_line(var: &str, value: &Path) -> String {
+    let quoted = value.to_string_lossy().replace('\'', "'\\''");
+    format!("export {var}='{quoted}'")
+}
+
+/// Add or replace `target`'s export in `.cloak/env`. Returns what was written,
+/// or `None` when the target has no known variable.
+pub fn record(root: &Path, target: &str) -> Result<Option<(&'static str, PathBuf)>> {
+    let Some((var, value)) = export_for(root, target) else {
+        return Ok(None);
+    };
+    let mut lines = read_lines(root)?;
+    lines.retain(|line| !is_export_of(line, var));
+    lines.push(export_line(var, &value));
+    write_lines(root, &lines)?;
+    Ok(Some((var, value)))
+}
+
+/// Drop `target`'s export from `.cloak/env`, deleting the file once nothing is left.
+pub fn forget(root: &Path, target: &str) -> Result<()> {
+    let Some(var) = var_for(target) else {
+        return Ok(());
+    };
+    let path = env_file(root);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let mut lines = read_lines(root)?;
+    lines.retain(|line| !is_export_of(line, var));
+    if lines.iter().all(|line| line.starts_with('#')) {
+        fs::remove_file(&path).with_context(|| format!("failed to remove {}", path.display()))?;
+        return Ok(());
+    }
+    write_lines(root, &lines)
+}
+
+/// Whether `.cloak/env` holds `target`'s export.
+pub fn is_recorded(root: &Path, target: &str) -> Result<bool> {
+    let Some(var) = var_for(target) else {
+        return Ok(false);
+    };
+    if !env_file(root).exists() {
+        return Ok(false);
+    }
+    Ok(read_lines(root)?.iter().any(|line| is_export_of(line, var)))
+}
+
+fn is_export_of(line: &str, var: &str) -> bool {
+    line.strip_prefix("export ")
+        .and_then(|rest| rest.strip_prefix(var))
+        .is_some_and(|rest| rest.starts_with('='))
+}
+
+fn read_lines(root: &Path) -> Result<Vec<String>> {
+    let path = env_file(root);
+    if !path.exists() {
+        return Ok(vec![
+            "# Written by `cloak hide --env-detect`; source it so tools read config from storage."
+                .to_string(),
+        ]);
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(content.lines().map(str::to_string).collect())
+}
+
+fn write_lines(root: &Path, lines: &[String]) -> Result<()> {
+    let path = env_file(root);
+    let mut content = lines.join("\n");
+    content.push('\n');
+    fs::write(&path, content).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,151 @@
+use crate::core::meta::Meta;
+use crate::core::mover;
+use anyhow::{Context, Result, bail};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest stored first in every bundle: the entries it holds and
+/// the ghost link name each was hidden under.
+pub const MANIFEST: &str = "cloak-bundle.json";
+
+/// Archive the storage entries `names` into a gzipped tarball at `dest`, each under
+/// its storage name (`.cursor`, `config/ai`), after the manifest.
+///
+/// Symlinks inside an entry are archived as symlinks, not followed. A partly
+/// written archive is removed when anything fails.
+pub fn export(root: &Path, names: &[String], dest: &Path) -> Result<()> {
+    let file =
+        fs::File::create(dest).with_context(|| format!("failed to create {}", dest.display()))?;
+    let result = write_archive(root, names, file);
+    if result.is_err() {
+        let _ = fs::remove_file(dest);
+    }
+    result.with_context(|| format!("failed to write {}", dest.display()))
+}
+
+fn write_archive(root: &Path, names: &[String], file: fs::File) -> Result<()> {
+    let meta = Meta::load(root)?;
+    let storage = mover::storage_dir(root);
+    let mut tar = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    tar.follow_symlinks(false);
+
+    let entries: Vec<serde_json::Value> = names
+        .iter()
+        .map(|name| serde_json::json!({ "name": name, "link": meta.link_name(name) }))
+        .collect();
+    let manifest = serde_json::to_vec_pretty(&serde_json::json!({ "entries": entries }))?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(crate::utils::time::unix_now());
+    header.set_cksum();
+    tar.append_data(&mut header, MANIFEST, manifest.as_slice())?;
+
+    for name in names {
+        let path = storage.join(name);
+        let is_dir = fs::symlink_metadata(&path)
+            .with_context(|| format!("failed to stat {}", path.display()))?
+            .is_dir();
+        if is_dir {
+            tar.append_dir_all(name, &path)
+        } else {
+            tar.append_path_with_name(&path, name)
+        }
+        .with_context(|| format!("failed to archive {}", path.display()))?;
+    }
+
+    tar.into_inner()?.finish()?;
+    Ok(())
+}
+
+/// One entry of an unpacked bundle.
+#[derive(Debug)]
+pub struct Bundled {
+    /// Storage name, e.g. `.cursor` or `config/ai`.
+    pub name: String,
+    /// Ghost link name at the root.
+    pub link: String,
+}
+
+/// A bundle unpacked into a private directory under `.cloak`, removed on drop.
+pub struct Unpacked {
+    pub dir: PathBuf,
+    pub entries: Vec<Bundled>,
+}
+
+impl Drop for Unpacked {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Unpack the gzipped tarball at `archive` next to storage and list its entries:
+/// those in its manifest, or every top-level path for an archive without one.
+///
+/// Archive paths that would land outside the directory (`..`, absolute) are not
+/// extracted.
+pub fn unpack(root: &Path, archive: &Path) -> Result<Unpacked> {
+    let file =
+        fs::File::open(archive).with_context(|| format!("failed to open {}", archive.display()))?;
+    let dir = root
+        .join(mover::CLOAK_DIR)
+        .join(format!(".import-{}", std::process::id()));
+    if dir.symlink_metadata().is_ok() {
+        mover::remove_path(&dir)?;
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let mut unpacked = Unpacked {
+        dir,
+        entries: Vec::new(),
+    };
+
+    let mut tar = tar::Archive::new(GzDecoder::new(file));
+    tar.set_preserve_permissions(true);
+    tar.unpack(&unpacked.dir)
+        .with_context(|| format!("failed to unpack {}", archive.display()))?;
+
+    let manifest = unpacked.dir.join(MANIFEST);
+    unpacked.entries = if manifest.exists() {
+        let content = fs::read_to_string(&manifest)
+            .with_context(|| format!("failed to read {MANIFEST} in {}", archive.display()))?;
+        fs::remove_file(&manifest)
+            .with_context(|| format!("failed to remove {}", manifest.display()))?;
+        parse_manifest(&content)
+            .with_context(|| format!("invalid {MANIFEST} in {}", archive.display()))?
+    } else {
+        let mut names: Vec<String> = fs::read_dir(&unpacked.dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| Bundled {
+                link: name.clone(),
+                name,
+            })
+            .collect()
+    };
+    Ok(unpacked)
+}
+
+fn parse_manifest(content: &str) -> Result<Vec<Bundled>> {
+    let doc: serde_json::Value = serde_json::from_str(content)?;
+    let Some(items) = doc.get("entries").and_then(|v| v.as_array()) else {
+        bail!("`entries` must be an array");
+    };
+    items
+        .iter()
+        .map(|item| {
+            let field = |key: &str| item.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            let Some(name) = field("name") else {
+                bail!("every entry needs a `name`, found {item}");
+            };
+            let link = field("link").unwrap_or_else(|| name.clone());
+            Ok(Bundled { name, link })
+        })
+        .collect()
+}
@@ -0,0 +1,591 @@
+use crate::config::{ide, project::ProjectConfig};
+use crate::core::linker::{self, LinkHealth, LinkKind};
+use crate::core::{meta::Meta, mover, storage};
+use crate::utils::git;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How serious a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// A link, storage entry or ignore rule is broken; `doctor` exits non-zero.
+    Error,
+    /// Leftovers or gaps that break nothing, such as IDE excludes out of step
+    /// with storage; reported, but `doctor` still succeeds.
+    Warning,
+}
+
+/// What kind of inconsistency a finding describes. Findings are reported grouped by category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    /// Several ghost links resolve to one storage entry.
+    SharedStorage,
+    /// Storage entries and `.gitignore` managed rules disagree.
+    IgnoreDrift,
+    /// Ghost links that are broken or resolve outside this project's storage.
+    BadLink,
+    /// External storage whose `.cloak/storage` link was replaced by a real directory.
+    DetachedStorage,
+    /// Ghost links that are no longer the kind of link recorded when they were hidden.
+    LinkKindDrift,
+    /// IDE excludes cloak added for targets that are no longer in storage.
+    OrphanedExclude,
+    /// Ghost links whose storage entry links back, so nothing reaches real content.
+    LinkCycle,
+    /// Storage entries with nothing at their ghost link's path.
+    MissingLink,
+    /// Storage entries whose ghost link's path holds a real file or directory.
+    RootConflict,
+    /// Ghost links that resolve to a different storage entry than their own.
+    WrongTarget,
+    /// Hidden targets an IDE present at the root does not exclude.
+    MissingExclude,
+}
+
+impl Category {
+    pub fn title(self) -> &'static str {
+        match self {
+            Category::SharedStorage => "Links sharing one storage entry",
+            Category::IgnoreDrift => "Gitignore rules out of sync with storage",
+            Category::BadLink => "Ghost links that do not resolve to storage",
+            Category::DetachedStorage => "External storage no longer linked",
+            Category::LinkKindDrift => "Ghost links recreated as a different link type",
+            Category::OrphanedExclude => "IDE excludes for targets cloak no longer manages",
+            Category::LinkCycle => "Ghost links caught in a symlink cycle",
+            Category::MissingLink => "Storage entries without a ghost link",
+            Category::RootConflict => "Real files where a ghost link belongs",
+            Category::WrongTarget => "Ghost links pointing at another storage entry",
+            Category::MissingExclude => "Hidden targets the IDE still shows",
+        }
+    }
+}
+
+/// A single problem found by `diagnose`.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub category: Category,
+    /// The root-relative path the finding is about.
+    pub subject: String,
+    pub detail: String,
+}
+
+/// Run every read-only check against the project and return the findings.
+pub fn diagnose(root: &Path) -> Result<Vec<Finding>> {
+    let links = ghost_links(root)?;
+
+    let mut findings = Vec::new();
+    findings.extend(check_shared_storage(root, &links));
+    findings.extend(check_ignore_drift(root)?);
+    findings.extend(check_link_targets(root)?);
+    findings.extend(check_external_storage(root)?);
+    findings.extend(check_link_kinds(root)?);
+    findings.extend(check_orphaned_excludes(root)?);
+    findings.extend(check_entry_links(root)?);
+    findings.extend(check_missing_excludes(root)?);
+    findings.sort_by(|a, b| (a.category, &a.subject).cmp(&(b.category, &b.subject)));
+    Ok(findings)
+}
+
+/// A root symlink that resolves into `.cloak/storage`.
+#[derive(Debug)]
+struct GhostLink {
+    name: String,
+    resolved: PathBuf,
+}
+
+/// Collect root entries that are symlinks pointing into storage.
+fn ghost_links(root: &Path) -> Result<Vec<GhostLink>> {
+    let storage = linker::normalize(&mover::storage_dir(root));
+    let mut links = Vec::new();
+
+    for entry in fs::read_dir(root).with_context(|| format!("failed to read {}", root.display()))? {
+        let entry = entry?;
+        let path = entry.path();
+        let is_link = path
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink());
+        if !is_link {
+            continue;
+        }
+        let Some(resolved) = linker::resolve_link(&path) else {
+            continue;
+        };
+        if resolved.starts_with(&storage) {
+            links.push(GhostLink {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                resolved,
+            });
+        }
+    }
+
+    links.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(links)
+}
+
+/// Restoring one of several links that share a storage entry would break the others.
+fn check_shared_storage(root: &Path, links: &[GhostLink]) -> Vec<Finding> {
+    let mut by_target: BTreeMap<&Path, Vec<&str>> = BTreeMap::new();
+    for link in links {
+        by_target
+            .entry(link.resolved.as_path())
+            .or_default()
+            .push(&link.name);
+    }
+
+    by_target
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(target, names)| {
+            let stored = target
+                .strip_prefix(linker::normalize(root))
+                .unwrap_or(target);
+            Finding {
+                severity: Severity::Error,
+                category: Category::SharedStorage,
+                subject: stored.display().to_string(),
+                detail: format!(
+                    "linked from {}; unhiding one would break the others",
+                    names.join(", ")
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Every storage entry should have a managed `.gitignore` rule and vice versa.
+/// A missing rule lets git track the ghost link; a leftover rule hides a real path.
+fn check_ignore_drift(root: &Path) -> Result<Vec<Finding>> {
+    let Some((stored, ignored)) = ignore_rules(root)? else {
+        return Ok(Vec::new());
+    };
+
+    let missing_rules = stored.difference(&ignored).map(|name| Finding {
+        severity: Severity::Error,
+        category: Category::IgnoreDrift,
+        subject: name.clone(),
+        detail: format!("storage entry has no gitignore rule (expected `/{name}`)"),
+    });
+    let stale_rules = ignored.difference(&stored).map(|name| Finding {
+        severity: Severity::Warning,
+        category: Category::IgnoreDrift,
+        subject: name.clone(),
+        detail: format!("gitignore rule `/{name}` has no storage entry"),
+    });
+
+    Ok(missing_rules.chain(stale_rules).collect())
+}
+
+/// The link names in storage and the names with a managed ignore rule, or `None`
+/// when cloak does not manage the ignore files here.
+fn ignore_rules(root: &Path) -> Result<Option<(BTreeSet<String>, BTreeSet<String>)>> {
+    // Rules are deliberately left to the user when `gitignore = false`.
+    if !mover::storage_dir(root).is_dir() || ProjectConfig::load(root)?.skip_gitignore {
+        return Ok(None);
+    }
+    let ignored: BTreeSet<String> = git::managed_entries(root)?.into_iter().collect();
+    // Outside a repository cloak only writes rules when `--git` asked for them.
+    if ignored.is_empty() && !git::in_repository(root) {
+        return Ok(None);
+    }
+    // Rules are written for the ghost link's root name, which `--link-name` may change.
+    let meta = Meta::load(root)?;
+    let stored: BTreeSet<String> = storage::entry_names(root)?
+        .iter()
+        .map(|name| meta.link_name(name).to_string())
+        .collect();
+    Ok(Some((stored, ignored)))
+}
+
+/// Root symlinks meant as ghost links (their text goes through `.cloak/storage`, or
+/// they resolve into storage) must land on their own, existing storage entry.
+///
+/// Relative link text is resolved against the link's directory, so a link whose
+/// `..` components climb out of the project is reported rather than trusted. A link
+/// whose chain loops (e.g. the storage entry was replaced by a link back to the root)
+/// is reported as a cycle, since resolving it fails with `ELOOP`.
+fn check_link_targets(root: &Path) -> Result<Vec<Finding>> {
+    let storage = linker::normalize(&mover::storage_dir(root));
+    let mut findings = Vec::new();
+
+    for entry in fs::read_dir(root).with_context(|| format!("failed to read {}", root.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(text) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        let Some(resolved) = linker::resolve_link(&entry.path()) else {
+            continue;
+        };
+        if !resolved.starts_with(&storage) && !linker::mentions_storage(&text) {
+            continue;
+        }
+
+        if let Some(chain) = linker::link_cycle(&entry.path()) {
+            let base = linker::normalize(root);
+            let hops: Vec<String> = chain
+                .iter()
+                .map(|p| p.strip_prefix(&base).unwrap_or(p).display().to_string())
+                .collect();
+            findings.push(Finding {
+                severity: Severity::Error,
+                category: Category::LinkCycle,
+                subject: name,
+                detail: format!(
+                    "resolves in a loop ({}); nothing holds the real content. Replace the \
+                     storage entry with the real content (from a backup, or a snapshot via \
+                     `cloak rollback`)",
+                    hops.join(" -> ")
+                ),
+            });
+            continue;
+        }
+
+        let detail = match linker::link_health(root, &name) {
+            Some(LinkHealth::Broken) => format!(
+                "link `{}` resolves to its storage entry, which is missing",
+                text.display()
+            ),
+            // Pointing at another entry inside storage is the shared-storage check's job.
+            Some(LinkHealth::Misdirected(to)) if !to.starts_with(&storage) => format!(
+                "link `{}` resolves outside this project's storage ({})",
+                text.display(),
+                to.display()
+            ),
+            _ => continue,
+        };
+        findings.push(Finding {
+            severity: Severity::Error,
+            category: Category::BadLink,
+            subject: name,
+            detail,
+        });
+    }
+    Ok(findings)
+}
+
+/// With external storage configured, `.cloak/storage` must still be the link to it.
+/// A real directory there means hides and unhides no longer reach the external copy.
+fn check_external_storage(root: &Path) -> Result<Vec<Finding>> {
+    let meta = Meta::load(root)?;
+    let Some(external) = meta.external_storage() else {
+        return Ok(Vec::new());
+    };
+    let storage = mover::storage_dir(root);
+    let Ok(info) = storage.symlink_metadata() else {
+        return Ok(Vec::new());
+    };
+    #[cfg(windows)]
+    let is_link = info.file_type().is_symlink() || junction::exists(&storage).unwrap_or(false);
+    #[cfg(not(windows))]
+    let is_link = info.file_type().is_symlink();
+    if is_link || !info.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    Ok(vec![Finding {
+        severity: Severity::Error,
+        category: Category::DetachedStorage,
+        subject: format!("{}/{}", mover::CLOAK_DIR, mover::STORAGE_DIR),
+        detail: format!(
+            "is a real directory but storage is configured at {ext}; its contents may have \
+             diverged. Merge anything newer into {ext}, remove .cloak/storage, then run \
+             `cloak init --storage {ext}` to link it again",
+            ext = external.display()
+        ),
+    }])
+}
+
+/// A ghost link recreated without symlink permission (Windows without Developer
+/// Mode) silently becomes a junction; tools that treat the two differently break.
+fn check_link_kinds(root: &Path) -> Result<Vec<Finding>> {
+    if !mover::storage_dir(root).is_dir() {
+        return Ok(Vec::new());
+    }
+    let meta = Meta::load(root)?;
+    let mut findings = Vec::new();
+
+    for name in storage::entry_names(root)? {
+        let Some(expected) = meta.get(&name).and_then(|e| e.link_kind) else {
+            continue;
+        };
+        let link = meta.link_name(&name);
+        let Some(actual) = linker::link_kind(&root.join(link)) else {
+            continue;
+        };
+        if actual == expected {
+            continue;
+        }
+
+        let remedy = match expected {
+            LinkKind::Symlink => "enable Developer Mode (or run elevated), then ",
+            LinkKind::Junction => "",
+        };
+        let hide = if link == name {
+            format!("cloak hide {name}")
+        } else {
+            format!("cloak hide --link-name {link} {name}")
+        };
+        findings.push(Finding {
+            severity: Severity::Error,
+            category: Category::LinkKindDrift,
+            subject: link.to_string(),
+            detail: format!(
+                "was hidden as a {} but is now a {}; {remedy}run `cloak unhide {name}` \
+                 and `{hide}` to recreate it",
+                expected.as_str(),
+                actual.as_str()
+            ),
+        });
+    }
+    Ok(findings)
+}
+
+/// Each storage entry should be reachable through its own ghost link (the target's
+/// name, or its `--link-name`). Links that go through storage but resolve outside
+/// it, or to nothing, are `check_link_targets`' job; a foreign symlink sitting at
+/// the link path is reported here.
+fn check_entry_links(root: &Path) -> Result<Vec<Finding>> {
+    if !mover::storage_dir(root).is_dir() {
+        return Ok(Vec::new());
+    }
+    let storage = linker::normalize(&mover::storage_dir(root));
+    let meta = Meta::load(root)?;
+    let mut findings = Vec::new();
+
+    for name in storage::entry_names(root)? {
+        let link = meta.link_name(&name);
+        let (category, detail) = match root.join(link).symlink_metadata() {
+            Err(_) => (
+                Category::MissingLink,
+                format!(
+                    "nothing at the root links to .cloak/storage/{name}; \
+                     run `cloak repair` to recreate the link"
+                ),
+            ),
+            Ok(info) if !info.file_type().is_symlink() => (
+                Category::RootConflict,
+                format!(
+                    "a real {} occupies the link path for .cloak/storage/{name}; compare the \
+                     two, then move one aside or absorb it with `cloak hide --update {link}`",
+                    if info.is_dir() { "directory" } else { "file" }
+                ),
+            ),
+            Ok(_) => match linker::link_health_as(root, link, &name) {
+                Some(LinkHealth::Misdirected(to)) if to.starts_with(&storage) => (
+                    Category::WrongTarget,
+                    format!(
+                        "resolves to {} instead of .cloak/storage/{name}",
+                        to.strip_prefix(linker::normalize(root))
+                            .unwrap_or(&to)
+                            .display()
+                    ),
+                ),
+                Some(LinkHealth::Misdirected(to))
+                    if !fs::read_link(root.join(link))
+                        .is_ok_and(|t| linker::mentions_storage(&t)) =>
+                {
+                    (
+                        Category::BadLink,
+                        format!(
+                            "is a foreign symlink to {} instead of a link to \
+                             .cloak/storage/{name}; remove it, then run `cloak repair`",
+                            to.display()
+                        ),
+                    )
+                }
+                _ => continue,
+            },
+        };
+        findings.push(Finding {
+            severity: Severity::Error,
+            category,
+            subject: link.to_string(),
+            detail,
+        });
+    }
+    Ok(findings)
+}
+
+/// `files.exclude` / `search.exclude` keys cloak wrote for a target whose storage
+/// entry has since gone (e.g. purged by hand) keep the name out of the IDE for no reason.
+///
+/// Only names meta.json still records are flagged, so excludes the user added
+/// with the same `**/<name>` form are left alone.
+fn check_orphaned_excludes(root: &Path) -> Result<Vec<Finding>> {
+    let orphaned = orphaned_names(root)?;
+    Ok(ide::excluded_names(root)
+        .into_iter()
+        .filter(|(_, name)| orphaned.contains(name))
+        .map(|(file, name)| {
+            let detail = if file.ends_with(".iml") {
+                format!(
+                    "excludes `{name}`, which cloak added but {name} is no longer in storage; \
+                     remove its excludeFolder entry"
+                )
+            } else {
+                format!(
+                    "excludes `**/{name}`, which cloak added but {name} is no longer in storage; \
+                     remove the key from files.exclude and search.exclude"
+                )
+            };
+            Finding {
+                severity: Severity::Warning,
+                category: Category::OrphanedExclude,
+                subject: file,
+                detail,
+            }
+        })
+        .collect())
+}
+
+/// Every hidden target should be excluded in each IDE present at the root, as
+/// `hide` leaves it. Settings that cannot be read are left to the command that
+/// edits them next.
+fn check_missing_excludes(root: &Path) -> Result<Vec<Finding>> {
+    Ok(unexcluded(root)?
+        .into_iter()
+        .map(|(link, missing)| Finding {
+            severity: Severity::Warning,
+            category: Category::MissingExclude,
+            subject: link,
+            detail: format!(
+                "is not excluded in {}; run `cloak relink` to add it",
+                missing.join(", ")
+            ),
+        })
+        .collect())
+}
+
+/// Link names meta.json records for targets that are no longer in storage.
+fn orphaned_names(root: &Path) -> Result<BTreeSet<String>> {
+    let meta = Meta::load(root)?;
+    let stored: BTreeSet<String> = if mover::storage_dir(root).is_dir() {
+        storage::entry_names(root)?.into_iter().collect()
+    } else {
+        BTreeSet::new()
+    };
+    let live: BTreeSet<&str> = stored.iter().map(|name| meta.link_name(name)).collect();
+    Ok(meta
+        .targets()
+        .filter(|target| !stored.contains(*target))
+        .map(|target| meta.link_name(target))
+        .filter(|name| !live.contains(name))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Each hidden target's link name with the present IDE directories that do not
+/// exclude it, for targets missing at least one.
+fn unexcluded(root: &Path) -> Result<Vec<(String, Vec<String>)>> {
+    if !mover::storage_dir(root).is_dir() {
+        return Ok(Vec::new());
+    }
+    let present = ide::present_dirs(root);
+    if present.is_empty() {
+        return Ok(Vec::new());
+    }
+    let meta = Meta::load(root)?;
+    let mut unexcluded = Vec::new();
+
+    for name in storage::entry_names(root)? {
+        let link = meta.link_name(&name);
+        let Ok(status) = ide::exclude_status(root, link) else {
+            continue;
+        };
+        let missing: Vec<String> = present
+            .iter()
+            .filter(|dir| !status.iter().any(|(d, on)| d == *dir && *on))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            unexcluded.push((link.to_string(), missing));
+        }
+    }
+    Ok(unexcluded)
+}
+
+/// Something `fix` changed, reported like a finding.
+#[derive(Debug, Clone)]
+pub struct Fixed {
+    /// The root-relative path that was fixed.
+    pub subject: String,
+    pub detail: String,
+}
+
+/// Fix what needs no judgement once `repair` has recreated the ghost links:
+/// remove root symlinks into storage that resolve to nothing and belong to no
+/// storage entry, drop managed ignore rules and IDE excludes with no storage
+/// entry, and re-add IDE excludes missing for hidden targets. Real files and
+/// storage are never touched.
+pub fn fix(root: &Path) -> Result<Vec<Fixed>> {
+    if !mover::storage_dir(root).is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut fixed = Vec::new();
+
+    let meta = Meta::load(root)?;
+    let links: BTreeSet<String> = storage::entry_names(root)?
+        .iter()
+        .map(|name| meta.link_name(name).to_string())
+        .collect();
+    let storage = linker::normalize(&mover::storage_dir(root));
+    for entry in fs::read_dir(root).with_context(|| format!("failed to read {}", root.display()))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(text) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        let Some(resolved) = linker::resolve_link(&entry.path()) else {
+            continue;
+        };
+        let into_storage = resolved.starts_with(&storage) || linker::mentions_storage(&text);
+        if !into_storage
+            || links.contains(&name)
+            || resolved.symlink_metadata().is_ok()
+            || linker::link_cycle(&entry.path()).is_some()
+        {
+            continue;
+        }
+        linker::remove_ghost_link(root, &name)?;
+        fixed.push(Fixed {
+            subject: name,
+            detail: format!("removed the dangling link to `{}`", text.display()),
+        });
+    }
+
+    if let Some((stored, ignored)) = ignore_rules(root)? {
+        for name in ignored.difference(&stored) {
+            git::remove_ignore_entry(root, name)?;
+            fixed.push(Fixed {
+                subject: name.clone(),
+                detail: format!("pruned the stale gitignore rule `/{name}`"),
+            });
+        }
+    }
+
+    let orphaned = orphaned_names(root)?;
+    let excluded: BTreeSet<String> = ide::excluded_names(root)
+        .into_iter()
+        .map(|(_, name)| name)
+        .filter(|name| orphaned.contains(name))
+        .collect();
+    for name in excluded {
+        ide::remove_ide_exclude(root, &name)?;
+        fixed.push(Fixed {
+            detail: format!("dropped the IDE excludes left for `{name}`"),
+            subject: name,
+        });
+    }
+
+    for (link, missing) in unexcluded(root)? {
+        ide::add_ide_exclude(root, &link, &ide::IdeOptions::default())?;
+        fixed.push(Fixed {
+            subject: link,
+            detail: format!("re-added the IDE excludes in {}", missing.join(", ")),
+        });
+    }
+    Ok(fixed)
+}
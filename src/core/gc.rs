@@ -0,0 +1,134 @@
+use crate::config::project::ProjectConfig;
+use crate::core::lock::{self, LOCK_FILE};
+use crate::core::{mover, snapshot};
+use crate::utils::process;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Something `gc` removed (or would remove in dry-run mode).
+#[derive(Debug)]
+pub struct Reclaimed {
+    pub path: PathBuf,
+    pub reason: &'static str,
+    pub bytes: u64,
+}
+
+/// Find and (unless `dry_run`) remove leftover artifacts under `.cloak`:
+/// snapshots past retention, a stale lock, and empty directories.
+///
+/// Hidden entries in storage are never touched, even if they are empty.
+pub fn collect(root: &Path, config: &ProjectConfig, dry_run: bool) -> Result<Vec<Reclaimed>> {
+    let cloak_dir = root.join(mover::CLOAK_DIR);
+    let mut reclaimed = Vec::new();
+    if !cloak_dir.exists() {
+        return Ok(reclaimed);
+    }
+
+    // 1. Snapshots beyond the configured retention
+    if let Some(keep) = config.snapshot_retention {
+        for target in snapshot::targets(root)? {
+            let snapshots = snapshot::list(root, &target)?;
+            let excess = snapshots.len().saturating_sub(keep);
+            for snap in snapshots.into_iter().take(excess) {
+                reclaimed.push(Reclaimed {
+                    bytes: mover::size_of(&snap.path),
+                    path: snap.path,
+                    reason: "snapshot past retention",
+                });
+            }
+        }
+    }
+
+    // 2. Stale lock whose owning process is gone
+    let lock = cloak_dir.join(LOCK_FILE);
+    if is_stale_lock(&lock) {
+        reclaimed.push(Reclaimed {
+            bytes: mover::size_of(&lock),
+            path: lock,
+            reason: "stale lock",
+        });
+    }
+
+    if !dry_run {
+        for item in &reclaimed {
+            mover::remove_path(&item.path)?;
+        }
+    }
+
+    // 3. Empty directories (computed after the removals above so that a
+    //    snapshot dir emptied by retention is also reclaimed)
+    let mut empty = Vec::new();
+    for entry in read_dir_sorted(&cloak_dir)? {
+        if entry == mover::storage_dir(root) {
+            let snapshots = snapshot::snapshot_root(root);
+            if snapshots.is_dir() {
+                collect_empty_dirs(&snapshots, &reclaimed, &mut empty)?;
+            }
+        } else if entry.is_dir() && !entry.is_symlink() {
+            collect_empty_dirs(&entry, &reclaimed, &mut empty)?;
+        }
+    }
+
+    for dir in empty {
+        if !dry_run {
+            fs::remove_dir(&dir)
+                .with_context(|| format!("failed to remove directory: {}", dir.display()))?;
+        }
+        reclaimed.push(Reclaimed {
+            path: dir,
+            reason: "empty directory",
+            bytes: 0,
+        });
+    }
+
+    Ok(reclaimed)
+}
+
+/// A lock is stale when no process holds it and its recorded PID is known not
+/// to be running. Unreadable locks or platforms without a liveness check are left alone.
+fn is_stale_lock(lock: &Path) -> bool {
+    if lock::is_held(lock) {
+        return false;
+    }
+    let Ok(content) = fs::read_to_string(lock) else {
+        return false;
+    };
+    let Ok(pid) = content.trim().parse::<u32>() else {
+        return false;
+    };
+    process::is_alive(pid) == Some(false)
+}
+
+/// Depth-first: push `dir` (after its children) if everything inside it is
+/// either already reclaimed or itself an empty directory. Returns whether
+/// `dir` counts as empty.
+fn collect_empty_dirs(dir: &Path, reclaimed: &[Reclaimed], out: &mut Vec<PathBuf>) -> Result<bool> {
+    let mut empty = true;
+    for child in read_dir_sorted(dir)? {
+        if reclaimed.iter().any(|r| r.path == child) {
+            continue;
+        }
+        if child.is_dir() && !child.is_symlink() {
+            if !collect_empty_dirs(&child, reclaimed, out)? {
+                empty = false;
+            }
+        } else {
+            empty = false;
+        }
+    }
+    if empty {
+        out.push(dir.to_path_buf());
+    }
+    Ok(empty)
+}
+
+fn read_dir_sorted(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)
+        .with_context(|| format!("failed to read directory: {}", dir.display()))?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("failed to read directory: {}", dir.display()))?;
+    entries.sort();
+    Ok(entries)
+}
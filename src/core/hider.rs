@@ -1,10 +1,15 @@
-use anyhow::{Context, Result};
+use crate::utils::output::trace;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use anyhow::Context;
+use anyhow::Result;
 use std::path::Path;
 
 /// Set the OS-level hidden flag on the symlink so it disappears from Finder/Explorer.
 pub fn hide_path(root: &Path, target: &str) -> Result<()> {
     let path = root.join(target);
 
+    trace!("hide_path {}", path.display());
+
     #[cfg(target_os = "macos")]
     {
         macos_set_hidden(&path, true)?;
@@ -29,6 +34,8 @@ pub fn hide_path(root: &Path, target: &str) -> Result<()> {
 pub fn unhide_path(root: &Path, target: &str) -> Result<()> {
     let path = root.join(target);
 
+    trace!("unhide_path {}", path.display());
+
     #[cfg(target_os = "macos")]
     {
         macos_set_hidden(&path, false)?;
@@ -85,6 +92,36 @@ fn macos_set_hidden(path: &Path, hidden: bool) -> Result<()> {
     Ok(())
 }
 
+/// Set or clear the OS immutable flag on `path` and everything under it, so writes
+/// through a ghost link fail (`chattr +i` on Linux, `chflags uchg` on macOS).
+///
+/// Setting the flag usually needs elevated privileges (`CAP_LINUX_IMMUTABLE` on
+/// Linux) and a filesystem that supports it; the tool's own message is reported.
+#[cfg(unix)]
+pub fn set_immutable(path: &Path, immutable: bool) -> Result<()> {
+    use anyhow::{Context, bail};
+
+    #[cfg(target_os = "macos")]
+    let (program, flag) = ("chflags", if immutable { "uchg" } else { "nouchg" });
+    #[cfg(not(target_os = "macos"))]
+    let (program, flag) = ("chattr", if immutable { "+i" } else { "-i" });
+
+    let output = std::process::Command::new(program)
+        .arg("-R")
+        .arg(flag)
+        .arg(path)
+        .output()
+        .with_context(|| format!("failed to run {program}"))?;
+    if !output.status.success() {
+        bail!(
+            "{program} -R {flag} {} failed: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
 #[cfg(target_os = "windows")]
 fn windows_set_hidden(path: &Path, hidden: bool) -> Result<()> {
     use std::os::windows::ffi::OsStrExt;
@@ -1,47 +1,302 @@
+use crate::error::CloakError;
+use crate::utils::output::trace;
 use anyhow::{Context, Result, bail};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+
+/// How the ghost link's target text is written.
+///
+/// Relative is the default on every platform. On Windows a directory that falls
+/// back to a junction (no Developer Mode) still gets an absolute target, since
+/// junctions cannot hold relative ones; `link_kind` records when that happened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LinkStyle {
+    /// `.cursor -> .cloak/storage/.cursor`; survives moving or cloning the project.
+    #[default]
+    Relative,
+    /// `.cursor -> /abs/project/.cloak/storage/.cursor`.
+    Absolute,
+}
+
+impl LinkStyle {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkStyle::Relative => "relative",
+            LinkStyle::Absolute => "absolute",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "relative" => Some(LinkStyle::Relative),
+            "absolute" => Some(LinkStyle::Absolute),
+            _ => None,
+        }
+    }
+}
+
+/// What kind of filesystem link a ghost link is. Junctions only exist on Windows,
+/// where they stand in for directory symlinks without Developer Mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Symlink,
+    Junction,
+}
+
+impl LinkKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkKind::Symlink => "symlink",
+            LinkKind::Junction => "junction",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "symlink" => Some(LinkKind::Symlink),
+            "junction" => Some(LinkKind::Junction),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of link at `path`, or `None` when it is not a link at all.
+pub fn link_kind(path: &Path) -> Option<LinkKind> {
+    #[cfg(windows)]
+    if junction::exists(path).unwrap_or(false) {
+        return Some(LinkKind::Junction);
+    }
+    path.symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink())
+        .then_some(LinkKind::Symlink)
+}
+
+/// The text a ghost link at `root/link` for `target` should contain in the given style.
+///
+/// Relative text climbs out of the link's parent directory back to the root,
+/// so nested links get the right number of `..` components.
+pub fn link_target_text(root: &Path, link: &str, target: &str, style: LinkStyle) -> PathBuf {
+    match style {
+        LinkStyle::Absolute => root.join(".cloak").join("storage").join(target),
+        LinkStyle::Relative => {
+            let depth = Path::new(link).components().count().saturating_sub(1);
+            let mut text = PathBuf::new();
+            for _ in 0..depth {
+                text.push("..");
+            }
+            text.join(".cloak").join("storage").join(target)
+        }
+    }
+}
+
+/// Whether link text goes through a `.cloak/storage` directory, i.e. was written as
+/// a ghost link, wherever that storage is (or was).
+pub fn mentions_storage(text: &Path) -> bool {
+    let parts: Vec<Component> = text.components().collect();
+    parts
+        .windows(2)
+        .any(|pair| pair[0].as_os_str() == ".cloak" && pair[1].as_os_str() == "storage")
+}
+
+/// Read a symlink and return where it points as a path anchored at the link's
+/// directory, with `.`/`..` folded lexically (the target need not exist).
+pub fn resolve_link(link_path: &Path) -> Option<PathBuf> {
+    let text = std::fs::read_link(link_path).ok()?;
+    let base = link_path.parent().unwrap_or(Path::new(""));
+    Some(normalize(&base.join(text)))
+}
+
+/// How many links `link_cycle` follows before calling the chain a loop; the same
+/// limit Linux applies before failing with `ELOOP`.
+const MAX_LINK_HOPS: usize = 40;
+
+/// Follow the chain of symlinks starting at `link_path` and return it when it never
+/// reaches a real entry: the paths visited, ending with the first one seen twice.
+/// Each hop is resolved lexically, like `resolve_link`.
+pub fn link_cycle(link_path: &Path) -> Option<Vec<PathBuf>> {
+    let mut chain = vec![normalize(link_path)];
+    while chain.len() <= MAX_LINK_HOPS {
+        let current = chain.last()?;
+        if !current
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            return None;
+        }
+        let next = resolve_link(current)?;
+        let seen = chain.contains(&next);
+        chain.push(next);
+        if seen {
+            return Some(chain);
+        }
+    }
+    Some(chain)
+}
+
+/// Where an existing ghost link for a target actually leads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkHealth {
+    /// Resolves to the target's storage entry, which exists.
+    Linked,
+    /// Resolves to the target's storage entry, which is gone.
+    Broken,
+    /// Resolves somewhere other than the target's storage entry.
+    Misdirected(PathBuf),
+}
+
+/// Check the symlink at `root/target`, resolving relative link text against the
+/// link's own directory. Returns `None` when there is no symlink there.
+pub fn link_health(root: &Path, target: &str) -> Option<LinkHealth> {
+    link_health_as(root, target, target)
+}
+
+/// Like `link_health`, for a ghost link named `link` that stands in for `target`.
+pub fn link_health_as(root: &Path, link: &str, target: &str) -> Option<LinkHealth> {
+    let link_path = root.join(link);
+    if !link_path
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink())
+    {
+        return None;
+    }
+
+    let resolved = resolve_link(&link_path)?;
+    let expected = normalize(&root.join(".cloak").join("storage").join(target));
+    // Compare canonical forms too, so `/tmp/x` and `/private/tmp/x` agree.
+    let same = resolved == expected
+        || matches!(
+            (resolved.canonicalize(), expected.canonicalize()),
+            (Ok(a), Ok(b)) if a == b
+        );
+
+    Some(if !same {
+        LinkHealth::Misdirected(resolved)
+    } else if link_path.exists() {
+        LinkHealth::Linked
+    } else {
+        LinkHealth::Broken
+    })
+}
+
+/// Lexically fold `.` and `..` components without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
+
+/// On a case-insensitive filesystem, return the on-disk name of an entry that
+/// answers to `target` under a different case (e.g. `.Cursor` for `.cursor`).
+///
+/// Case-sensitive filesystems let both names coexist, so this returns `None` there.
+pub fn case_variant(root: &Path, target: &str) -> Option<String> {
+    let path = root.join(target);
+    path.symlink_metadata().ok()?;
+
+    let wanted = path.file_name()?.to_str()?;
+    let parent = path.parent()?;
+    let names: Vec<String> = std::fs::read_dir(parent)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect();
+    if names.iter().any(|n| n == wanted) {
+        return None;
+    }
+    names.into_iter().find(|n| n.eq_ignore_ascii_case(wanted))
+}
+
+/// Refuse to work on `target` when the filesystem would silently resolve it to a
+/// differently-cased existing entry.
+pub fn ensure_no_case_variant(root: &Path, target: &str) -> Result<()> {
+    if let Some(existing) = case_variant(root, target) {
+        bail!(CloakError::Conflict(format!(
+            "a case-variant already exists: {existing} (this filesystem ignores case, \
+             so {target} would refer to it; rename it or hide {existing} instead)"
+        )));
+    }
+    Ok(())
+}
 
 /// Create a symlink at the original location pointing to `.cloak/storage/<target>`.
-pub fn create_ghost_link(root: &Path, target: &str) -> Result<()> {
-    let link_path = root.join(target);
+pub fn create_ghost_link(root: &Path, target: &str, style: LinkStyle) -> Result<()> {
+    create_ghost_link_as(root, target, target, style)
+}
+
+/// Like `create_ghost_link`, but the link is made at `root/link`, which may sit at a
+/// different depth than `target` does in storage (`hide --flatten`).
+pub fn create_ghost_link_as(root: &Path, link: &str, target: &str, style: LinkStyle) -> Result<()> {
+    let link_path = root.join(link);
     let storage_path = root.join(".cloak").join("storage").join(target);
+    let link_text = link_target_text(root, link, target, style);
+
+    ensure_no_case_variant(root, link)?;
 
     if link_path.exists() || link_path.symlink_metadata().is_ok() {
-        bail!(
+        bail!(CloakError::Conflict(format!(
             "cannot create symlink: path already exists at {}",
             link_path.display()
-        );
+        )));
     }
 
     if !storage_path.exists() {
         bail!("storage target does not exist: {}", storage_path.display());
     }
 
+    if let Some(parent) = link_path.parent()
+        && !parent.is_dir()
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    trace!(
+        "create_ghost_link {} -> {}",
+        link_path.display(),
+        link_text.display()
+    );
+
     #[cfg(unix)]
     {
-        std::os::unix::fs::symlink(&storage_path, &link_path).with_context(|| {
+        std::os::unix::fs::symlink(&link_text, &link_path).with_context(|| {
             format!(
                 "failed to create symlink {} -> {}",
                 link_path.display(),
-                storage_path.display()
+                link_text.display()
             )
         })?;
     }
 
     #[cfg(windows)]
     {
-        create_ghost_link_windows(&storage_path, &link_path)?;
+        create_ghost_link_windows(&storage_path, &link_text, &link_path)?;
     }
 
     Ok(())
 }
 
 /// Windows-specific link creation with junction fallback for directories.
+/// Junctions always use the absolute `storage_path`, whatever `link_text` says.
 #[cfg(windows)]
-fn create_ghost_link_windows(storage_path: &Path, link_path: &Path) -> Result<()> {
+fn create_ghost_link_windows(
+    storage_path: &Path,
+    link_text: &Path,
+    link_path: &Path,
+) -> Result<()> {
     if storage_path.is_dir() {
         // Try symlink first; fall back to junction if permission denied
-        match std::os::windows::fs::symlink_dir(storage_path, link_path) {
+        match std::os::windows::fs::symlink_dir(link_text, link_path) {
             Ok(()) => {}
             Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
                 eprintln!(
@@ -66,7 +321,7 @@ fn create_ghost_link_windows(storage_path: &Path, link_path: &Path) -> Result<()
             }
         }
     } else {
-        std::os::windows::fs::symlink_file(storage_path, link_path).with_context(|| {
+        std::os::windows::fs::symlink_file(link_text, link_path).with_context(|| {
             format!(
                 "failed to create file symlink {} -> {} (file symlinks require Developer Mode on Windows)",
                 link_path.display(),
@@ -97,13 +352,15 @@ pub fn remove_ghost_link(root: &Path, target: &str) -> Result<()> {
             }
         }
 
-        bail!(
+        bail!(CloakError::Conflict(format!(
             "path is not a symlink (refusing to remove): {}",
             link_path.display()
-        );
+        )));
     }
 
     // On Unix, symlinks (even to directories) are removed with remove_file.
+    trace!("remove_ghost_link {}", link_path.display());
+
     // On Windows, directory symlinks need remove_dir.
     #[cfg(unix)]
     {
@@ -126,3 +383,81 @@ pub fn remove_ghost_link(root: &Path, target: &str) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn make_temp_dir(prefix: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock before epoch")
+            .as_nanos();
+        let pid = std::process::id();
+        let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!("cloak-{prefix}-{pid}-{nanos}-{seq}"));
+        fs::create_dir_all(&dir).expect("failed to create temp test dir");
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn create_ghost_link_writes_requested_target_text() {
+        let root = make_temp_dir("link-style");
+        for target in [".cursor", ".idea"] {
+            fs::create_dir_all(root.join(".cloak").join("storage").join(target))
+                .expect("create storage entry failed");
+        }
+
+        create_ghost_link(&root, ".cursor", LinkStyle::Relative).expect("relative link failed");
+        create_ghost_link(&root, ".idea", LinkStyle::Absolute).expect("absolute link failed");
+
+        assert_eq!(
+            fs::read_link(root.join(".cursor")).expect("read_link failed"),
+            Path::new(".cloak").join("storage").join(".cursor")
+        );
+        assert_eq!(
+            fs::read_link(root.join(".idea")).expect("read_link failed"),
+            root.join(".cloak").join("storage").join(".idea")
+        );
+        assert!(
+            root.join(".cursor").is_dir(),
+            "relative link should resolve"
+        );
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn case_variant_is_reported_only_on_case_insensitive_filesystems() {
+        let root = make_temp_dir("case-variant");
+        fs::create_dir_all(root.join(".Cursor")).expect("create .Cursor failed");
+
+        let case_insensitive = root.join(".cursor").symlink_metadata().is_ok();
+        if !case_insensitive {
+            // Both names can coexist here, so there is nothing to collide with.
+            assert_eq!(case_variant(&root, ".cursor"), None);
+            fs::remove_dir_all(root).expect("cleanup failed");
+            return;
+        }
+
+        assert_eq!(case_variant(&root, ".cursor").as_deref(), Some(".Cursor"));
+        assert_eq!(case_variant(&root, ".Cursor"), None);
+
+        fs::create_dir_all(root.join(".cloak").join("storage").join(".cursor"))
+            .expect("create storage entry failed");
+        let err = create_ghost_link(&root, ".cursor", LinkStyle::Relative).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("a case-variant already exists: .Cursor"),
+            "{err}"
+        );
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+}
@@ -0,0 +1,97 @@
+use crate::core::mover;
+use crate::error::CloakError;
+use anyhow::{Context, Result, bail};
+use std::fs::{self, File, TryLockError};
+use std::io::Write;
+use std::path::Path;
+
+/// Lock file held by a running cloak process (contains the owner's PID).
+pub const LOCK_FILE: &str = ".lock";
+
+/// An exclusive advisory lock on `.cloak/.lock`, released when dropped (or when
+/// the process exits, however it exits).
+///
+/// The file itself is left in place: deleting it on release would let a second
+/// process lock a file a third one can no longer see.
+#[derive(Debug)]
+pub struct ProjectLock {
+    _file: File,
+}
+
+/// Take the project lock for a command that changes the root or `.cloak`.
+///
+/// `.cloak` is only created when `create` is set (commands that set cloak up);
+/// otherwise an uninitialized root is left untouched and `None` returned. Fails
+/// with a `Conflict` naming the holder when another process has the lock.
+pub fn acquire(root: &Path, create: bool) -> Result<Option<ProjectLock>> {
+    let cloak_dir = root.join(mover::CLOAK_DIR);
+    if !cloak_dir.is_dir() {
+        if !create {
+            return Ok(None);
+        }
+        fs::create_dir_all(&cloak_dir)
+            .with_context(|| format!("failed to create {}", cloak_dir.display()))?;
+    }
+
+    let path = cloak_dir.join(LOCK_FILE);
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            let holder = fs::read_to_string(&path)
+                .ok()
+                .and_then(|pid| pid.trim().parse::<u32>().ok())
+                .map_or_else(String::new, |pid| format!(" (pid {pid})"));
+            bail!(CloakError::Conflict(format!(
+                "another cloak process{holder} is working in {}; try again once it has finished",
+                root.display()
+            )));
+        }
+        Err(TryLockError::Error(e)) => {
+            return Err(e).with_context(|| format!("failed to lock {}", path.display()));
+        }
+    }
+
+    // Record the owner for the message above and for `gc`'s stale-lock check.
+    file.set_len(0)
+        .and_then(|()| write!(file, "{}", std::process::id()))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(Some(ProjectLock { _file: file }))
+}
+
+/// Whether some process currently holds the lock on `path`.
+pub fn is_held(path: &Path) -> bool {
+    File::open(path).is_ok_and(|file| matches!(file.try_lock(), Err(TryLockError::WouldBlock)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_acquire_is_refused_until_the_first_is_dropped() {
+        let root = std::env::temp_dir().join(format!("cloak-lock-{}", std::process::id()));
+        fs::create_dir_all(&root).expect("failed to create temp dir");
+
+        assert!(acquire(&root, false).expect("acquire failed").is_none());
+        assert!(!root.join(mover::CLOAK_DIR).exists());
+
+        let held = acquire(&root, true).expect("acquire failed");
+        assert!(held.is_some());
+        let err = acquire(&root, false).expect_err("second lock must be refused");
+        assert_eq!(crate::error::exit_code(&err), 4);
+        assert!(format!("{err}").contains(&format!("pid {}", std::process::id())));
+
+        drop(held);
+        assert!(acquire(&root, false).expect("acquire failed").is_some());
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+}
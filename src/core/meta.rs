@@ -0,0 +1,313 @@
+use crate::core::linker::{LinkKind, LinkStyle};
+use crate::core::mover;
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const META_FILE: &str = "meta.json";
+const META_VERSION: u64 = 1;
+
+/// What cloak remembers about one hidden target beyond what the filesystem shows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entry {
+    /// How the ghost link's target text was written.
+    pub link_style: Option<LinkStyle>,
+    /// When the target was hidden (seconds since the Unix epoch).
+    pub hidden_at: Option<u64>,
+    /// Mode applied to the stored entry by `hide --chmod`; kept on unhide.
+    pub chmod: Option<u32>,
+    /// Permission bits the target had at the root when it was hidden (Unix).
+    pub mode: Option<u32>,
+    /// Root name of the ghost link when `hide --link-name` chose one other than the target.
+    pub link_name: Option<String>,
+    /// Whether the ghost link was made as a symlink or (Windows) a junction.
+    pub link_kind: Option<LinkKind>,
+    /// Why the target is hidden, for collaborators (`hide --label`, `cloak label`).
+    pub label: Option<String>,
+    /// Root path the entry was hidden from when `hide --flatten` stored it under
+    /// another name; unhide restores it there.
+    pub origin: Option<String>,
+    /// Whether `hide --immutable` set the OS immutable flag on the stored entry;
+    /// unhide clears it before moving the content back.
+    pub immutable: bool,
+}
+
+impl Entry {
+    fn to_json(&self) -> Value {
+        let mut map = Map::new();
+        if let Some(style) = self.link_style {
+            map.insert("link_style".into(), Value::String(style.as_str().into()));
+        }
+        if let Some(ts) = self.hidden_at {
+            map.insert("hidden_at".into(), Value::from(ts));
+        }
+        if let Some(mode) = self.chmod {
+            map.insert("chmod".into(), Value::String(format!("{mode:04o}")));
+        }
+        if let Some(mode) = self.mode {
+            map.insert("mode".into(), Value::String(format!("{mode:04o}")));
+        }
+        if let Some(name) = &self.link_name {
+            map.insert("link_name".into(), Value::String(name.clone()));
+        }
+        if let Some(kind) = self.link_kind {
+            map.insert("link_kind".into(), Value::String(kind.as_str().into()));
+        }
+        if let Some(label) = &self.label {
+            map.insert("label".into(), Value::String(label.clone()));
+        }
+        if let Some(origin) = &self.origin {
+            map.insert("origin".into(), Value::String(origin.clone()));
+        }
+        if self.immutable {
+            map.insert("immutable".into(), Value::Bool(true));
+        }
+        Value::Object(map)
+    }
+
+    fn from_json(value: &Value) -> Self {
+        Self {
+            link_style: value
+                .get("link_style")
+                .and_then(Value::as_str)
+                .and_then(LinkStyle::parse),
+            hidden_at: value.get("hidden_at").and_then(Value::as_u64),
+            chmod: value
+                .get("chmod")
+                .and_then(Value::as_str)
+                .and_then(|s| u32::from_str_radix(s, 8).ok()),
+            mode: value
+                .get("mode")
+                .and_then(Value::as_str)
+                .and_then(|s| u32::from_str_radix(s, 8).ok()),
+            link_name: value
+                .get("link_name")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            link_kind: value
+                .get("link_kind")
+                .and_then(Value::as_str)
+                .and_then(LinkKind::parse),
+            label: value
+                .get("label")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            origin: value
+                .get("origin")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            immutable: value
+                .get("immutable")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// `.cloak/meta.json`: per-target records keyed by root-relative target name, plus
+/// where storage lives when it is kept outside the project.
+///
+/// Lives beside storage (not inside it), so it is covered by the `/.cloak/*` ignore rule.
+#[derive(Debug, Default)]
+pub struct Meta {
+    entries: BTreeMap<String, Entry>,
+    external_storage: Option<PathBuf>,
+}
+
+fn meta_path(root: &Path) -> PathBuf {
+    root.join(mover::CLOAK_DIR).join(META_FILE)
+}
+
+impl Meta {
+    /// Load meta, returning an empty record set when the file does not exist.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = meta_path(root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let value: Value = serde_json::from_str(&content)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+
+        let Some(entries) = value.get("entries").and_then(Value::as_object) else {
+            bail!("{}: missing `entries` object", path.display());
+        };
+
+        Ok(Self {
+            entries: entries
+                .iter()
+                .map(|(name, entry)| (name.clone(), Entry::from_json(entry)))
+                .collect(),
+            external_storage: value
+                .get("external_storage")
+                .and_then(Value::as_str)
+                .map(PathBuf::from),
+        })
+    }
+
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let path = meta_path(root);
+        let entries: Map<String, Value> = self
+            .entries
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.to_json()))
+            .collect();
+
+        let mut doc = Map::new();
+        doc.insert("version".into(), Value::from(META_VERSION));
+        doc.insert("entries".into(), Value::Object(entries));
+        if let Some(path) = &self.external_storage {
+            doc.insert(
+                "external_storage".into(),
+                Value::String(path.to_string_lossy().into_owned()),
+            );
+        }
+
+        let mut content = serde_json::to_string_pretty(&Value::Object(doc))
+            .context("failed to serialize meta")?;
+        content.push('\n');
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create directory: {}", parent.display()))?;
+        }
+        fs::write(&path, content.as_bytes())
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn get(&self, target: &str) -> Option<&Entry> {
+        self.entries.get(target)
+    }
+
+    /// Get the record for `target`, creating an empty one if needed.
+    pub fn entry_mut(&mut self, target: &str) -> &mut Entry {
+        self.entries.entry(target.to_string()).or_default()
+    }
+
+    /// The root name of `target`'s ghost link: its `--link-name`, or the target itself.
+    pub fn link_name<'a>(&'a self, target: &'a str) -> &'a str {
+        self.get(target)
+            .and_then(|e| e.link_name.as_deref())
+            .unwrap_or(target)
+    }
+
+    /// Where `target`'s content lives at the root once restored: its `--flatten`
+    /// origin, or the target itself.
+    pub fn origin<'a>(&'a self, target: &'a str) -> &'a str {
+        self.get(target)
+            .and_then(|e| e.origin.as_deref())
+            .unwrap_or(target)
+    }
+
+    /// The storage name of whatever was hidden from root path `path`: the flattened
+    /// entry recorded with that origin, or `path` itself.
+    pub fn storage_key<'a>(&'a self, path: &'a str) -> &'a str {
+        self.entries
+            .iter()
+            .find(|(_, e)| e.origin.as_deref() == Some(path))
+            .map_or(path, |(key, _)| key.as_str())
+    }
+
+    /// Every recorded target, in name order.
+    pub fn targets(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Recorded targets whose storage entry is gone, e.g. deleted by hand or
+    /// never pulled after a clone, in name order.
+    pub fn missing_from_storage(&self, root: &Path) -> Vec<&str> {
+        let storage = mover::storage_dir(root);
+        self.targets()
+            .filter(|target| storage.join(target).symlink_metadata().is_err())
+            .collect()
+    }
+
+    pub fn remove(&mut self, target: &str) -> Option<Entry> {
+        self.entries.remove(target)
+    }
+
+    /// The directory `.cloak/storage` should link to, or `None` for inline storage.
+    pub fn external_storage(&self) -> Option<&Path> {
+        self.external_storage.as_deref()
+    }
+
+    pub fn set_external_storage(&mut self, path: Option<PathBuf>) {
+        self.external_storage = path;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn make_temp_dir(prefix: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock before epoch")
+            .as_nanos();
+        let pid = std::process::id();
+        let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!("cloak-{prefix}-{pid}-{nanos}-{seq}"));
+        fs::create_dir_all(&dir).expect("failed to create temp test dir");
+        dir
+    }
+
+    #[test]
+    fn meta_round_trips_through_disk() {
+        let root = make_temp_dir("meta-roundtrip");
+
+        let mut meta = Meta::load(&root).expect("load empty meta failed");
+        meta.entry_mut(".cursor").link_style = Some(LinkStyle::Absolute);
+        meta.entry_mut(".cursor").hidden_at = Some(1_700_000_000);
+        meta.entry_mut(".cursor").chmod = Some(0o600);
+        meta.entry_mut(".cursor").mode = Some(0o755);
+        meta.entry_mut(".cursor").link_name = Some(".cursor-active".into());
+        meta.entry_mut(".cursor").link_kind = Some(LinkKind::Junction);
+        meta.entry_mut(".cursor").label = Some("shared MCP servers".into());
+        meta.entry_mut(".idea");
+        meta.entry_mut("a__b__.env").origin = Some("a/b/.env".into());
+        meta.set_external_storage(Some(PathBuf::from("/data/cloak/web")));
+        meta.save(&root).expect("save meta failed");
+
+        let loaded = Meta::load(&root).expect("reload meta failed");
+        assert_eq!(
+            loaded.get(".cursor").and_then(|e| e.link_style),
+            Some(LinkStyle::Absolute)
+        );
+        assert_eq!(
+            loaded.get(".cursor").and_then(|e| e.hidden_at),
+            Some(1_700_000_000)
+        );
+        assert_eq!(loaded.get(".cursor").and_then(|e| e.chmod), Some(0o600));
+        assert_eq!(loaded.get(".cursor").and_then(|e| e.mode), Some(0o755));
+        assert_eq!(loaded.link_name(".cursor"), ".cursor-active");
+        assert_eq!(
+            loaded.get(".cursor").and_then(|e| e.link_kind),
+            Some(LinkKind::Junction)
+        );
+        assert_eq!(
+            loaded.get(".cursor").and_then(|e| e.label.as_deref()),
+            Some("shared MCP servers")
+        );
+        assert_eq!(loaded.link_name(".idea"), ".idea");
+        assert_eq!(loaded.get(".idea"), Some(&Entry::default()));
+        assert_eq!(loaded.storage_key("a/b/.env"), "a__b__.env");
+        assert_eq!(loaded.origin("a__b__.env"), "a/b/.env");
+        assert_eq!(loaded.storage_key(".idea"), ".idea");
+        assert_eq!(
+            loaded.external_storage(),
+            Some(Path::new("/data/cloak/web"))
+        );
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+}
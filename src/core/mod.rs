@@ -1,3 +1,14 @@
+pub mod bundle;
+pub mod doctor;
+pub mod gc;
 pub mod hider;
 pub mod linker;
+pub mod lock;
+pub mod meta;
 pub mod mover;
+pub mod pipeline;
+pub mod repair;
+pub mod snapshot;
+pub mod storage;
+pub mod verify;
+pub mod watch;
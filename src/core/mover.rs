@@ -1,9 +1,17 @@
+use crate::core::verify::Fingerprint;
+use crate::error::CloakError;
+use crate::utils::output::trace;
 use anyhow::{Context, Result, bail};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-const CLOAK_DIR: &str = ".cloak";
-const STORAGE_DIR: &str = "storage";
+pub const CLOAK_DIR: &str = ".cloak";
+pub const STORAGE_DIR: &str = "storage";
+
+/// Path to `.cloak/storage/` under the project root.
+pub fn storage_dir(root: &Path) -> PathBuf {
+    root.join(CLOAK_DIR).join(STORAGE_DIR)
+}
 
 /// Ensure `.cloak/storage/` exists.
 pub fn ensure_storage_dir(root: &Path) -> Result<()> {
@@ -13,6 +21,187 @@ pub fn ensure_storage_dir(root: &Path) -> Result<()> {
     Ok(())
 }
 
+const README_FILE: &str = "README.md";
+const README: &str = "\
+# .cloak
+
+This project uses cloak to keep editor and AI tool configs (`.cursor`, `.claude`,
+...) out of the project root.
+
+- `storage/` holds the real files and is committed. Edit them here or through the links.
+- The matching root entries (e.g. `.cursor`) are symlinks into `storage/`, ignored by git.
+- Everything else in this directory is local state and is not committed.
+
+After cloning, recreate the links, IDE excludes and ignore rules with:
+
+    cloak relink
+
+To move something back to the root for good, run `cloak unhide <name>`.
+";
+
+/// Write `.cloak/README.md` for collaborators who find `.cloak/storage` in the repo.
+/// An existing README, possibly edited, is left alone.
+pub fn ensure_readme(root: &Path) -> Result<()> {
+    let path = root.join(CLOAK_DIR).join(README_FILE);
+    if path.exists() {
+        return Ok(());
+    }
+    fs::write(&path, README).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Point `.cloak/storage` at `external` (created if needed) instead of a local directory.
+///
+/// An existing empty local storage directory is replaced; a link that already points
+/// at `external` is left alone. Anything else is refused so stored data is never orphaned.
+pub fn link_external_storage(root: &Path, external: &Path) -> Result<()> {
+    let storage = storage_dir(root);
+    fs::create_dir_all(external).with_context(|| {
+        format!(
+            "failed to create external storage directory: {}",
+            external.display()
+        )
+    })?;
+
+    if let Ok(meta) = storage.symlink_metadata() {
+        if meta.file_type().is_symlink() {
+            if fs::read_link(&storage).is_ok_and(|current| current == external) {
+                return Ok(());
+            }
+            bail!(
+                "{} already links elsewhere; remove it first to change storage",
+                storage.display()
+            );
+        }
+        let is_empty_dir = meta.is_dir()
+            && fs::read_dir(&storage).is_ok_and(|mut entries| entries.next().is_none());
+        if !is_empty_dir {
+            bail!(
+                "{} already holds data; unhide everything before moving storage",
+                storage.display()
+            );
+        }
+        fs::remove_dir(&storage).with_context(|| {
+            format!("failed to remove empty storage dir: {}", storage.display())
+        })?;
+    }
+
+    let cloak_dir = root.join(CLOAK_DIR);
+    fs::create_dir_all(&cloak_dir)
+        .with_context(|| format!("failed to create directory: {}", cloak_dir.display()))?;
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(external, &storage).with_context(|| {
+        format!(
+            "failed to link {} -> {}",
+            storage.display(),
+            external.display()
+        )
+    })?;
+
+    #[cfg(windows)]
+    std::os::windows::fs::symlink_dir(external, &storage)
+        .or_else(|_| junction::create(external, &storage))
+        .with_context(|| {
+            format!(
+                "failed to link {} -> {}",
+                storage.display(),
+                external.display()
+            )
+        })?;
+
+    Ok(())
+}
+
+/// Refuse a storage move to `dest` that `relocate_storage` could not finish: the
+/// current location, a non-empty directory, or somewhere inside storage itself.
+/// Nothing is changed, so callers can run it before unlinking anything.
+pub fn check_relocation(root: &Path, dest: &Path) -> Result<()> {
+    let inline = storage_dir(root);
+    let is_link = inline
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink());
+    let current = inline
+        .canonicalize()
+        .with_context(|| format!("storage directory not found: {}", inline.display()))?;
+
+    if dest == inline {
+        if !is_link {
+            bail!("storage already lives at {}", dest.display());
+        }
+    } else if dest.exists() {
+        let dest_real = dest.canonicalize()?;
+        if dest_real == current {
+            bail!("storage already lives at {}", dest.display());
+        }
+        if fs::read_dir(dest)?.next().is_some() {
+            bail!(
+                "destination is not empty: {} (choose an empty or new directory)",
+                dest.display()
+            );
+        }
+    }
+    let dest_abs = if dest.is_absolute() {
+        dest.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(dest)
+    };
+    if dest_abs.starts_with(&current) {
+        bail!("cannot move storage into itself: {}", dest.display());
+    }
+    Ok(())
+}
+
+/// Move every stored entry from wherever `.cloak/storage` currently lives to `dest`,
+/// then make `.cloak/storage` point there: a real directory when `dest` is the inline
+/// location itself, a symlink otherwise. Returns how many entries were moved.
+///
+/// Ghost links are the caller's concern; entries move one by one with the usual
+/// cross-device fallback.
+pub fn relocate_storage(root: &Path, dest: &Path) -> Result<usize> {
+    check_relocation(root, dest)?;
+    let inline = storage_dir(root);
+    let is_link = inline
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink());
+    let current = inline
+        .canonicalize()
+        .with_context(|| format!("storage directory not found: {}", inline.display()))?;
+    let dest_is_inline = dest == inline;
+
+    if dest_is_inline {
+        // Drop the link first so the inline directory can take its place.
+        fs::remove_file(&inline)
+            .or_else(|_| fs::remove_dir(&inline))
+            .with_context(|| format!("failed to remove storage link: {}", inline.display()))?;
+    }
+    fs::create_dir_all(dest)
+        .with_context(|| format!("failed to create directory: {}", dest.display()))?;
+
+    let mut moved = 0;
+    for entry in fs::read_dir(&current)
+        .with_context(|| format!("failed to read storage directory: {}", current.display()))?
+    {
+        let entry = entry?;
+        move_path(&entry.path(), &dest.join(entry.file_name()))?;
+        moved += 1;
+    }
+
+    if !dest_is_inline {
+        if is_link {
+            fs::remove_file(&inline)
+                .or_else(|_| fs::remove_dir(&inline))
+                .with_context(|| format!("failed to remove storage link: {}", inline.display()))?;
+        }
+        link_external_storage(root, dest)?;
+    }
+    if is_link {
+        // The old external directory is empty now; leave it if something else appeared.
+        let _ = fs::remove_dir(&current);
+    }
+
+    Ok(moved)
+}
+
 /// Move a path, falling back to copy+delete if rename fails with a cross-device error.
 fn move_path(src: &Path, dest: &Path) -> Result<()> {
     match fs::rename(src, dest) {
@@ -40,22 +229,84 @@ fn is_cross_device_error(e: &std::io::Error) -> bool {
     false
 }
 
+/// Check if an IO error means the destination filesystem is full (ENOSPC).
+fn is_out_of_space_error(e: &std::io::Error) -> bool {
+    if e.kind() == std::io::ErrorKind::StorageFull {
+        return true;
+    }
+    // ENOSPC is errno 28 on Linux and macOS
+    #[cfg(unix)]
+    if e.raw_os_error() == Some(28) {
+        return true;
+    }
+    // ERROR_HANDLE_DISK_FULL / ERROR_DISK_FULL
+    #[cfg(windows)]
+    if matches!(e.raw_os_error(), Some(39 | 112)) {
+        return true;
+    }
+    false
+}
+
+/// Whether any cause in `err` is an out-of-space IO error.
+fn ran_out_of_space(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(is_out_of_space_error)
+    })
+}
+
+/// Free space a cross-device copy must leave on the destination, so that it
+/// never fills the device completely.
+const SPACE_MARGIN: u64 = 8 * 1024 * 1024;
+
 /// Copy src to dest, then delete src. Handles both files and directories.
+///
+/// Nothing is copied unless the destination filesystem has room for all of src
+/// plus `SPACE_MARGIN`. Both copy paths stream through a fixed-size buffer, so large files are never
+/// held in memory. If the destination fills up, or the copy does not match the
+/// source, the copy is removed and the source is left untouched.
 fn copy_and_delete(src: &Path, dest: &Path) -> Result<()> {
-    if src.is_dir() {
-        let mut options = fs_extra::dir::CopyOptions::new();
-        options.copy_inside = true;
-        options.content_only = true;
-        fs::create_dir_all(dest).with_context(|| {
-            format!("failed to create destination directory: {}", dest.display())
-        })?;
-        fs_extra::dir::copy(src, dest, &options).with_context(|| {
-            format!(
-                "cross-device fallback: failed to copy directory {} -> {}",
-                src.display(),
+    ensure_room(src, dest)?;
+    trace!(
+        "copy {} -> {} (different filesystems)",
+        src.display(),
+        dest.display()
+    );
+    if let Err(e) = copy_path(src, dest) {
+        if !ran_out_of_space(&e) {
+            return Err(e.context("cross-device fallback"));
+        }
+        let cleanup = if dest.symlink_metadata().is_ok() {
+            remove_path(dest).err()
+        } else {
+            None
+        };
+        let mut msg = format!(
+            "destination is out of space: {} (free up space and retry; {} was left in place)",
+            dest.parent().unwrap_or(dest).display(),
+            src.display()
+        );
+        if let Some(cleanup) = cleanup {
+            msg.push_str(&format!(
+                "; the partial copy at {} could not be removed: {cleanup:#}",
                 dest.display()
-            )
-        })?;
+            ));
+        }
+        return Err(e.context(msg));
+    }
+    if let Err(e) = verify_copy(src, dest) {
+        let mut msg = format!(
+            "cross-device fallback: the copy at {} does not match the source; {} was left in place",
+            dest.display(),
+            src.display()
+        );
+        if let Err(cleanup) = remove_path(dest) {
+            msg.push_str(&format!("; the bad copy could not be removed: {cleanup:#}"));
+        }
+        return Err(e.context(msg));
+    }
+    if src.is_dir() {
         fs::remove_dir_all(src).with_context(|| {
             format!(
                 "cross-device fallback: failed to remove source directory: {}",
@@ -63,13 +314,6 @@ fn copy_and_delete(src: &Path, dest: &Path) -> Result<()> {
             )
         })?;
     } else {
-        fs::copy(src, dest).with_context(|| {
-            format!(
-                "cross-device fallback: failed to copy file {} -> {}",
-                src.display(),
-                dest.display()
-            )
-        })?;
         fs::remove_file(src).with_context(|| {
             format!(
                 "cross-device fallback: failed to remove source file: {}",
@@ -80,31 +324,390 @@ fn copy_and_delete(src: &Path, dest: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Move a target from project root into `.cloak/storage/`.
-pub fn ingest(root: &Path, target: &str) -> Result<()> {
+/// Fail before anything is copied if the filesystem `dest` would go on cannot hold
+/// `src` and still keep `SPACE_MARGIN` free. Unknown free space is not an error:
+/// the copy then relies on its out-of-space cleanup.
+fn ensure_room(src: &Path, dest: &Path) -> Result<()> {
+    let Some(free) = dest.parent().and_then(free_space) else {
+        return Ok(());
+    };
+    let needed = size_of(src);
+    if needed.saturating_add(SPACE_MARGIN) > free {
+        bail!(
+            "insufficient space on {}: {} needs {needed} bytes plus a {SPACE_MARGIN}-byte margin, \
+             but only {free} bytes are free (nothing was copied; {} was left in place)",
+            dest.parent().unwrap_or(dest).display(),
+            src.display(),
+            src.display()
+        );
+    }
+    Ok(())
+}
+
+/// Bytes available to this user on the filesystem holding `path`, if it can be told.
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::useless_conversion)]
+    let (blocks, block_size) = (u64::from(stat.f_bavail), u64::from(stat.f_frsize));
+    Some(blocks.saturating_mul(block_size))
+}
+
+#[cfg(windows)]
+fn free_space(path: &Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::fileapi::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut free = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut free as *mut u64 as *mut _,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    (ok != 0).then_some(free)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Copy src to dest without removing src. Handles both files and directories.
+///
+/// Symlinks inside a directory are recreated as symlinks with the same text, not
+/// followed, and every other entry keeps its permissions and modification time.
+pub fn copy_path(src: &Path, dest: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(src) {
+        let entry = entry.with_context(|| format!("failed to walk {}", src.display()))?;
+        let to = counterpart(src, dest, entry.path());
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            fs::create_dir_all(&to).with_context(|| {
+                format!("failed to create destination directory: {}", to.display())
+            })?;
+        } else if file_type.is_symlink() {
+            copy_symlink(entry.path(), &to)?;
+        } else {
+            fs::copy(entry.path(), &to).with_context(|| {
+                format!(
+                    "failed to copy file {} -> {}",
+                    entry.path().display(),
+                    to.display()
+                )
+            })?;
+        }
+    }
+    copy_metadata(src, dest)
+}
+
+/// Where `path`, inside the tree at `src`, goes in its copy at `dest`. `src`
+/// itself maps to `dest` exactly: joining an empty path would add a trailing
+/// slash, which a file path cannot take.
+fn counterpart(src: &Path, dest: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(src) {
+        Ok(rel) if !rel.as_os_str().is_empty() => dest.join(rel),
+        _ => dest.to_path_buf(),
+    }
+}
+
+/// Recreate the symlink `src` at `dest` with the same (possibly relative) text.
+fn copy_symlink(src: &Path, dest: &Path) -> Result<()> {
+    let text =
+        fs::read_link(src).with_context(|| format!("failed to read link {}", src.display()))?;
+    #[cfg(unix)]
+    let made = std::os::unix::fs::symlink(&text, dest);
+    #[cfg(windows)]
+    let made = if src.is_dir() {
+        std::os::windows::fs::symlink_dir(&text, dest)
+    } else {
+        std::os::windows::fs::symlink_file(&text, dest)
+    };
+    made.with_context(|| {
+        format!(
+            "failed to copy symlink {} -> {}",
+            src.display(),
+            dest.display()
+        )
+    })
+}
+
+/// Check that `dest` holds everything `src` does, before `src` is deleted: the same
+/// number of entries, each present, and every file of the same length and content hash.
+fn verify_copy(src: &Path, dest: &Path) -> Result<()> {
+    let mut entries = 0usize;
+    for entry in walkdir::WalkDir::new(src) {
+        let entry = entry.with_context(|| format!("failed to walk {}", src.display()))?;
+        let rel = entry.path().strip_prefix(src).unwrap_or(Path::new(""));
+        let copy = counterpart(src, dest, entry.path());
+        let shown = if rel.as_os_str().is_empty() {
+            src.display().to_string()
+        } else {
+            rel.display().to_string()
+        };
+        if copy.symlink_metadata().is_err() {
+            bail!("{shown} is missing from the copy");
+        }
+        if entry.file_type().is_file() && Fingerprint::of(entry.path())? != Fingerprint::of(&copy)?
+        {
+            bail!("{shown} differs from the original in the copy");
+        }
+        entries += 1;
+    }
+
+    // What a symlink in `src` was copied as is not compared, only that it is there.
+    let copied = walkdir::WalkDir::new(dest)
+        .into_iter()
+        .filter_entry(|e| {
+            let rel = e.path().strip_prefix(dest).unwrap_or(Path::new(""));
+            rel.parent()
+                .is_none_or(|parent| !src.join(parent).is_symlink())
+        })
+        .count();
+    if copied != entries {
+        bail!("the copy holds {copied} entries, expected {entries}");
+    }
+    Ok(())
+}
+
+/// Mirror the modification time and permissions of every entry under `src` onto
+/// the same path under `dest`.
+///
+/// `fs::copy` already keeps file modes, but directories are created with the
+/// default mode, so e.g. a `0700` hooks directory would come back world-readable.
+/// Children go first, so a directory's time is set after its contents are final.
+fn copy_metadata(src: &Path, dest: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(src).contents_first(true) {
+        let entry = entry.with_context(|| format!("failed to walk {}", src.display()))?;
+        if entry.path_is_symlink() && entry.depth() > 0 {
+            continue;
+        }
+        let target = counterpart(src, dest, entry.path());
+        let meta = entry
+            .metadata()
+            .with_context(|| format!("failed to stat {}", entry.path().display()))?;
+        if let Ok(modified) = meta.modified() {
+            set_modified(&target, modified)?;
+        }
+        fs::set_permissions(&target, meta.permissions())
+            .with_context(|| format!("failed to set permissions on {}", target.display()))?;
+    }
+    Ok(())
+}
+
+fn set_modified(path: &Path, time: std::time::SystemTime) -> Result<()> {
+    #[cfg(unix)]
+    let file = fs::File::open(path);
+    #[cfg(windows)]
+    let file = {
+        use std::os::windows::fs::OpenOptionsExt;
+        // FILE_WRITE_ATTRIBUTES, and FILE_FLAG_BACKUP_SEMANTICS so directories open too.
+        fs::File::options()
+            .access_mode(0x100)
+            .custom_flags(0x0200_0000)
+            .open(path)
+    };
+    file.and_then(|file| file.set_modified(time))
+        .with_context(|| format!("failed to set modification time on {}", path.display()))
+}
+
+/// The permission bits of `path` itself (not following a symlink), or `None`
+/// when it cannot be read or the platform has no such bits.
+#[cfg(unix)]
+pub fn mode_of(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let meta = path.symlink_metadata().ok()?;
+    Some(meta.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+pub fn mode_of(_path: &Path) -> Option<u32> {
+    None
+}
+
+/// Apply `mode` to a stored entry: files get `mode`, directories get `mode` plus an
+/// execute bit wherever it grants read (so `0600` becomes `0700` for directories).
+#[cfg(unix)]
+pub fn apply_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir_mode = mode | ((mode & 0o444) >> 2);
+    // Children first, so tightening a directory never locks us out of its contents.
+    for entry in walkdir::WalkDir::new(path).contents_first(true) {
+        let entry = entry.with_context(|| format!("failed to walk {}", path.display()))?;
+        if entry.path_is_symlink() {
+            continue;
+        }
+        let bits = if entry.file_type().is_dir() {
+            dir_mode
+        } else {
+            mode
+        };
+        fs::set_permissions(entry.path(), fs::Permissions::from_mode(bits)).with_context(|| {
+            format!("failed to set mode {bits:o} on {}", entry.path().display())
+        })?;
+    }
+    Ok(())
+}
+
+/// Put back the permission bits `mode_of` recorded on `path` itself; its
+/// contents are left as they are.
+#[cfg(unix)]
+pub fn restore_mode(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("failed to set mode {mode:o} on {}", path.display()))
+}
+
+/// The first free `<path>.cloak-bak[.N]` to move something aside to.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let base = format!("{}.cloak-bak", path.display());
+    let mut candidate = PathBuf::from(&base);
+    let mut n = 1;
+    while candidate.symlink_metadata().is_ok() {
+        candidate = PathBuf::from(format!("{base}.{n}"));
+        n += 1;
+    }
+    candidate
+}
+
+/// Remove a file, symlink, or directory tree.
+pub fn remove_path(path: &Path) -> Result<()> {
+    let meta = fs::symlink_metadata(path)
+        .with_context(|| format!("path does not exist: {}", path.display()))?;
+    if meta.is_dir() {
+        fs::remove_dir_all(path)
+            .with_context(|| format!("failed to remove directory: {}", path.display()))?;
+    } else {
+        fs::remove_file(path)
+            .with_context(|| format!("failed to remove file: {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Total size in bytes of a file or directory tree (best effort; unreadable entries count as 0).
+pub fn size_of(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Move `target` from project root into `.cloak/storage/` as `key`, which is the
+/// target's own name unless `hide --flatten` chose a flat one.
+pub fn ingest(root: &Path, target: &str, key: &str) -> Result<()> {
     let src = root.join(target);
-    let dest = root.join(CLOAK_DIR).join(STORAGE_DIR).join(target);
+    let dest = root.join(CLOAK_DIR).join(STORAGE_DIR).join(key);
 
     if !src.exists() {
-        bail!("target does not exist: {}", src.display());
+        bail!(CloakError::Invalid(format!(
+            "target does not exist: {}",
+            src.display()
+        )));
     }
 
+    ensure_hideable(&src)?;
+
     if dest.exists() {
-        bail!(
+        bail!(CloakError::Conflict(format!(
             "target already exists in storage: {} (already hidden?)",
             dest.display()
-        );
+        )));
     }
 
-    ensure_storage_dir(root)?;
+    // A nested key (`config/ai`) keeps its directory structure inside storage.
+    let parent = dest.parent().unwrap_or(&dest);
+    fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create storage directory: {}", parent.display()))?;
+    trace!("ingest {} -> {}", src.display(), dest.display());
     move_path(&src, &dest)?;
 
     Ok(())
 }
 
-/// Move a target from `.cloak/storage/` back to project root.
-pub fn egest(root: &Path, target: &str) -> Result<()> {
-    let src = root.join(CLOAK_DIR).join(STORAGE_DIR).join(target);
+/// Move `src`, which lives outside the project root (e.g. an unpacked bundle),
+/// into storage as `key`.
+pub fn adopt(root: &Path, src: &Path, key: &str) -> Result<()> {
+    let dest = storage_dir(root).join(key);
+    if dest.symlink_metadata().is_ok() {
+        bail!(CloakError::Conflict(format!(
+            "target already exists in storage: {}",
+            dest.display()
+        )));
+    }
+    let parent = dest.parent().unwrap_or(&dest);
+    fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create storage directory: {}", parent.display()))?;
+    trace!("adopt {} -> {}", src.display(), dest.display());
+    move_path(src, &dest)
+}
+
+/// Refuse paths that moving or copying would mangle: sockets, FIFOs, device files,
+/// and mount points (a directory on a different filesystem than its parent).
+pub fn ensure_hideable(path: &Path) -> Result<()> {
+    if let Some(kind) = special_kind(path) {
+        bail!(CloakError::Invalid(format!(
+            "refusing to hide a {kind}: {}",
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn special_kind(path: &Path) -> Option<&'static str> {
+    use std::os::unix::fs::{FileTypeExt, MetadataExt};
+
+    let meta = path.symlink_metadata().ok()?;
+    let file_type = meta.file_type();
+    if file_type.is_socket() {
+        return Some("socket");
+    }
+    if file_type.is_fifo() {
+        return Some("FIFO");
+    }
+    if file_type.is_block_device() || file_type.is_char_device() {
+        return Some("device file");
+    }
+    let parent = path.parent()?.metadata().ok()?;
+    (file_type.is_dir() && meta.dev() != parent.dev()).then_some("mount point")
+}
+
+#[cfg(not(unix))]
+fn special_kind(_path: &Path) -> Option<&'static str> {
+    None
+}
+
+/// The directory a nested target would be restored into, if it is missing at root.
+pub fn missing_parent(root: &Path, target: &str) -> Option<PathBuf> {
+    let parent = Path::new(target).parent()?;
+    if parent.as_os_str().is_empty() {
+        return None;
+    }
+    let dir = root.join(parent);
+    (!dir.is_dir()).then_some(dir)
+}
+
+/// Move storage entry `key` back to `target` at the project root.
+///
+/// A nested target whose parent directory is gone from the root is an error
+/// unless `parents` is set, in which case the parent is created.
+pub fn egest(root: &Path, target: &str, key: &str, parents: bool) -> Result<()> {
+    let src = root.join(CLOAK_DIR).join(STORAGE_DIR).join(key);
     let dest = root.join(target);
 
     if !src.exists() {
@@ -112,13 +715,151 @@ pub fn egest(root: &Path, target: &str) -> Result<()> {
     }
 
     if dest.exists() {
-        bail!(
+        bail!(CloakError::Conflict(format!(
             "target already exists at root: {} (remove the symlink first)",
             dest.display()
-        );
+        )));
+    }
+
+    if let Some(parent) = missing_parent(root, target) {
+        if !parents {
+            bail!(
+                "parent directory does not exist at root: {} (pass --parents to create it)",
+                parent.display()
+            );
+        }
+        fs::create_dir_all(&parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+
+    trace!("egest {} -> {}", src.display(), dest.display());
+    move_path(&src, &dest)?;
+    prune_empty_parents(root, key);
+
+    Ok(())
+}
+
+/// Move storage entry `from` to storage key `to` (e.g. into a category directory),
+/// creating `to`'s parents and removing the directories `from` leaves empty.
+pub fn rekey(root: &Path, from: &str, to: &str) -> Result<()> {
+    let storage = storage_dir(root);
+    let src = storage.join(from);
+    let dest = storage.join(to);
+
+    if dest.symlink_metadata().is_ok() {
+        bail!(CloakError::Conflict(format!(
+            "storage entry already exists: {}",
+            dest.display()
+        )));
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
     }
 
+    trace!("rekey {} -> {}", src.display(), dest.display());
     move_path(&src, &dest)?;
+    prune_empty_parents(root, from);
+    Ok(())
+}
 
+/// Delete storage entry `key`, and the directories that leaves empty.
+pub fn discard(root: &Path, key: &str) -> Result<()> {
+    let path = storage_dir(root).join(key);
+    trace!("discard {}", path.display());
+    remove_path(&path)?;
+    prune_empty_parents(root, key);
     Ok(())
 }
+
+/// Remove the directories a nested storage entry `key` left empty, up to (not
+/// including) storage itself, so they are not mistaken for entries later.
+fn prune_empty_parents(root: &Path, key: &str) {
+    let storage = storage_dir(root);
+    for parent in Path::new(key).ancestors().skip(1) {
+        if parent.as_os_str().is_empty() || fs::remove_dir(storage.join(parent)).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn out_of_space_is_detected_through_wrappers() {
+        let full = || io::Error::from(io::ErrorKind::StorageFull);
+        assert!(ran_out_of_space(
+            &anyhow::Error::new(full()).context("failed to copy file")
+        ));
+
+        #[cfg(unix)]
+        assert!(ran_out_of_space(&anyhow::Error::new(
+            io::Error::from_raw_os_error(28)
+        )));
+
+        assert!(!ran_out_of_space(&anyhow::Error::new(io::Error::from(
+            io::ErrorKind::PermissionDenied
+        ))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ensure_room_refuses_a_copy_larger_than_the_free_space() {
+        let base = std::env::temp_dir().join(format!("cloak-room-{}", std::process::id()));
+        fs::create_dir_all(&base).expect("failed to create temp dir");
+        let src = base.join("small");
+        fs::write(&src, "x").expect("write failed");
+        let free = free_space(&base).expect("statvfs failed");
+        assert!(free > 0);
+
+        if free > SPACE_MARGIN + 1 {
+            ensure_room(&src, &base.join("copy")).expect("a one-byte file must fit");
+        }
+        // A sparse file claims more bytes than the filesystem has free.
+        let big = base.join("big");
+        fs::File::create(&big)
+            .and_then(|f| f.set_len(free))
+            .expect("failed to create sparse file");
+        let err = ensure_room(&big, &base.join("copy")).expect_err("must not fit");
+        assert!(format!("{err}").contains("insufficient space"), "{err}");
+        assert!(!base.join("copy").exists());
+
+        fs::remove_dir_all(base).expect("cleanup failed");
+    }
+
+    #[test]
+    fn verify_copy_catches_missing_extra_and_changed_files() {
+        let base = std::env::temp_dir().join(format!("cloak-verify-copy-{}", std::process::id()));
+        let (src, dest) = (base.join("src"), base.join("dest"));
+        fs::create_dir_all(src.join("hooks")).expect("failed to create src");
+        fs::write(src.join("hooks").join("pre"), "#!/bin/sh\n").expect("write failed");
+        fs::write(src.join("settings.json"), "{}").expect("write failed");
+
+        copy_path(&src, &dest).expect("copy failed");
+        verify_copy(&src, &dest).expect("a faithful copy must verify");
+
+        fs::write(dest.join("settings.json"), "{ }").expect("write failed");
+        let err = verify_copy(&src, &dest).expect_err("changed content must fail");
+        assert!(format!("{err}").contains("settings.json"), "{err}");
+
+        fs::write(dest.join("settings.json"), "{}").expect("write failed");
+        fs::remove_file(dest.join("hooks").join("pre")).expect("remove failed");
+        assert!(verify_copy(&src, &dest).is_err());
+
+        fs::write(dest.join("hooks").join("pre"), "#!/bin/sh\n").expect("write failed");
+        fs::write(dest.join("stray"), "").expect("write failed");
+        assert!(verify_copy(&src, &dest).is_err());
+
+        let file = base.join("single");
+        fs::write(&file, "abc").expect("write failed");
+        fs::write(base.join("single-copy"), "ab").expect("write failed");
+        fs::write(base.join("same-copy"), "abc").expect("write failed");
+        verify_copy(&file, &base.join("same-copy")).expect("an equal file must verify");
+        assert!(verify_copy(&file, &base.join("single-copy")).is_err());
+
+        fs::remove_dir_all(base).expect("cleanup failed");
+    }
+}
@@ -0,0 +1,528 @@
+//! The per-target hide and unhide pipeline shared by the CLI and [`CloakProject`].
+//!
+//! `stage_hide` does the part of a hide that only touches the target's own paths,
+//! so a batch can run it on worker threads; `finish_hide` then makes the edits to
+//! files every target shares and records the target in meta.json.
+//!
+//! [`CloakProject`]: crate::CloakProject
+
+use crate::config::{self, ide::IdeOptions, project::ProjectConfig};
+use crate::core::linker::{self, LinkHealth, LinkStyle};
+use crate::core::meta::Meta;
+use crate::core::{hider, mover, snapshot, storage, verify};
+use crate::error::CloakError;
+use crate::utils::{git, time};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::Path;
+
+/// How each target is hidden (`hide`, `tidy`, `import`, the library).
+#[derive(Debug, Clone, Default)]
+pub struct HideOptions {
+    pub snapshot: bool,
+    pub link_style: LinkStyle,
+    pub ide: IdeOptions,
+    /// Export known tool env vars for hidden targets to `.cloak/env`.
+    pub env_detect: bool,
+    /// Leave `.gitignore` alone instead of adding a rule per target.
+    pub no_gitignore: bool,
+    /// Add each target's rule to `.git/info/exclude` rather than `.gitignore`.
+    pub local_ignore: bool,
+    /// Re-check each link against the original content once it is in place.
+    pub verify: bool,
+    /// Root name for the ghost link of the single target being hidden.
+    pub link_name: Option<String>,
+    /// Store nested targets under their flat key instead of their own path.
+    pub flatten: bool,
+    /// Replace a target's existing storage copy with the content back at the root.
+    pub update: bool,
+    /// Recreate an already-hidden target's broken or misdirected ghost link.
+    pub force: bool,
+    /// Mode to apply to each stored entry right after it is moved into storage.
+    pub chmod: Option<u32>,
+    /// Recorded in meta as why the targets are hidden.
+    pub label: Option<String>,
+}
+
+impl HideOptions {
+    /// The storage name for `target`: its flat key with `--flatten`, else itself.
+    pub fn storage_key(&self, target: &str) -> String {
+        if self.flatten {
+            storage::flat_key(target)
+        } else {
+            target.to_string()
+        }
+    }
+
+    /// The root path of `target`'s ghost link: `--link-name`, else the target itself.
+    pub fn link<'a>(&'a self, target: &'a str) -> &'a str {
+        self.link_name.as_deref().unwrap_or(target)
+    }
+}
+
+/// How each target is restored (`unhide`, `prune --restore`, the library).
+#[derive(Debug, Clone, Default)]
+pub struct UnhideOptions {
+    /// Create a nested target's missing parent directory instead of failing.
+    pub parents: bool,
+    /// Leave the target's `.gitignore` rule in place.
+    pub no_gitignore: bool,
+    /// Back up a real file/dir occupying the target path instead of failing.
+    pub force: bool,
+    /// Report how storage differs from `HEAD` before restoring it.
+    pub diff_storage: bool,
+}
+
+/// Refuse a nested target that sits below a symlink at the root: hiding it would
+/// move content that is already in storage (or somewhere else entirely).
+pub fn ensure_outside_hidden(root: &Path, target: &str) -> Result<()> {
+    for ancestor in Path::new(target).ancestors().skip(1) {
+        if ancestor.as_os_str().is_empty() {
+            break;
+        }
+        if root
+            .join(ancestor)
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            bail!(CloakError::Invalid(format!(
+                "{target} is inside {}, which is a symlink (already hidden?); \
+                 hide or unhide {} as a whole instead",
+                ancestor.display(),
+                ancestor.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// A target counts as already hidden when its root path is a link and storage holds it.
+pub fn is_already_hidden(root: &Path, target: &str, key: &str) -> bool {
+    let is_link = root
+        .join(target)
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink());
+    is_link
+        && mover::storage_dir(root)
+            .join(key)
+            .symlink_metadata()
+            .is_ok()
+}
+
+/// With `--force`, why an already-hidden target's ghost link needs recreating:
+/// it is broken, dangling, or resolves somewhere other than its storage entry.
+pub fn stale_link(root: &Path, target: &str, opts: &HideOptions) -> Option<&'static str> {
+    if !opts.force {
+        return None;
+    }
+    match linker::link_health_as(root, opts.link(target), &opts.storage_key(target)) {
+        Some(LinkHealth::Broken) => Some("broken"),
+        Some(LinkHealth::Misdirected(to)) if to.symlink_metadata().is_err() => Some("dangling"),
+        Some(LinkHealth::Misdirected(_)) => Some("misdirected"),
+        _ => None,
+    }
+}
+
+/// Per-target half of the hide pipeline: move into storage, snapshot, link, OS-hide.
+/// Returns notes to print once the target's output is reported.
+pub fn stage_hide(
+    root: &Path,
+    target: &str,
+    opts: &HideOptions,
+    project: &ProjectConfig,
+) -> Result<Staged> {
+    let mut notes = Vec::new();
+
+    let key = opts.storage_key(target);
+    if is_already_hidden(root, target, &key)
+        && let Some(reason) = stale_link(root, target, opts)
+    {
+        let link = opts.link(target);
+        linker::remove_ghost_link(root, link)?;
+        linker::create_ghost_link_as(root, link, &key, opts.link_style)?;
+        hider::hide_path(root, link)?;
+        notes.push(format!("~ recreated the {reason} ghost link"));
+        return Ok(Staged {
+            notes,
+            replaced: None,
+            relinked: true,
+            mode: None,
+        });
+    }
+
+    // Checked before ingest: on a case-insensitive filesystem the move would
+    // otherwise quietly pick up the differently-cased entry.
+    linker::ensure_no_case_variant(root, target)?;
+    // Also checked by ingest, but fingerprinting a FIFO would block first.
+    mover::ensure_hideable(&root.join(target))?;
+    let before = if opts.verify {
+        Some(verify::Fingerprint::of(&root.join(target))?)
+    } else {
+        None
+    };
+    let mode = mover::mode_of(&root.join(target));
+    let replaced = if opts.update {
+        clear_stale_storage(root, target, &key)?
+    } else {
+        None
+    };
+    if let Err(e) = mover::ingest(root, target, &key) {
+        restore_replaced(root, &key, replaced)?;
+        return Err(e);
+    }
+    if let Some(timestamp) = replaced {
+        notes.push(format!(
+            "~ replaced the previous storage copy (kept as snapshot {timestamp})"
+        ));
+    }
+    if key != target {
+        notes.push(format!("+ stored as {key}"));
+    }
+
+    // Once the content is in storage, any failure puts it back before returning.
+    let link = opts.link(target);
+    let linked = (|| -> Result<()> {
+        #[cfg(unix)]
+        if let Some(mode) = opts.chmod {
+            let stored = mover::storage_dir(root).join(&key);
+            mover::apply_mode(&stored, mode)?;
+            notes.push(format!("+ mode {mode:04o}"));
+        }
+        // A replaced copy was just snapshotted; a second one this second would collide.
+        if opts.snapshot && replaced.is_none() {
+            notes.extend(take_snapshot(root, &key, project)?);
+        }
+        linker::create_ghost_link_as(root, link, &key, opts.link_style)?;
+        hider::hide_path(root, link)?;
+        if link != target {
+            notes.push(format!("+ linked as {link}"));
+        }
+        if let Some(before) = before {
+            verify::check(root, link, &key, before).context("verification failed")?;
+            notes.push("verified link and content".to_string());
+        }
+        Ok(())
+    })();
+    if let Err(e) = linked {
+        return Err(roll_back_target(root, target, &key, link, &[], replaced, e));
+    }
+
+    Ok(Staged {
+        notes,
+        replaced,
+        relinked: false,
+        mode,
+    })
+}
+
+/// A target `stage_hide` moved into storage and linked.
+pub struct Staged {
+    /// Lines to print once the target's result is reported.
+    notes: Vec<String>,
+    /// Snapshot of the storage copy `--update` replaced, if any.
+    replaced: Option<u64>,
+    /// The target was already hidden and `--force` only recreated its ghost link.
+    relinked: bool,
+    /// Permission bits the target had at the root before it was moved.
+    mode: Option<u32>,
+}
+
+/// An edit to a file shared by the whole batch, made for one target in hide's
+/// serial phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharedEdit {
+    IdeExclude,
+    Gitignore,
+    Env,
+}
+
+/// Serial half of the hide pipeline, after `stage_hide`: add the target's IDE
+/// excludes, ignore rule and env export, then record it in `meta`. A failed edit
+/// puts the target back at the root, except for a `--force` relink, which leaves
+/// the target hidden. Returns the notes to print and the shared edits made.
+pub fn finish_hide(
+    root: &Path,
+    meta: &mut Meta,
+    target: &str,
+    staged: Staged,
+    opts: &HideOptions,
+) -> Result<(Vec<String>, Vec<SharedEdit>)> {
+    let Staged {
+        mut notes,
+        replaced,
+        relinked,
+        mode,
+    } = staged;
+    let key = opts.storage_key(target);
+    let link = opts.link(target);
+    let mut edits = Vec::new();
+    if let Err(e) = apply_shared_edits(root, &key, link, opts, &mut edits, &mut notes) {
+        // A relinked target stays hidden; only its new link is in place.
+        if relinked {
+            return Err(e);
+        }
+        return Err(roll_back_target(
+            root, target, &key, link, &edits, replaced, e,
+        ));
+    }
+
+    let entry = meta.entry_mut(&key);
+    entry.link_style = Some(opts.link_style);
+    entry.link_kind = linker::link_kind(&root.join(link));
+    if !relinked {
+        entry.hidden_at = Some(time::unix_now());
+        entry.chmod = opts.chmod;
+        entry.mode = mode;
+        entry.link_name = (link != key).then(|| link.to_string());
+        entry.label = opts.label.clone();
+        entry.origin = (target != key).then(|| target.to_string());
+    }
+    Ok((notes, edits))
+}
+
+/// Add `link`'s IDE excludes and `.gitignore` rule (and its `.cloak/env` export with
+/// `--env-detect`), recording in `edits` each one that changed something.
+pub fn apply_shared_edits(
+    root: &Path,
+    key: &str,
+    link: &str,
+    opts: &HideOptions,
+    edits: &mut Vec<SharedEdit>,
+    notes: &mut Vec<String>,
+) -> Result<()> {
+    let excluded = config::ide::exclude_status(root, link)?
+        .iter()
+        .filter(|(_, on)| *on)
+        .count();
+    if excluded < config::ide::present_dirs(root).len() {
+        config::ide::add_ide_exclude(root, link, &opts.ide)?;
+        edits.push(SharedEdit::IdeExclude);
+    }
+    if !opts.no_gitignore && !git::has_ignore_entry(root, link)? {
+        let file = if opts.local_ignore {
+            git::RuleFile::LocalExclude
+        } else {
+            git::RuleFile::Gitignore
+        };
+        git::add_ignore_entry(root, link, file)?;
+        edits.push(SharedEdit::Gitignore);
+    }
+    if opts.env_detect
+        && let Some((var, _)) = config::tool_env::record(root, key)?
+    {
+        notes.push(format!("+ {var} in .cloak/env"));
+        edits.push(SharedEdit::Env);
+    }
+    Ok(())
+}
+
+/// Undo what a failed hide of `target` did, most recent step first: the shared
+/// `edits`, then the ghost link and the move into storage, then any `--update`
+/// replacement. Returns `err`, noting whether the rollback worked.
+pub fn roll_back_target(
+    root: &Path,
+    target: &str,
+    key: &str,
+    link: &str,
+    edits: &[SharedEdit],
+    replaced: Option<u64>,
+    err: anyhow::Error,
+) -> anyhow::Error {
+    let undone = edits
+        .iter()
+        .rev()
+        .try_for_each(|edit| match edit {
+            SharedEdit::IdeExclude => config::ide::remove_ide_exclude(root, link),
+            SharedEdit::Gitignore => git::remove_ignore_entry(root, link),
+            SharedEdit::Env => config::tool_env::forget(root, key),
+        })
+        .and_then(|()| undo_stage_hide(root, link, target, key))
+        .and_then(|()| restore_replaced(root, key, replaced));
+    match undone {
+        Ok(()) => err.context(format!("hide of {target} rolled back")),
+        Err(undo) => err.context(format!(
+            "hide of {target} could not be rolled back ({undo:#}); \
+             its content may be left in .cloak/storage/{key}"
+        )),
+    }
+}
+
+/// For `hide --update`: when `target` is real content at the root while an older
+/// copy still sits in storage (a tool replaced the ghost link), snapshot that copy
+/// and remove it so the root content can take its place. Returns the snapshot's
+/// timestamp, or `None` when there was nothing to replace.
+fn clear_stale_storage(root: &Path, target: &str, key: &str) -> Result<Option<u64>> {
+    let stored = mover::storage_dir(root).join(key);
+    let at_root = root
+        .join(target)
+        .symlink_metadata()
+        .is_ok_and(|m| !m.file_type().is_symlink());
+    if !at_root || stored.symlink_metadata().is_err() {
+        return Ok(None);
+    }
+    let snap = snapshot::create(root, key)?;
+    mover::remove_path(&stored)?;
+    Ok(Some(snap.timestamp))
+}
+
+/// Put the storage copy `clear_stale_storage` replaced back from its snapshot.
+fn restore_replaced(root: &Path, key: &str, replaced: Option<u64>) -> Result<()> {
+    match replaced {
+        Some(timestamp) => snapshot::rollback(root, key, timestamp)
+            .context("failed to restore the previous storage copy from its snapshot"),
+        None => Ok(()),
+    }
+}
+
+/// Put a freshly staged target back at the root: drop its ghost link (if one was
+/// made) and move the content out of storage entry `key`. A symlink at `link` that
+/// does not lead to that entry is someone else's and is left alone.
+pub fn undo_stage_hide(root: &Path, link: &str, target: &str, key: &str) -> Result<()> {
+    if matches!(
+        linker::link_health_as(root, link, key),
+        Some(LinkHealth::Linked | LinkHealth::Broken)
+    ) {
+        linker::remove_ghost_link(root, link)?;
+    }
+    mover::egest(root, target, key, false)
+}
+
+/// Snapshot a freshly stored target and apply the configured retention.
+fn take_snapshot(root: &Path, target: &str, project: &ProjectConfig) -> Result<Vec<String>> {
+    let snap = snapshot::create(root, target)?;
+    let mut notes = vec![format!("+ snapshot {}", snap.timestamp)];
+
+    if let Some(keep) = project.snapshot_retention {
+        for removed in snapshot::prune(root, target, keep)? {
+            notes.push(format!("- pruned old snapshot {}", removed.display()));
+        }
+    }
+    Ok(notes)
+}
+
+/// Reverse the hide pipeline for one target. Returns notes to print with its result.
+pub fn unhide_target(root: &Path, target: &str, opts: &UnhideOptions) -> Result<Vec<String>> {
+    // A flattened target may be named by its root path or by its storage key.
+    let mut meta = Meta::load(root)?;
+    let key = meta.storage_key(target).to_string();
+    let origin = meta.origin(&key).to_string();
+    let target = origin.as_str();
+
+    // Check before touching anything so a refused restore leaves no partial state.
+    let missing_parent = mover::missing_parent(root, target);
+    if let Some(parent) = &missing_parent
+        && !opts.parents
+    {
+        bail!(
+            "parent directory does not exist at root: {} (pass --parents to create it)",
+            parent.display()
+        );
+    }
+
+    let link = meta.link_name(&key).to_string();
+    let mut notes = Vec::new();
+    if opts.diff_storage {
+        notes.extend(storage_change_notes(root, &key));
+    }
+    let live = root.join(target);
+    let occupied = live
+        .symlink_metadata()
+        .is_ok_and(|m| !m.file_type().is_symlink());
+    if occupied {
+        if !opts.force {
+            bail!(CloakError::Conflict(format!(
+                "path is not a symlink (refusing to overwrite): {} \
+                 (pass --force to back it up to {}.cloak-bak and restore over it)",
+                live.display(),
+                target
+            )));
+        }
+        // Never delete what is there: it is moved aside before anything else changes.
+        let backup = mover::backup_path(&live);
+        fs::rename(&live, &backup).with_context(|| {
+            format!(
+                "failed to back up {} -> {}",
+                live.display(),
+                backup.display()
+            )
+        })?;
+        notes.push(format!(
+            "backed up existing {target} to {}",
+            backup.strip_prefix(root).unwrap_or(&backup).display()
+        ));
+    }
+
+    // Only once the restore is going ahead: the IDE edits below may write through
+    // the link into storage (a hidden `.cursor` holds its own settings).
+    #[cfg(unix)]
+    if meta.get(&key).is_some_and(|e| e.immutable) {
+        let stored = mover::storage_dir(root).join(&key);
+        hider::set_immutable(&stored, false)
+            .context("cannot clear the immutable flag set by --immutable")?;
+        notes.push("cleared immutable flag".to_string());
+    }
+    config::ide::remove_ide_exclude(root, &link)?;
+    if !opts.no_gitignore {
+        git::remove_ignore_entry(root, &link)?;
+    }
+    // Without its parent directory, with a real entry just moved aside, or after
+    // the link was deleted by hand, there is no ghost link left to remove.
+    let link_gone = missing_parent.is_some()
+        || (occupied && link == target)
+        || root.join(&link).symlink_metadata().is_err();
+    if !link_gone {
+        linker::remove_ghost_link(root, &link)?;
+    }
+    mover::egest(root, target, &key, opts.parents)?;
+    // Clear the flag on the restored entry itself: the ghost link that carried
+    // it is gone, and the original may have been hidden before cloak took it.
+    hider::unhide_path(root, target)?;
+    config::tool_env::forget(root, &key)?;
+
+    if let Some(entry) = meta.remove(&key) {
+        meta.save(root)?;
+        // Only the target's own mode was recorded, so a directory's contents keep
+        // what `--chmod` gave them; records from before modes were kept have none.
+        match (entry.chmod, entry.mode) {
+            #[cfg(unix)]
+            (Some(_), Some(mode)) => {
+                let restored = root.join(target);
+                mover::restore_mode(&restored, mode)?;
+                if restored.is_dir() {
+                    notes.push(format!(
+                        "restored mode {mode:04o} (its contents keep the --chmod mode)"
+                    ));
+                } else {
+                    notes.push(format!("restored mode {mode:04o}"));
+                }
+            }
+            (Some(chmod), _) => notes.push(format!("kept mode {chmod:04o} set by --chmod")),
+            _ => {}
+        }
+    }
+    Ok(notes)
+}
+
+/// Notes for `unhide --diff-storage`: whether the content about to be restored is
+/// what git last saw in storage.
+fn storage_change_notes(root: &Path, target: &str) -> Vec<String> {
+    use git::StorageChange;
+
+    match git::storage_changes(root, target) {
+        None => vec!["storage is not in a git repository; nothing to compare".to_string()],
+        Some(StorageChange::Uncommitted) => {
+            vec!["storage content was never committed".to_string()]
+        }
+        Some(StorageChange::Unchanged) => {
+            vec!["storage content matches the last commit".to_string()]
+        }
+        Some(StorageChange::Changed(lines)) => {
+            let mut notes = vec![format!(
+                "storage content differs from the last commit ({} path(s)):",
+                lines.len()
+            )];
+            notes.extend(lines.into_iter().map(|line| format!("  {line}")));
+            notes
+        }
+    }
+}
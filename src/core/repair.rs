@@ -0,0 +1,123 @@
+use crate::core::linker::{self, LinkHealth, LinkKind, LinkStyle};
+use crate::core::meta::Meta;
+use crate::core::{hider, storage};
+use anyhow::{Result, bail};
+use std::fs;
+use std::path::Path;
+
+/// What `repair` did with one hidden target's ghost link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// The link already resolved to its storage entry in the wanted form.
+    Untouched,
+    /// There was no link; one was made.
+    Created,
+    /// The link was replaced; the reason says what was wrong with it.
+    Recreated(&'static str),
+    /// The link could not be fixed safely.
+    Failed(String),
+}
+
+/// One hidden target and what happened to its link.
+#[derive(Debug)]
+pub struct Repaired {
+    pub target: String,
+    pub link: String,
+    pub action: Action,
+}
+
+/// Make sure every hidden target has a ghost link that resolves to its storage entry.
+///
+/// Missing links are created and dangling ones (e.g. absolute links left over from
+/// where the project used to live) recreated, in the style recorded when the target
+/// was hidden. With `normalize`, every link is also rewritten as a relative symlink
+/// unless it already is one with the canonical `.cloak/storage/<target>` text.
+/// Each link made is checked to resolve before it is reported as fixed.
+pub fn repair(root: &Path, normalize: bool) -> Result<Vec<Repaired>> {
+    let mut meta = Meta::load(root)?;
+    let mut repaired = Vec::new();
+
+    for target in storage::entry_names(root)? {
+        let link = meta.link_name(&target).to_string();
+        let style = if normalize {
+            LinkStyle::Relative
+        } else {
+            meta.get(&target)
+                .and_then(|e| e.link_style)
+                .unwrap_or_default()
+        };
+
+        let action = match plan(root, &link, &target, normalize) {
+            Ok(Action::Untouched) => Action::Untouched,
+            Ok(action) => match relink(root, &link, &target, style) {
+                Ok(()) => {
+                    let entry = meta.entry_mut(&target);
+                    entry.link_style = Some(style);
+                    entry.link_kind = linker::link_kind(&root.join(&link));
+                    action
+                }
+                Err(e) => Action::Failed(format!("{e:#}")),
+            },
+            Err(e) => Action::Failed(format!("{e:#}")),
+        };
+        repaired.push(Repaired {
+            target,
+            link,
+            action,
+        });
+    }
+
+    meta.save(root)?;
+    Ok(repaired)
+}
+
+/// Decide what `root/link` needs: left alone, created, or recreated. An error means
+/// it cannot be fixed without risking something that is not cloak's.
+fn plan(root: &Path, link: &str, target: &str, normalize: bool) -> Result<Action> {
+    let link_path = root.join(link);
+    let Some(health) = linker::link_health_as(root, link, target) else {
+        if link_path.symlink_metadata().is_ok() {
+            bail!("a real file or directory occupies the link path; move it aside first");
+        }
+        return Ok(Action::Created);
+    };
+
+    match health {
+        LinkHealth::Linked if !normalize => Ok(Action::Untouched),
+        LinkHealth::Linked => {
+            if linker::link_kind(&link_path) == Some(LinkKind::Junction) {
+                return Ok(Action::Recreated("junction"));
+            }
+            let text = fs::read_link(&link_path)?;
+            if text == linker::link_target_text(root, link, target, LinkStyle::Relative) {
+                Ok(Action::Untouched)
+            } else if text.is_absolute() {
+                Ok(Action::Recreated("absolute link"))
+            } else {
+                Ok(Action::Recreated("non-canonical link text"))
+            }
+        }
+        LinkHealth::Broken => Ok(Action::Recreated("broken link")),
+        // Dangling: nothing of the user's is lost by pointing it back at storage.
+        LinkHealth::Misdirected(to) if to.symlink_metadata().is_err() => {
+            Ok(Action::Recreated("dangling link"))
+        }
+        LinkHealth::Misdirected(to) => bail!(
+            "link resolves to {}, not its storage entry; fix it by hand",
+            to.display()
+        ),
+    }
+}
+
+fn relink(root: &Path, link: &str, target: &str, style: LinkStyle) -> Result<()> {
+    if root.join(link).symlink_metadata().is_ok() {
+        linker::remove_ghost_link(root, link)?;
+    }
+    linker::create_ghost_link_as(root, link, target, style)?;
+    hider::hide_path(root, link)?;
+
+    match linker::link_health_as(root, link, target) {
+        Some(LinkHealth::Linked) => Ok(()),
+        _ => bail!("the recreated link does not resolve to its storage entry"),
+    }
+}
@@ -0,0 +1,185 @@
+use crate::core::{mover, storage};
+use crate::utils::time;
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory inside storage that holds point-in-time copies of hidden entries.
+pub const SNAPSHOT_DIR: &str = ".snapshots";
+
+/// A single snapshot of a stored target, named `<target>.<timestamp>`; a nested
+/// target is named by its flat key (`config__ai.<timestamp>`).
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub timestamp: u64,
+    pub path: PathBuf,
+}
+
+/// Path to `.cloak/storage/.snapshots/`.
+pub fn snapshot_root(root: &Path) -> PathBuf {
+    mover::storage_dir(root).join(SNAPSHOT_DIR)
+}
+
+/// Copy the current storage content of `target` into `.cloak/storage/.snapshots/`.
+pub fn create(root: &Path, target: &str) -> Result<Snapshot> {
+    let src = mover::storage_dir(root).join(target);
+    if !src.exists() {
+        bail!("target not found in storage: {}", src.display());
+    }
+
+    let timestamp = time::unix_now();
+    let dir = snapshot_root(root);
+    let dest = dir.join(format!("{}.{timestamp}", storage::flat_key(target)));
+
+    if dest.exists() {
+        bail!(
+            "snapshot already exists: {} (wait a second and retry)",
+            dest.display()
+        );
+    }
+
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("failed to create snapshot directory: {}", dir.display()))?;
+    mover::copy_path(&src, &dest)?;
+
+    Ok(Snapshot {
+        timestamp,
+        path: dest,
+    })
+}
+
+/// List snapshots for `target`, oldest first.
+pub fn list(root: &Path, target: &str) -> Result<Vec<Snapshot>> {
+    let dir = snapshot_root(root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(&dir)
+        .with_context(|| format!("failed to read snapshot directory: {}", dir.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some((stem, ts)) = name.to_str().and_then(|n| n.rsplit_once('.')) else {
+            continue;
+        };
+        if stem != storage::flat_key(target) {
+            continue;
+        }
+        if let Ok(timestamp) = ts.parse::<u64>() {
+            snapshots.push(Snapshot {
+                timestamp,
+                path: entry.path(),
+            });
+        }
+    }
+
+    snapshots.sort_by_key(|s| s.timestamp);
+    Ok(snapshots)
+}
+
+/// List every target that has at least one snapshot, sorted by name.
+pub fn targets(root: &Path) -> Result<Vec<String>> {
+    let dir = snapshot_root(root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(&dir)
+        .with_context(|| format!("failed to read snapshot directory: {}", dir.display()))?
+    {
+        let name = entry?.file_name();
+        if let Some((stem, ts)) = name.to_str().and_then(|n| n.rsplit_once('.'))
+            && ts.parse::<u64>().is_ok()
+        {
+            names.push(stem.to_string());
+        }
+    }
+
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Replace the live storage content of `target` with the snapshot taken at `timestamp`.
+///
+/// The snapshot itself is kept so a rollback can be repeated or undone.
+pub fn rollback(root: &Path, target: &str, timestamp: u64) -> Result<()> {
+    let Some(snapshot) = list(root, target)?
+        .into_iter()
+        .find(|s| s.timestamp == timestamp)
+    else {
+        bail!("no snapshot of {target} at {timestamp} (see `cloak snapshots {target}`)");
+    };
+
+    let live = mover::storage_dir(root).join(target);
+    if live.symlink_metadata().is_ok() {
+        mover::remove_path(&live)?;
+    }
+    mover::copy_path(&snapshot.path, &live)?;
+
+    Ok(())
+}
+
+/// Delete the oldest snapshots of `target` so that at most `keep` remain.
+/// Returns the paths that were removed.
+pub fn prune(root: &Path, target: &str, keep: usize) -> Result<Vec<PathBuf>> {
+    let snapshots = list(root, target)?;
+    let excess = snapshots.len().saturating_sub(keep);
+
+    let mut removed = Vec::new();
+    for snapshot in snapshots.into_iter().take(excess) {
+        mover::remove_path(&snapshot.path)?;
+        removed.push(snapshot.path);
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn make_temp_dir(prefix: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock before epoch")
+            .as_nanos();
+        let pid = std::process::id();
+        let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!("cloak-{prefix}-{pid}-{nanos}-{seq}"));
+        fs::create_dir_all(&dir).expect("failed to create temp test dir");
+        dir
+    }
+
+    #[test]
+    fn rollback_restores_snapshot_content_and_prune_keeps_newest() {
+        let root = make_temp_dir("snapshot-rollback");
+        let live = mover::storage_dir(&root).join(".cursor");
+        fs::create_dir_all(&live).expect("create storage entry failed");
+        fs::write(live.join("settings.json"), "original").expect("write failed");
+
+        let snap = create(&root, ".cursor").expect("create snapshot failed");
+        fs::write(live.join("settings.json"), "mutated").expect("write failed");
+
+        rollback(&root, ".cursor", snap.timestamp).expect("rollback failed");
+        assert_eq!(
+            fs::read_to_string(live.join("settings.json")).expect("read failed"),
+            "original"
+        );
+
+        // Fabricate an older snapshot and check retention drops it first.
+        let older = snapshot_root(&root).join(format!(".cursor.{}", snap.timestamp - 10));
+        fs::create_dir_all(&older).expect("create old snapshot failed");
+        let removed = prune(&root, ".cursor", 1).expect("prune failed");
+        assert_eq!(removed, vec![older]);
+        assert_eq!(list(&root, ".cursor").expect("list failed").len(), 1);
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+}
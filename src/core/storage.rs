@@ -0,0 +1,274 @@
+use crate::core::{meta::Meta, mover, snapshot};
+use crate::error::CloakError;
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::Path;
+
+/// Validate a root-relative target name; nested names like `config/ai` are allowed.
+pub fn validate_target(target: &str) -> Result<()> {
+    if target.is_empty() {
+        bail!(CloakError::Invalid("target name cannot be empty".into()));
+    }
+
+    if target.starts_with('/') || target.starts_with('\\') {
+        bail!(CloakError::Invalid(format!(
+            "absolute paths are not allowed: {target}"
+        )));
+    }
+
+    // Reject Windows-style absolute paths like C:\foo
+    if target.len() >= 2 && target.as_bytes()[1] == b':' {
+        bail!(CloakError::Invalid(format!(
+            "absolute paths are not allowed: {target}"
+        )));
+    }
+
+    if target == ".."
+        || target.contains("/../")
+        || target.starts_with("../")
+        || target.ends_with("/..")
+    {
+        bail!(CloakError::Invalid(format!(
+            "path traversal is not allowed: {target}"
+        )));
+    }
+
+    if target == ".cloak" || target.starts_with(".cloak/") || target.starts_with(".cloak\\") {
+        bail!(CloakError::Invalid(
+            "cannot hide the .cloak directory itself".into()
+        ));
+    }
+
+    if target.split(['/', '\\']).next() == Some(snapshot::SNAPSHOT_DIR) {
+        bail!(CloakError::Invalid(format!(
+            "{target} is reserved for cloak snapshots"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Link state of a hidden entry as seen from the project root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    Linked,
+    Missing,
+}
+
+impl LinkState {
+    pub fn label(self) -> &'static str {
+        match self {
+            LinkState::Linked => "linked",
+            LinkState::Missing => "link missing",
+        }
+    }
+}
+
+/// A node in the storage tree: either a grouping directory or a hidden entry.
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    /// `Some` for hidden entries, `None` for directories that only group entries.
+    pub state: Option<LinkState>,
+    pub children: Vec<Node>,
+}
+
+/// Walk `.cloak/storage` recursively and classify each path.
+///
+/// A storage key meta.json records is an entry, linked when something is at its
+/// recorded link name; a directory holding recorded keys groups them (e.g. the
+/// category directories of `storage regroup`). Otherwise a storage path is a
+/// grouping directory when the same path at the root is a real directory and
+/// something below it is linked, and an entry, annotated with whether its ghost
+/// link exists, when it is not. Cloak's own `.snapshots` is skipped.
+pub fn walk(root: &Path) -> Result<Vec<Node>> {
+    walk_dir(root, &Meta::load(root)?, Path::new(""))
+}
+
+/// Joins the components of a nested target in its `hide --flatten` storage name.
+pub const FLATTEN_SEPARATOR: &str = "__";
+
+/// The single-component storage name `hide --flatten` gives `target`
+/// (`a/b/.env` becomes `a__b__.env`).
+pub fn flat_key(target: &str) -> String {
+    target
+        .split(['/', '\\'])
+        .collect::<Vec<_>>()
+        .join(FLATTEN_SEPARATOR)
+}
+
+/// Root-relative names (`/`-separated) of every hidden entry in storage, sorted.
+pub fn entry_names(root: &Path) -> Result<Vec<String>> {
+    fn collect(nodes: &[Node], prefix: &str, out: &mut Vec<String>) {
+        for node in nodes {
+            let name = format!("{prefix}{}", node.name);
+            if node.state.is_some() {
+                out.push(name);
+            } else {
+                collect(&node.children, &format!("{name}/"), out);
+            }
+        }
+    }
+
+    let mut names = Vec::new();
+    collect(&walk(root)?, "", &mut names);
+    Ok(names)
+}
+
+/// Storage entries with nothing at all where their ghost link belongs, e.g. because
+/// the link was deleted by hand. A real file in its place is a conflict, not this.
+pub fn unlinked_entries(root: &Path) -> Result<Vec<String>> {
+    let meta = Meta::load(root)?;
+    Ok(entry_names(root)?
+        .into_iter()
+        .filter(|name| root.join(meta.link_name(name)).symlink_metadata().is_err())
+        .collect())
+}
+
+/// Why `name`'s storage entry cannot be read, if it cannot: a directory that cannot
+/// be listed or a file that cannot be opened. A missing entry is not reported here.
+pub fn read_error(root: &Path, name: &str) -> Option<std::io::Error> {
+    let stored = mover::storage_dir(root).join(name);
+    let meta = fs::symlink_metadata(&stored).ok()?;
+    let result = if meta.is_dir() {
+        fs::read_dir(&stored).map(drop)
+    } else if meta.is_file() {
+        fs::File::open(&stored).map(drop)
+    } else {
+        Ok(())
+    };
+    result.err()
+}
+
+fn walk_dir(root: &Path, meta: &Meta, rel: &Path) -> Result<Vec<Node>> {
+    let dir = mover::storage_dir(root).join(rel);
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .with_context(|| format!("failed to read storage directory: {}", dir.display()))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|name| !(rel.as_os_str().is_empty() && name == snapshot::SNAPSHOT_DIR))
+        .collect();
+    names.sort();
+
+    let mut nodes = Vec::new();
+    for name in names {
+        let child_rel = rel.join(&name);
+        nodes.push(classify(root, meta, &child_rel, name)?);
+    }
+    Ok(nodes)
+}
+
+fn classify(root: &Path, meta: &Meta, rel: &Path, name: String) -> Result<Node> {
+    let root_path = root.join(rel);
+    let stored = mover::storage_dir(root).join(rel);
+
+    let key = rel.to_string_lossy().replace('\\', "/");
+    if meta.get(&key).is_some() {
+        let linked = root
+            .join(meta.link_name(&key))
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink());
+        return Ok(leaf(
+            name,
+            if linked {
+                LinkState::Linked
+            } else {
+                LinkState::Missing
+            },
+        ));
+    }
+    let prefix = format!("{key}/");
+    if stored.is_dir() && meta.targets().any(|target| target.starts_with(&prefix)) {
+        return Ok(Node {
+            name,
+            state: None,
+            children: walk_dir(root, meta, rel)?,
+        });
+    }
+
+    let is_link = root_path
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink());
+    if is_link {
+        return Ok(leaf(name, LinkState::Linked));
+    }
+
+    let root_is_real_dir = root_path.symlink_metadata().is_ok_and(|m| m.is_dir());
+    if root_is_real_dir && stored.is_dir() {
+        let children = walk_dir(root, meta, rel)?;
+        if children.iter().any(has_linked) {
+            return Ok(Node {
+                name,
+                state: None,
+                children,
+            });
+        }
+    }
+
+    Ok(leaf(name, LinkState::Missing))
+}
+
+fn leaf(name: String, state: LinkState) -> Node {
+    Node {
+        name,
+        state: Some(state),
+        children: Vec::new(),
+    }
+}
+
+fn has_linked(node: &Node) -> bool {
+    node.state == Some(LinkState::Linked) || node.children.iter().any(has_linked)
+}
+
+/// A storage entry that looks like an interrupted hide, unhide, or move left it behind.
+#[derive(Debug)]
+pub struct Suspect {
+    /// Root-relative name of the entry.
+    pub target: String,
+    pub reason: &'static str,
+}
+
+/// Flag unlinked storage entries that look incomplete: empty, duplicated by a real
+/// path at the root (a copy whose source was never deleted), or never recorded in
+/// meta.json (a hide stopped before it finished). Linked entries are trusted.
+pub fn verify(root: &Path) -> Result<Vec<Suspect>> {
+    let meta = Meta::load(root)?;
+    let mut suspects = Vec::new();
+
+    for target in entry_names(root)? {
+        let stored = mover::storage_dir(root).join(&target);
+        let live = root.join(meta.link_name(&target));
+        let Ok(live_meta) = live.symlink_metadata() else {
+            let reason = if is_empty(&stored)? {
+                "empty and unlinked; likely left by an interrupted move"
+            } else if meta.get(&target).is_none() {
+                "unlinked and not recorded in meta.json; the hide that stored it may not have finished"
+            } else {
+                continue;
+            };
+            suspects.push(Suspect { target, reason });
+            continue;
+        };
+        if !live_meta.file_type().is_symlink() {
+            suspects.push(Suspect {
+                target,
+                reason: "also present at the root as a real path; an interrupted move may have \
+                         left two copies",
+            });
+        }
+    }
+    Ok(suspects)
+}
+
+fn is_empty(path: &Path) -> Result<bool> {
+    let meta = path
+        .symlink_metadata()
+        .with_context(|| format!("failed to stat {}", path.display()))?;
+    if meta.is_dir() {
+        let mut entries =
+            fs::read_dir(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(entries.next().is_none())
+    } else {
+        Ok(meta.len() == 0)
+    }
+}
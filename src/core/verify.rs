@@ -0,0 +1,74 @@
+use crate::core::linker::{self, LinkHealth};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::Read;
+use std::path::Path;
+
+/// What a target looked like before it was moved into storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fingerprint {
+    /// A file: its length and a hash of its content.
+    File { len: u64, hash: u64 },
+    /// A directory: how many entries it holds, recursively.
+    Dir { entries: usize },
+}
+
+impl Fingerprint {
+    /// Fingerprint the file or directory at `path`, following a symlink at `path` itself.
+    pub fn of(path: &Path) -> Result<Self> {
+        let meta =
+            fs::metadata(path).with_context(|| format!("failed to stat {}", path.display()))?;
+        if meta.is_dir() {
+            // `min_depth(1)` leaves out `path` itself; nested links are counted, not followed.
+            let entries = walkdir::WalkDir::new(path).min_depth(1).into_iter().count();
+            return Ok(Fingerprint::Dir { entries });
+        }
+
+        let mut file =
+            fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let mut hasher = DefaultHasher::new();
+        let mut buf = [0u8; 64 * 1024];
+        let mut len = 0u64;
+        loop {
+            let n = file
+                .read(&mut buf)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            if n == 0 {
+                break;
+            }
+            hasher.write(&buf[..n]);
+            len += n as u64;
+        }
+        Ok(Fingerprint::File {
+            len,
+            hash: hasher.finish(),
+        })
+    }
+}
+
+/// Check that `root/link` is a ghost link resolving to `target`'s storage entry and
+/// that what it shows matches `before`: the same content for a file, the same number
+/// of entries for a directory.
+pub fn check(root: &Path, link: &str, target: &str, before: Fingerprint) -> Result<()> {
+    match linker::link_health_as(root, link, target) {
+        Some(LinkHealth::Linked) => {}
+        Some(LinkHealth::Broken) => bail!("ghost link resolves to a missing storage entry"),
+        Some(LinkHealth::Misdirected(to)) => {
+            bail!("ghost link resolves outside storage ({})", to.display())
+        }
+        None => bail!("no ghost link at {}", root.join(link).display()),
+    }
+
+    let after = Fingerprint::of(&root.join(link))?;
+    match (before, after) {
+        (a, b) if a == b => Ok(()),
+        (Fingerprint::Dir { entries: a }, Fingerprint::Dir { entries: b }) => {
+            bail!("storage holds {b} entries, expected {a}")
+        }
+        (Fingerprint::File { .. }, Fingerprint::File { .. }) => {
+            bail!("content read through the link differs from the original")
+        }
+        _ => bail!("link resolves to a different kind of entry than was hidden"),
+    }
+}
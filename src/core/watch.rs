@@ -0,0 +1,282 @@
+use crate::core::linker;
+use crate::core::meta::Meta;
+use crate::core::storage::{self, LinkState, Node};
+use crate::core::{hider, mover};
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A ghost link that was found replaced by a real file/dir and restored.
+#[derive(Debug)]
+pub struct Relinked {
+    pub target: String,
+    /// Where the ghost link sits at the root; differs from `target` after
+    /// `--link-name` or `--flatten`.
+    pub link: String,
+    /// Number of files from the replacement that were merged into storage.
+    pub merged: usize,
+}
+
+/// Find hidden targets (by storage key) whose ghost link has been replaced by a
+/// real file or directory.
+///
+/// Recorded targets are checked at the link name meta.json gives them. Storage
+/// entries meta.json does not know about show up in the storage walk as entries
+/// without a link while something real (not a symlink) sits at the same path.
+pub fn clobbered(root: &Path) -> Result<Vec<String>> {
+    let meta = Meta::load(root)?;
+    let storage = mover::storage_dir(root);
+    let mut found: Vec<String> = meta
+        .targets()
+        .filter(|key| storage.join(key).symlink_metadata().is_ok())
+        .filter(|key| is_real(&root.join(meta.link_name(key))))
+        .map(str::to_string)
+        .collect();
+    for node in storage::walk(root)? {
+        collect_clobbered(root, &meta, &node, Path::new(""), &mut found);
+    }
+    Ok(found)
+}
+
+fn collect_clobbered(
+    root: &Path,
+    meta: &Meta,
+    node: &Node,
+    parent: &Path,
+    found: &mut Vec<String>,
+) {
+    let rel = parent.join(&node.name);
+    let key = rel.to_string_lossy().replace('\\', "/");
+    if meta.get(&key).is_some() {
+        return;
+    }
+    if node.state == Some(LinkState::Missing) && is_real(&root.join(&rel)) {
+        found.push(key);
+    }
+    for child in &node.children {
+        collect_clobbered(root, meta, child, &rel, found);
+    }
+}
+
+/// Directories a watcher has to observe to see every ghost link get replaced:
+/// the root, `.cloak` (hides and unhides rewrite meta.json), and the parent of
+/// each link. Only these are watched, non-recursively, so large trees under the
+/// root (build output, `node_modules`) never reach the watcher. Parents that do
+/// not exist yet are left out.
+pub fn watched_dirs(root: &Path) -> Result<BTreeSet<PathBuf>> {
+    let meta = Meta::load(root)?;
+    let mut links: Vec<PathBuf> = meta
+        .targets()
+        .map(|key| PathBuf::from(meta.link_name(key)))
+        .collect();
+    for node in storage::walk(root)? {
+        collect_links(&node, Path::new(""), &mut links);
+    }
+
+    let mut dirs = BTreeSet::from([root.to_path_buf(), root.join(mover::CLOAK_DIR)]);
+    for link in links {
+        if let Some(parent) = root.join(link).parent() {
+            dirs.insert(parent.to_path_buf());
+        }
+    }
+    dirs.retain(|dir| dir.is_dir());
+    Ok(dirs)
+}
+
+fn collect_links(node: &Node, parent: &Path, links: &mut Vec<PathBuf>) {
+    let rel = parent.join(&node.name);
+    if node.state.is_some() {
+        links.push(rel.clone());
+    }
+    for child in &node.children {
+        collect_links(child, &rel, links);
+    }
+}
+
+fn is_real(path: &Path) -> bool {
+    path.symlink_metadata()
+        .is_ok_and(|m| !m.file_type().is_symlink())
+}
+
+/// Merge the real content now sitting at the ghost link of storage key `target`
+/// into storage and put the link back, using the link name and style recorded
+/// when the target was hidden.
+///
+/// Files written by the tool win over what storage had; anything only in storage is kept.
+pub fn relink(root: &Path, target: &str) -> Result<Relinked> {
+    let meta = Meta::load(root)?;
+    let link = meta.link_name(target);
+    let live = root.join(link);
+    let stored = mover::storage_dir(root).join(target);
+
+    let merged = merge_into(&live, &stored)?;
+    mover::remove_path(&live)?;
+
+    let style = meta
+        .get(target)
+        .and_then(|e| e.link_style)
+        .unwrap_or_default();
+    linker::create_ghost_link_as(root, link, target, style)?;
+    hider::hide_path(root, link)?;
+
+    Ok(Relinked {
+        target: target.to_string(),
+        link: link.to_string(),
+        merged,
+    })
+}
+
+/// Copy `src` over `dest`, recursing into directories present on both sides.
+/// Returns the number of files copied.
+fn merge_into(src: &Path, dest: &Path) -> Result<usize> {
+    let dest_is_dir = dest.symlink_metadata().is_ok_and(|m| m.is_dir());
+    if src.is_dir() && dest_is_dir {
+        let mut copied = 0;
+        for entry in fs::read_dir(src)
+            .with_context(|| format!("failed to read directory: {}", src.display()))?
+        {
+            let entry = entry?;
+            copied += merge_into(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+        return Ok(copied);
+    }
+
+    if dest.symlink_metadata().is_ok() {
+        mover::remove_path(dest)?;
+    }
+    mover::copy_path(src, dest)?;
+    Ok(count_files(src))
+}
+
+fn count_files(path: &Path) -> usize {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn make_temp_dir(prefix: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock before epoch")
+            .as_nanos();
+        let pid = std::process::id();
+        let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!("cloak-{prefix}-{pid}-{nanos}-{seq}"));
+        fs::create_dir_all(&dir).expect("failed to create temp test dir");
+        dir
+    }
+
+    #[test]
+    fn relink_merges_clobbering_dir_and_restores_link() {
+        let root = make_temp_dir("watch-relink");
+        let stored = mover::storage_dir(&root).join(".cursor");
+        fs::create_dir_all(&stored).expect("create storage entry failed");
+        fs::write(stored.join("settings.json"), "old").expect("write failed");
+        fs::write(stored.join("rules.md"), "keep me").expect("write failed");
+
+        // Simulate an IDE recreating its config dir as a real directory.
+        let live = root.join(".cursor");
+        fs::create_dir_all(&live).expect("create clobbering dir failed");
+        fs::write(live.join("settings.json"), "new").expect("write failed");
+
+        assert_eq!(clobbered(&root).expect("scan failed"), vec![".cursor"]);
+
+        let relinked = relink(&root, ".cursor").expect("relink failed");
+        assert_eq!(relinked.merged, 1);
+        assert!(
+            live.symlink_metadata()
+                .expect("link missing")
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(
+            fs::read_to_string(stored.join("settings.json")).expect("read failed"),
+            "new"
+        );
+        assert_eq!(
+            fs::read_to_string(stored.join("rules.md")).expect("read failed"),
+            "keep me"
+        );
+        assert!(clobbered(&root).expect("rescan failed").is_empty());
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn relink_restores_a_flattened_target_at_its_recorded_link() {
+        let root = make_temp_dir("watch-flat");
+        let stored = mover::storage_dir(&root).join("a__b__.env");
+        fs::create_dir_all(stored.parent().expect("no parent")).expect("create storage failed");
+        fs::write(&stored, "TOKEN=old").expect("write failed");
+        let mut meta = Meta::default();
+        let entry = meta.entry_mut("a__b__.env");
+        entry.link_name = Some("a/b/.env".to_string());
+        entry.origin = Some("a/b/.env".to_string());
+        meta.save(&root).expect("save meta failed");
+
+        let live = root.join("a/b/.env");
+        fs::create_dir_all(live.parent().expect("no parent")).expect("create dir failed");
+        fs::write(&live, "TOKEN=new").expect("write failed");
+
+        assert_eq!(clobbered(&root).expect("scan failed"), vec!["a__b__.env"]);
+
+        let relinked = relink(&root, "a__b__.env").expect("relink failed");
+        assert_eq!(relinked.link, "a/b/.env");
+        assert!(
+            live.symlink_metadata()
+                .expect("link missing")
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_to_string(&live).expect("read failed"), "TOKEN=new");
+        assert!(!root.join("a__b__.env").exists());
+        assert!(clobbered(&root).expect("rescan failed").is_empty());
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn watched_dirs_cover_the_root_and_each_link_parent() {
+        let root = make_temp_dir("watch-dirs");
+        let storage = mover::storage_dir(&root);
+        fs::create_dir_all(storage.join("a__b__.env").parent().expect("no parent"))
+            .expect("create storage failed");
+        fs::write(storage.join("a__b__.env"), "TOKEN=x").expect("write failed");
+        fs::create_dir_all(storage.join("tools/.cursor")).expect("create entry failed");
+        fs::create_dir_all(root.join("a/b")).expect("create dir failed");
+        fs::create_dir_all(root.join("tools")).expect("create dir failed");
+        fs::create_dir_all(root.join("node_modules/pkg")).expect("create dir failed");
+        let mut meta = Meta::default();
+        let entry = meta.entry_mut("a__b__.env");
+        entry.link_name = Some("a/b/.env".to_string());
+        entry.origin = Some("a/b/.env".to_string());
+        meta.entry_mut("tools/.cursor");
+        meta.entry_mut("gone/.idea");
+        meta.save(&root).expect("save meta failed");
+
+        let dirs = watched_dirs(&root).expect("collect failed");
+        assert_eq!(
+            dirs.into_iter().collect::<Vec<_>>(),
+            vec![
+                root.clone(),
+                root.join(".cloak"),
+                root.join("a/b"),
+                root.join("tools"),
+            ]
+        );
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+}
@@ -0,0 +1,52 @@
+use std::process::ExitCode;
+
+/// Failure classes that scripts can tell apart by exit code.
+///
+/// Errors that are not one of these exit with 1, or with 5 when an IO error
+/// (missing permission, full disk, ...) caused them.
+#[derive(Debug, thiserror::Error)]
+pub enum CloakError {
+    /// The command line named something cloak cannot act on (bad target, traversal, ...).
+    #[error("{0}")]
+    Invalid(String),
+    /// `.cloak/storage` is missing and the command was told not to create it.
+    #[error("{0}")]
+    NotInitialized(String),
+    /// Something already occupies the path cloak needs to write.
+    #[error("{0}")]
+    Conflict(String),
+    /// Several targets failed; `code` is the exit code they share, or 1 when they differ.
+    #[error("{message}")]
+    Batch { message: String, code: u8 },
+}
+
+impl CloakError {
+    fn code(&self) -> u8 {
+        match self {
+            CloakError::Invalid(_) => 2,
+            CloakError::NotInitialized(_) => 3,
+            CloakError::Conflict(_) => 4,
+            CloakError::Batch { code, .. } => *code,
+        }
+    }
+}
+
+/// The exit code for `err`: its `CloakError` class if one is in the chain, then 5
+/// for IO failures, otherwise 1.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    if let Some(code) = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<CloakError>())
+        .map(CloakError::code)
+    {
+        return code;
+    }
+    let is_io = err.chain().any(|cause| cause.is::<std::io::Error>());
+    if is_io { 5 } else { 1 }
+}
+
+/// Print `err` the way anyhow's `main` would and turn it into an exit code.
+pub fn report(err: &anyhow::Error) -> ExitCode {
+    eprintln!("Error: {err:?}");
+    ExitCode::from(exit_code(err))
+}
@@ -0,0 +1,27 @@
+//! The engine behind the `cloak` CLI, for editor plugins and scripts that want to
+//! hide and restore configs without shelling out to the binary.
+//!
+//! [`CloakProject`] covers the everyday operations. The modules underneath are the
+//! building blocks the CLI itself is made of: [`core::pipeline`] runs a hide or
+//! unhide for one target, [`core::mover`] moves content in and
+//! out of `.cloak/storage`, [`core::linker`] makes the ghost links,
+//! [`core::hider`] sets OS hidden flags, [`config::ide`] edits IDE excludes and
+//! [`utils::git`] the ignore rules.
+//!
+//! ```no_run
+//! use cloak::CloakProject;
+//!
+//! let project = CloakProject::open("/path/to/project")?;
+//! project.hide(".cursor")?;
+//! assert_eq!(project.hidden()?, [".cursor"]);
+//! project.unhide(".cursor")?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod config;
+pub mod core;
+pub mod error;
+mod project;
+pub mod utils;
+
+pub use project::CloakProject;
@@ -1,13 +1,21 @@
-mod config;
-mod core;
-mod utils;
+use cloak::{config, core, error, utils};
 
-use anyhow::{Result, bail};
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{CommandFactory, Parser, Subcommand};
 use colored::Colorize;
-use std::io::{self, Write};
+use core::linker::{LinkHealth, LinkStyle};
+use core::pipeline::{
+    SharedEdit, apply_shared_edits, ensure_outside_hidden, is_already_hidden, stage_hide,
+    stale_link, undo_stage_hide, unhide_target,
+};
+use core::storage::validate_target;
+use error::CloakError;
+use std::io::{self, IsTerminal, Write};
+use std::num::NonZeroUsize;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::ExitCode;
+use utils::output::say;
 
 #[derive(Parser)]
 #[command(
@@ -24,198 +32,3363 @@ struct Cli {
     /// Project root directory (defaults to current directory)
     #[arg(short, long, global = true)]
     root: Option<PathBuf>,
+
+    /// Print what hide, unhide, tidy, or gc would change without touching anything
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Print nothing but errors (on stderr) and output meant for other programs
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log every move, link, gitignore and IDE settings change to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Print without ANSI colors (also set by a non-empty NO_COLOR)
+    #[arg(long, global = true)]
+    no_color: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Initialize cloak in the current project
-    Init,
+    Init {
+        /// Keep hidden files outside the project (supports `~`, `$VAR`, and `${VAR}`)
+        #[arg(long, value_name = "PATH")]
+        storage: Option<String>,
+
+        /// Manage .gitignore even though the project is not inside a git repository
+        #[arg(long)]
+        git: bool,
+    },
+
+    /// Hide specified config files/directories into .cloak/storage
+    Hide {
+        /// Config paths to hide (e.g. .cursor .vscode .idea)
+        #[arg(required_unless_present_any = ["from_manifest", "stdin0", "known"])]
+        targets: Vec<String>,
+
+        /// Also hide every known tool config present at the root (what `tidy`
+        /// would offer), without prompting
+        #[arg(long)]
+        known: bool,
+
+        /// Also read targets from stdin, separated by NUL bytes
+        /// (e.g. `find . -maxdepth 1 -name '.*' -print0 | cloak hide --stdin0`)
+        #[arg(long, visible_alias = "stdin-null")]
+        stdin0: bool,
+
+        /// Also hide the targets listed under `cloak` in package.json or
+        /// `[tool.cloak]` in pyproject.toml
+        #[arg(long)]
+        from_manifest: bool,
+
+        /// Keep a point-in-time copy of each target in .cloak/storage/.snapshots
+        #[arg(long)]
+        snapshot: bool,
+
+        /// Maximum number of targets processed in parallel (1 = sequential)
+        #[arg(long, value_name = "N")]
+        concurrency: Option<NonZeroUsize>,
+
+        /// Write the ghost link as a relative or absolute path [default: relative]
+        /// (a Windows junction fallback is always absolute)
+        #[arg(long, value_enum, value_name = "STYLE")]
+        link_target_style: Option<LinkStyle>,
+
+        /// Back up unparseable IDE settings.json files and write fresh ones
+        #[arg(long)]
+        force_ide: bool,
+
+        /// For tools that read their config dir from an env var, write it to .cloak/env
+        #[arg(long)]
+        env_detect: bool,
+
+        /// Skip the init check (for scripts calling hide in a loop); errors if storage is missing
+        #[arg(long)]
+        assume_initialized: bool,
+
+        /// Leave .gitignore untouched (e.g. when a global ignore already covers these)
+        #[arg(long)]
+        no_gitignore: bool,
+
+        /// Write ignore rules to .git/info/exclude instead of the shared .gitignore
+        #[arg(long, conflicts_with = "no_gitignore")]
+        local_ignore: bool,
+
+        /// Manage .gitignore even though the project is not inside a git repository
+        #[arg(long, conflicts_with = "no_gitignore")]
+        git: bool,
+
+        /// Check each ghost link resolves to the moved content; undo the hide if not
+        #[arg(long)]
+        verify: bool,
+
+        /// Hide every target or none: on any failure, roll back the ones already hidden
+        #[arg(long)]
+        atomic: bool,
+
+        /// Create the ghost link under this root name instead of the target's own
+        /// (single target only); storage still keeps the original name
+        #[arg(long, value_name = "NAME")]
+        link_name: Option<String>,
+
+        /// Store nested targets (e.g. a/b/.env) under one flat name (a__b__.env)
+        /// instead of the same nested path; unhide restores it to the nested path
+        #[arg(long, conflicts_with = "link_name")]
+        flatten: bool,
+
+        /// Absorb a target that reappeared at the root as real content (e.g. a tool
+        /// replaced its ghost link): snapshot the old storage copy, then replace it
+        #[arg(long, conflicts_with = "atomic")]
+        update: bool,
+
+        /// Recreate the ghost link of an already-hidden target when it is broken or
+        /// points elsewhere; never replaces a real file or directory
+        #[arg(long, conflicts_with = "atomic")]
+        force: bool,
+
+        /// Set the stored entry's mode (octal, e.g. 600); directories also get matching
+        /// execute bits. Unix only
+        #[arg(long, value_name = "MODE", value_parser = parse_mode)]
+        chmod: Option<u32>,
+
+        /// Set the OS immutable flag on the stored entry (chattr +i / chflags uchg) so
+        /// writes through the ghost link fail; unhide clears it. Unix only
+        #[arg(long)]
+        immutable: bool,
+
+        /// Note why the targets are hidden; shown by `status` and `info`
+        #[arg(long, value_name = "TEXT")]
+        label: Option<String>,
+
+        /// Shell command to run once, from the project root, after every target succeeded
+        #[arg(long, value_name = "COMMAND")]
+        after: Option<String>,
+
+        /// Run the --after command even when some targets failed
+        #[arg(long, requires = "after")]
+        after_always: bool,
+
+        /// Print only a final count of succeeded/skipped/failed targets
+        #[arg(long)]
+        summary: bool,
+
+        /// Write a JSON record of each target's outcome to this file
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+    },
+
+    /// Restore hidden configs back to their original locations
+    Unhide {
+        /// Config paths to restore (e.g. .cursor .vscode)
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        targets: Vec<String>,
+
+        /// Restore every target in .cloak/storage, continuing past failures
+        #[arg(long)]
+        all: bool,
+
+        /// Create missing parent directories when restoring a nested target
+        #[arg(long)]
+        parents: bool,
+
+        /// Leave .gitignore untouched instead of removing the target's rule
+        #[arg(long)]
+        no_gitignore: bool,
+
+        /// Manage .gitignore even though the project is not inside a git repository
+        #[arg(long, conflicts_with = "no_gitignore")]
+        git: bool,
+
+        /// Move a real file/dir already at the target path to `<target>.cloak-bak`
+        /// and restore over it
+        #[arg(long)]
+        force: bool,
+
+        /// Before restoring, report how each target's storage differs from the last git commit
+        #[arg(long)]
+        diff_storage: bool,
+
+        /// Shell command to run once, from the project root, after every target succeeded
+        #[arg(long, value_name = "COMMAND")]
+        after: Option<String>,
+
+        /// Run the --after command even when some targets failed
+        #[arg(long, requires = "after")]
+        after_always: bool,
+
+        /// Print only a final count of succeeded/skipped/failed targets
+        #[arg(long)]
+        summary: bool,
+
+        /// Write a JSON record of each target's outcome to this file
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+    },
+
+    /// Show current cloak status and managed items
+    Status {
+        /// Render .cloak/storage as a tree, annotating each entry with its link state
+        #[arg(long)]
+        tree: bool,
+
+        /// Group hidden configs under their tool category (AI IDEs, agents, ...)
+        #[arg(long, conflicts_with = "tree")]
+        group: bool,
+
+        /// Print a JSON array with one object per hidden config and orphaned link
+        /// (same as --format json)
+        #[arg(long, conflicts_with_all = ["tree", "group", "format"])]
+        json: bool,
+
+        /// Output format; `json` prints the stable schema meant for scripts
+        #[arg(long, value_enum, default_value_t = StatusFormat::Table)]
+        format: StatusFormat,
+    },
+
+    /// List hidden targets, one per line
+    List {
+        /// Only show targets whose tool's command is no longer on PATH
+        #[arg(long)]
+        stale: bool,
+    },
+
+    /// Auto-scan project root for common dotfiles and hide them all
+    Tidy {
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Skip configs you already list in your own .gitignore rules
+        #[arg(long)]
+        respect_gitignore: bool,
+
+        /// Write ignore rules to .git/info/exclude instead of the shared .gitignore
+        #[arg(long)]
+        local_ignore: bool,
+
+        /// Manage .gitignore even though the project is not inside a git repository
+        #[arg(long)]
+        git: bool,
+
+        /// Print why each known config would be offered or skipped, then stop
+        #[arg(long)]
+        explain: bool,
+
+        /// Never hide this config (repeatable); added to `tidy_exclude` in cloak.toml
+        #[arg(long, value_name = "NAME")]
+        except: Vec<String>,
+
+        /// Maximum number of targets processed in parallel (1 = sequential)
+        #[arg(long, value_name = "N")]
+        concurrency: Option<NonZeroUsize>,
+
+        /// Shell command to run once, from the project root, after every target succeeded
+        #[arg(long, value_name = "COMMAND")]
+        after: Option<String>,
+
+        /// Run the --after command even when some targets failed
+        #[arg(long, requires = "after")]
+        after_always: bool,
+
+        /// Print only a final count of succeeded/skipped/failed targets
+        #[arg(long)]
+        summary: bool,
+
+        /// Write a JSON record of each target's outcome to this file
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+    },
+
+    /// List snapshots taken of a hidden target
+    Snapshots {
+        /// Hidden target (e.g. .cursor)
+        target: String,
+    },
+
+    /// Replace a hidden target's storage content with one of its snapshots
+    Rollback {
+        /// Hidden target (e.g. .cursor)
+        target: String,
+
+        /// Snapshot timestamp, as shown by `cloak snapshots`
+        timestamp: u64,
+    },
+
+    /// Show full details for one hidden target
+    Info {
+        /// Hidden target (e.g. .claude)
+        target: String,
+
+        /// Emit machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Set or clear the note explaining why a target is hidden
+    Label {
+        /// Hidden target (e.g. .idea)
+        target: String,
+
+        /// The note, e.g. "JetBrains config, team-shared"
+        #[arg(required_unless_present = "clear")]
+        text: Option<String>,
+
+        /// Remove the target's label
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+
+    /// Diagnose inconsistencies between the root, storage, and cloak's records
+    Doctor {
+        /// Recreate missing ghost links and re-anchor dangling ones first, then
+        /// remove root links into storage that resolve to nothing, prune ignore
+        /// rules and IDE excludes left for targets no longer in storage, and
+        /// re-add missing IDE excludes. Never touches real files or stored data;
+        /// reports what is left
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Recreate missing or dangling ghost links for everything in storage
+    Repair {
+        /// Also rewrite every ghost link as a relative symlink with canonical text
+        #[arg(long, visible_alias = "all-links-relative")]
+        normalize: bool,
+    },
+
+    /// Set up a fresh clone: recreate every ghost link from storage, plus each
+    /// target's IDE excludes and .gitignore rule
+    Relink {
+        /// Leave .gitignore untouched
+        #[arg(long, conflicts_with = "git")]
+        no_gitignore: bool,
+
+        /// Manage .gitignore even though the project is not inside a git repository
+        #[arg(long)]
+        git: bool,
+    },
+
+    /// List storage entries whose ghost link is gone; --restore or --delete acts on them
+    Prune {
+        /// Move each orphaned entry back to its original path at the root
+        #[arg(long, conflicts_with = "delete")]
+        restore: bool,
+
+        /// Delete each orphaned entry from storage (its snapshots are kept)
+        #[arg(long)]
+        delete: bool,
+
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Bundle every hidden config into a gzipped tarball, e.g. to share with a teammate
+    Export {
+        /// Archive to write (e.g. configs.tar.gz)
+        file: PathBuf,
+    },
+
+    /// Unpack a tarball made by `export` into storage and link each entry at the root;
+    /// an entry whose root path already exists is skipped, never overwritten
+    Import {
+        /// Archive to read (e.g. configs.tar.gz)
+        file: PathBuf,
+
+        /// Don't add .gitignore entries for the imported targets
+        #[arg(long, conflicts_with = "git")]
+        no_gitignore: bool,
+
+        /// Add .gitignore entries even outside a git repository
+        #[arg(long)]
+        git: bool,
+    },
+
+    /// Remove leftover .cloak artifacts (old snapshots, stale locks, empty dirs);
+    /// with --dry-run, only show what would be removed
+    Gc,
+
+    /// Print `export` lines pointing known tools' env vars at their storage entries
+    Env,
+
+    /// Manage where hidden configs are stored
+    Storage {
+        #[command(subcommand)]
+        action: StorageCommand,
+    },
+
+    /// Keep running and re-link hidden targets that a tool replaced with a real file/dir
+    Watch {
+        /// Milliseconds to wait for changes to settle before re-linking
+        #[arg(long, value_name = "MS", default_value_t = 500, value_parser = clap::value_parser!(u64).range(1..))]
+        debounce: u64,
+    },
+
+    /// Show version and build information
+    Version {
+        /// Emit machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a shell completion script for the name cloak was invoked as
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: utils::completions::Shell,
+
+        /// Register completions under this command name instead of the invoked one
+        #[arg(long, value_name = "NAME")]
+        bin_name: Option<String>,
+    },
+
+    /// Print hidden targets for the completion scripts; prints nothing when cloak
+    /// is not initialized
+    #[command(name = utils::completions::TARGETS_COMMAND, hide = true)]
+    CompleteTargets,
+}
+
+#[derive(Subcommand)]
+enum StorageCommand {
+    /// Relocate storage (e.g. outside the project) and re-create every ghost link;
+    /// pass `.cloak/storage` to move it back inline
+    Move {
+        /// New storage directory (supports `~`, `$VAR`, and `${VAR}`)
+        path: String,
+    },
+
+    /// Look for storage entries left incomplete by an interrupted hide, unhide, or move
+    Verify,
+
+    /// Move known tools' entries into per-category storage directories (e.g.
+    /// `.cloak/storage/agents/.claude`); ghost links stay at the same root paths
+    Regroup,
+}
+
+/// How `status` prints its report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum StatusFormat {
+    /// Colored, human-readable lines
+    Table,
+    /// One JSON object per hidden config and orphaned link
+    Json,
+}
+
+/// What kind of tool a known config directory belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ToolCategory {
+    Editor,
+    JetBrains,
+    Agent,
+    China,
+    VsCodeExtension,
+    /// Anything not in `KNOWN_DOTFILES`.
+    Other,
+}
+
+impl ToolCategory {
+    fn title(self) -> &'static str {
+        match self {
+            ToolCategory::Editor => "AI IDEs / Editors",
+            ToolCategory::JetBrains => "JetBrains",
+            ToolCategory::Agent => "AI coding agents",
+            ToolCategory::China => "China AI coding tools",
+            ToolCategory::VsCodeExtension => "VS Code AI extensions",
+            ToolCategory::Other => "Other",
+        }
+    }
+
+    /// The storage directory `storage regroup` files this category's entries under;
+    /// `None` leaves them where they are.
+    fn storage_dir(self) -> Option<&'static str> {
+        match self {
+            ToolCategory::Editor => Some("editors"),
+            ToolCategory::JetBrains => Some("jetbrains"),
+            ToolCategory::Agent => Some("agents"),
+            ToolCategory::China => Some("china"),
+            ToolCategory::VsCodeExtension => Some("vscode-extensions"),
+            ToolCategory::Other => None,
+        }
+    }
+
+    /// The category of a root-relative target name.
+    fn of(target: &str) -> Self {
+        KNOWN_DOTFILES
+            .iter()
+            .find(|(_, names)| names.contains(&target))
+            .map_or(ToolCategory::Other, |(category, _)| *category)
+    }
+}
+
+/// Known vibe coding tool config directories to auto-detect with `tidy`, by category.
+const KNOWN_DOTFILES: &[(ToolCategory, &[&str])] = &[
+    (
+        ToolCategory::Editor,
+        &[".cursor", ".vscode", ".windsurf", ".trae", ".zed"],
+    ),
+    (ToolCategory::JetBrains, &[".idea", ".junie"]),
+    (
+        ToolCategory::Agent,
+        &[
+            ".claude", ".codex", ".gemini", ".amazonq", ".augment", ".bolt", ".tabnine",
+        ],
+    ),
+    // 中国大模型代码工具
+    (
+        ToolCategory::China,
+        &[".codebuddy", ".lingma", ".comate", ".kimi"],
+    ),
+    (
+        ToolCategory::VsCodeExtension,
+        &[".cline", ".roo", ".kilocode"],
+    ),
+];
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => error::report(&e),
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    // `colored` already turns itself off when stdout is not a terminal, but lets
+    // CLICOLOR_FORCE win over NO_COLOR; an explicit opt-out should always win.
+    if cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        colored::control::set_override(false);
+    }
+    utils::output::set_level(if cli.quiet {
+        utils::output::Level::Quiet
+    } else if cli.verbose {
+        utils::output::Level::Verbose
+    } else {
+        utils::output::Level::Normal
+    });
+    let root = cli
+        .root
+        .unwrap_or_else(|| std::env::current_dir().expect("failed to get current directory"));
+    if !matches!(
+        cli.command,
+        Commands::Version { .. } | Commands::Completions { .. }
+    ) {
+        ensure_not_inside_cloak(&root)?;
+    }
+    let dry_run = cli.dry_run;
+    if dry_run
+        && !matches!(
+            cli.command,
+            Commands::Hide { .. } | Commands::Unhide { .. } | Commands::Tidy { .. } | Commands::Gc
+        )
+    {
+        bail!(CloakError::Invalid(
+            "--dry-run is only supported by hide, unhide, tidy, and gc".into()
+        ));
+    }
+    let _lock = if dry_run {
+        None
+    } else {
+        lock_project(&root, &cli.command)?
+    };
+
+    match cli.command {
+        Commands::Init { storage, git } => cmd_init(&root, storage.as_deref(), git),
+        Commands::Hide {
+            mut targets,
+            known,
+            stdin0,
+            from_manifest,
+            snapshot,
+            concurrency,
+            link_target_style,
+            force_ide,
+            env_detect,
+            assume_initialized,
+            no_gitignore,
+            local_ignore,
+            git,
+            verify,
+            atomic,
+            link_name,
+            flatten,
+            update,
+            force,
+            chmod,
+            immutable,
+            label,
+            after,
+            after_always,
+            summary,
+            report,
+        } => {
+            let opts = HideOptions {
+                pipeline: core::pipeline::HideOptions {
+                    snapshot,
+                    link_style: link_target_style.unwrap_or_default(),
+                    ide: config::ide::IdeOptions { force: force_ide },
+                    env_detect,
+                    no_gitignore,
+                    local_ignore,
+                    verify,
+                    link_name,
+                    flatten,
+                    update,
+                    force,
+                    chmod,
+                    label,
+                },
+                concurrency: worker_count(concurrency),
+                assume_initialized,
+                git,
+                atomic,
+                immutable,
+                batch: BatchOptions {
+                    summary,
+                    report,
+                    after,
+                    after_always,
+                    dry_run,
+                },
+            };
+            if stdin0 {
+                let listed = read_nul_separated(io::stdin().lock())?;
+                add_listed_targets(&root, &mut targets, listed, "stdin")?;
+            }
+            if from_manifest {
+                let listed = config::manifest::targets(&root)?;
+                add_listed_targets(&root, &mut targets, listed, "the manifest")?;
+            }
+            if known {
+                let listed = scan_known_dotfiles(&root, false, &[])?
+                    .into_iter()
+                    .filter(|scan| scan.offer)
+                    .map(|scan| scan.name)
+                    .collect();
+                add_listed_targets(&root, &mut targets, listed, "the known configs")?;
+                if targets.is_empty() {
+                    say!("{}", "No known dotfiles/configs found to hide.".dimmed());
+                    return Ok(());
+                }
+            }
+            cmd_hide(&root, &targets, &opts)
+        }
+        Commands::Unhide {
+            targets,
+            all,
+            parents,
+            no_gitignore,
+            git,
+            force,
+            diff_storage,
+            after,
+            after_always,
+            summary,
+            report,
+        } => {
+            let opts = UnhideOptions {
+                pipeline: core::pipeline::UnhideOptions {
+                    parents,
+                    no_gitignore,
+                    force,
+                    diff_storage,
+                },
+                all,
+                git,
+                batch: BatchOptions {
+                    summary,
+                    report,
+                    after,
+                    after_always,
+                    dry_run,
+                },
+            };
+            if all {
+                let targets = hidden_targets(&root)?;
+                let recorded = core::meta::Meta::load(&root)?;
+                if targets.is_empty() && recorded.missing_from_storage(&root).is_empty() {
+                    say!("{}", "No configs are currently hidden.".dimmed());
+                    return Ok(());
+                }
+                return cmd_unhide(&root, &targets, &opts);
+            }
+            cmd_unhide(&root, &targets, &opts)
+        }
+        Commands::Status {
+            tree,
+            group,
+            json,
+            format,
+        } => {
+            if json || format == StatusFormat::Json {
+                if tree || group {
+                    bail!(CloakError::Invalid(
+                        "--format json cannot be combined with --tree or --group".into()
+                    ));
+                }
+                cmd_status_json(&root)
+            } else {
+                cmd_status(&root, tree, group)
+            }
+        }
+        Commands::List { stale } => cmd_list(&root, stale),
+        Commands::Tidy {
+            yes,
+            respect_gitignore,
+            local_ignore,
+            git,
+            explain,
+            except,
+            concurrency,
+            after,
+            after_always,
+            summary,
+            report,
+        } => {
+            let opts = HideOptions {
+                pipeline: core::pipeline::HideOptions {
+                    local_ignore,
+                    ..Default::default()
+                },
+                concurrency: worker_count(concurrency),
+                git,
+                batch: BatchOptions {
+                    summary,
+                    report,
+                    after,
+                    after_always,
+                    dry_run,
+                },
+                ..HideOptions::default()
+            };
+            cmd_tidy(&root, yes, respect_gitignore, explain, &except, &opts)
+        }
+        Commands::Snapshots { target } => cmd_snapshots(&root, &target),
+        Commands::Rollback { target, timestamp } => cmd_rollback(&root, &target, timestamp),
+        Commands::Info { target, json } => cmd_info(&root, &target, json),
+        Commands::Label {
+            target,
+            text,
+            clear: _,
+        } => cmd_label(&root, &target, text),
+        Commands::Doctor { fix } => cmd_doctor(&root, fix),
+        Commands::Repair { normalize } => cmd_repair(&root, normalize, None),
+        Commands::Relink { no_gitignore, git } => {
+            let opts = core::pipeline::HideOptions {
+                no_gitignore: skips_gitignore(&root, no_gitignore, git)?,
+                local_ignore: uses_local_ignore(&root, false)?,
+                ..Default::default()
+            };
+            if !opts.no_gitignore && core::mover::storage_dir(&root).is_dir() {
+                utils::git::ensure_gitignore_entry(&root)?;
+            }
+            cmd_repair(&root, false, Some(&opts))
+        }
+        Commands::Prune {
+            restore,
+            delete,
+            yes,
+        } => cmd_prune(&root, restore, delete, yes),
+        Commands::Export { file } => cmd_export(&root, &file),
+        Commands::Import {
+            file,
+            no_gitignore,
+            git,
+        } => cmd_import(&root, &file, no_gitignore, git),
+        Commands::Gc => cmd_gc(&root, dry_run),
+        Commands::Env => cmd_env(&root),
+        Commands::Storage { action } => match action {
+            StorageCommand::Move { path } => cmd_storage_move(&root, &path),
+            StorageCommand::Regroup => cmd_storage_regroup(&root),
+            StorageCommand::Verify => cmd_storage_verify(&root),
+        },
+        Commands::Watch { debounce } => cmd_watch(&root, debounce),
+        Commands::Version { json } => cmd_version(json),
+        Commands::Completions { shell, bin_name } => cmd_completions(shell, bin_name),
+        Commands::CompleteTargets => cmd_complete_targets(&root),
+    }
+}
+
+/// Commands that change the root or `.cloak` hold the project lock while they run,
+/// so two of them cannot interleave edits to storage, `.gitignore` or meta.json.
+/// Read-only commands do not, nor does `watch`, which would hold it indefinitely,
+/// or `gc`, which only clears leftovers and must be able to judge a lock stale.
+fn lock_project(root: &Path, command: &Commands) -> Result<Option<core::lock::ProjectLock>> {
+    let create = match command {
+        Commands::Init { .. } | Commands::Tidy { .. } | Commands::Import { .. } => true,
+        Commands::Hide {
+            assume_initialized, ..
+        } => !assume_initialized,
+        Commands::Unhide { .. }
+        | Commands::Prune { .. }
+        | Commands::Repair { .. }
+        | Commands::Relink { .. }
+        | Commands::Rollback { .. }
+        | Commands::Label { .. }
+        | Commands::Doctor { fix: true }
+        | Commands::Storage {
+            action: StorageCommand::Move { .. } | StorageCommand::Regroup,
+        } => false,
+        _ => return Ok(None),
+    };
+    core::lock::acquire(root, create)
+}
+
+/// Append targets read from a list (stdin, a manifest) that were not named on the
+/// command line. Unlike an explicit argument, a valid listed target that does not
+/// exist is skipped with a warning, so one shared list works across checkouts.
+fn add_listed_targets(
+    root: &Path,
+    targets: &mut Vec<String>,
+    listed: Vec<String>,
+    source: &str,
+) -> Result<()> {
+    for target in listed {
+        let target = resolve_target(root, &target)?;
+        // Nesting is checked by `cmd_hide`, where `--flatten` is known.
+        validate_target(&target)?;
+        if targets.contains(&target) {
+            continue;
+        }
+        if root.join(&target).symlink_metadata().is_err() {
+            eprintln!(
+                "{} skipping {target} from {source}: not found",
+                "Warning:".yellow().bold()
+            );
+            continue;
+        }
+        targets.push(target);
+    }
+    Ok(())
+}
+
+/// Split NUL-delimited input (as written by `find -print0`) into target names.
+///
+/// Names may contain spaces or newlines; empty records (such as the trailing one
+/// after the final NUL) are skipped, and `find`'s leading `./` is dropped.
+fn read_nul_separated(mut input: impl io::Read) -> Result<Vec<String>> {
+    let mut bytes = Vec::new();
+    input
+        .read_to_end(&mut bytes)
+        .context("failed to read targets from stdin")?;
+    bytes
+        .split(|b| *b == 0)
+        .filter(|record| !record.is_empty())
+        .map(|record| {
+            let name = String::from_utf8(record.to_vec()).map_err(|_| {
+                CloakError::Invalid(format!(
+                    "target from stdin is not valid UTF-8: {}",
+                    String::from_utf8_lossy(record)
+                ))
+            })?;
+            Ok(name.strip_prefix("./").map(str::to_string).unwrap_or(name))
+        })
+        .collect()
+}
+
+/// Refuse to treat a `.cloak` directory (or anything below it) as a project root,
+/// which would otherwise set up a nested `.cloak` inside storage.
+fn ensure_not_inside_cloak(root: &Path) -> Result<()> {
+    let absolute = std::env::current_dir()
+        .map(|cwd| cwd.join(root))
+        .unwrap_or_else(|_| root.to_path_buf());
+    let candidates = [
+        Some(core::linker::normalize(&absolute)),
+        root.canonicalize().ok(),
+    ];
+    for path in candidates.iter().flatten() {
+        let Some(project) = path.ancestors().find_map(|dir| {
+            let is_cloak_dir = dir.file_name()
+                == Some(std::ffi::OsStr::new(core::mover::CLOAK_DIR))
+                && dir
+                    .join(core::mover::STORAGE_DIR)
+                    .symlink_metadata()
+                    .is_ok();
+            is_cloak_dir.then(|| dir.parent()).flatten()
+        }) else {
+            continue;
+        };
+        bail!(CloakError::Invalid(format!(
+            "you appear to be inside a cloak storage directory ({}); \
+             run from the project root ({})",
+            root.display(),
+            project.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Convert an absolute target that lies inside `root` into its root-relative name.
+///
+/// Trailing slashes (`.cursor/`, as shell completion adds) are dropped so the same
+/// name is used for storage, `.gitignore`, and IDE excludes. Otherwise relative
+/// targets are returned unchanged; `validate_target` still applies afterwards.
+fn resolve_target(root: &Path, target: &str) -> Result<String> {
+    let path = Path::new(target);
+    if !path.is_absolute() {
+        let trimmed = target.trim_end_matches(['/', '\\']);
+        return Ok(if trimmed.is_empty() { target } else { trimmed }.to_string());
+    }
+
+    // Canonicalize the parent only: the target itself may be a ghost link,
+    // and resolving it would point into storage instead of the root.
+    let root_abs = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let candidate = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|p| p.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    };
+
+    let Ok(relative) = candidate
+        .strip_prefix(&root_abs)
+        .or_else(|_| path.strip_prefix(root))
+    else {
+        bail!(CloakError::Invalid(format!(
+            "absolute path is outside the project root ({}): {target}",
+            root.display()
+        )));
+    };
+
+    let name = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    if name.is_empty() {
+        bail!(CloakError::Invalid(format!(
+            "cannot hide the project root itself: {target}"
+        )));
+    }
+    Ok(name)
+}
+
+fn resolve_targets(root: &Path, targets: &[String]) -> Result<Vec<String>> {
+    targets.iter().map(|t| resolve_target(root, t)).collect()
+}
+
+/// Ensure cloak is initialized, auto-initializing if needed.
+///
+/// With `gitignore` unset the `.cloak` rules are not written either.
+fn ensure_initialized(root: &Path, gitignore: bool) -> Result<()> {
+    let storage = root.join(".cloak").join("storage");
+    if !storage.exists() {
+        say!("{}", "Auto-initializing cloak...".dimmed());
+        core::mover::ensure_storage_dir(root)?;
+        core::mover::ensure_readme(root)?;
+        if gitignore {
+            utils::git::ensure_gitignore_entry(root)?;
+            warn_if_storage_ignored(root);
+        }
+    }
+    Ok(())
+}
+
+/// `--dry-run` counterpart of `ensure_initialized`.
+fn preview_init(root: &Path, gitignore: bool) {
+    if !core::mover::storage_dir(root).exists() {
+        say!("{}", "Would auto-initialize cloak:".dimmed());
+        say!(
+            "  {}",
+            "would create .cloak/storage and .cloak/README.md".dimmed()
+        );
+        if gitignore {
+            say!("  {}", "would add the .cloak rules to .gitignore".dimmed());
+        }
+    }
+}
+
+/// On `init`, keep the `.cloak` tree out of the editor's explorer and search
+/// (storage stays git-tracked) unless `ide_exclude_cloak = false`. A settings file
+/// that cannot be parsed only earns a warning: init should not fail over it.
+fn exclude_cloak_dir_in_ide(root: &Path) -> Result<()> {
+    if config::project::ProjectConfig::load(root)?.skip_ide_cloak_exclude {
+        return Ok(());
+    }
+    let opts = config::ide::IdeOptions::default();
+    if let Err(e) = config::ide::add_ide_exclude(root, core::mover::CLOAK_DIR, &opts) {
+        eprintln!(
+            "{} could not exclude .cloak in IDE settings: {e:#}",
+            "Warning:".yellow().bold()
+        );
+    }
+    Ok(())
+}
+
+/// Whether `.gitignore` should be left alone: `--no-gitignore`, `gitignore = false`
+/// in `.cloak/cloak.toml`, or a project outside any git repository unless `--git`
+/// asked for the rules anyway.
+fn skips_gitignore(root: &Path, flag: bool, git: bool) -> Result<bool> {
+    Ok(flag
+        || config::project::ProjectConfig::load(root)?.skip_gitignore
+        || (!git && !utils::git::in_repository(root)))
+}
+
+/// Whether new per-target rules go to `.git/info/exclude`: `--local-ignore` or
+/// `local_ignore = true` in `.cloak/cloak.toml`.
+fn uses_local_ignore(root: &Path, flag: bool) -> Result<bool> {
+    Ok(flag || config::project::ProjectConfig::load(root)?.local_ignore)
+}
+
+/// Warn when a broader gitignore rule would keep `.cloak/storage` out of git
+/// even though our whitelist line is present.
+fn warn_if_storage_ignored(root: &Path) {
+    if let Some(rule) = utils::git::storage_ignore_conflict(root) {
+        eprintln!(
+            "{} .cloak/storage is still ignored by git because of `{}`; \
+             hidden configs will not be committed until that rule is narrowed.",
+            "Warning:".yellow().bold(),
+            rule
+        );
+    }
+}
+
+fn cmd_init(root: &Path, storage: Option<&str>, git: bool) -> Result<()> {
+    say!("{}", "Initializing cloak...".bold());
+
+    match storage {
+        Some(raw) => {
+            let external = root.join(utils::env::expand(raw)?);
+            core::mover::link_external_storage(root, &external)?;
+            let mut meta = core::meta::Meta::load(root)?;
+            meta.set_external_storage(Some(external.clone()));
+            meta.save(root)?;
+            say!("  storage -> {}", external.display());
+        }
+        None => core::mover::ensure_storage_dir(root)?,
+    }
+    core::mover::ensure_readme(root)?;
+    if !skips_gitignore(root, false, git)? {
+        utils::git::ensure_gitignore_entry(root)?;
+        warn_if_storage_ignored(root);
+    }
+    exclude_cloak_dir_in_ide(root)?;
+
+    say!(
+        "{}",
+        "Cloak initialized. Use `cloak hide <target>` to start hiding configs.".green()
+    );
+    Ok(())
+}
+
+/// Options shared by every command that hides targets (`hide`, `tidy`).
+#[derive(Debug, Clone)]
+struct HideOptions {
+    /// How each target is hidden.
+    pipeline: core::pipeline::HideOptions,
+    concurrency: usize,
+    /// Trust that `.cloak/storage` exists instead of auto-initializing.
+    assume_initialized: bool,
+    /// Manage `.gitignore` even outside a git repository.
+    git: bool,
+    /// Roll back every hidden target (and the shared file edits) if any target fails.
+    atomic: bool,
+    /// Set the immutable flag on each stored entry once its hide is complete.
+    immutable: bool,
+    batch: BatchOptions,
+}
+
+/// Options for `unhide`.
+#[derive(Debug, Clone, Default)]
+struct UnhideOptions {
+    /// How each target is restored.
+    pipeline: core::pipeline::UnhideOptions,
+    /// Restoring every hidden target: records missing from storage are reported
+    /// as skipped, and a summary line always ends the run.
+    all: bool,
+    /// Manage `.gitignore` even outside a git repository.
+    git: bool,
+    batch: BatchOptions,
+}
+
+/// How a batch command reports its per-target results (`hide`, `unhide`, `tidy`).
+#[derive(Debug, Clone, Default)]
+struct BatchOptions {
+    /// Suppress per-target lines and print one aggregate line at the end.
+    summary: bool,
+    /// Also write a JSON report of the outcome to this file.
+    report: Option<PathBuf>,
+    /// Shell command to run once the batch has finished without failures.
+    after: Option<String>,
+    /// Run `after` even when some targets failed.
+    after_always: bool,
+    /// Check targets and print what would change, but change nothing.
+    dry_run: bool,
+}
+
+impl BatchOptions {
+    /// Run the `--after` command, if any, in `root`. Skipped when the batch had
+    /// failures unless `--after-always` was given.
+    fn run_after(&self, root: &Path, batch_ok: bool) -> Result<()> {
+        let Some(command) = &self.after else {
+            return Ok(());
+        };
+        if !batch_ok && !self.after_always {
+            say!(
+                "{}",
+                format!("Skipping --after command `{command}`: some targets failed.").dimmed()
+            );
+            return Ok(());
+        }
+        say!("{} {}", "Running".bold(), command);
+        utils::process::run_shell(command, root).context("--after command failed")
+    }
+}
+
+impl Default for HideOptions {
+    fn default() -> Self {
+        Self {
+            pipeline: core::pipeline::HideOptions::default(),
+            concurrency: worker_count(None),
+            assume_initialized: false,
+            git: false,
+            atomic: false,
+            immutable: false,
+            batch: BatchOptions::default(),
+        }
+    }
+}
+
+/// Parse an octal permission mode such as `600`, `0700`, or `0o600`.
+fn parse_mode(s: &str) -> std::result::Result<u32, String> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("`{s}` is not an octal mode (e.g. 600 or 0700)")),
+    }
+}
+
+/// Resolve `--concurrency`, defaulting to the number of CPUs (capped).
+fn worker_count(requested: Option<NonZeroUsize>) -> usize {
+    requested.map_or_else(utils::parallel::default_workers, NonZeroUsize::get)
+}
+
+fn cmd_hide(root: &Path, targets: &[String], opts: &HideOptions) -> Result<()> {
+    let targets = resolve_targets(root, targets)?;
+    for target in &targets {
+        validate_target(target)?;
+        ensure_outside_hidden(root, target)?;
+    }
+
+    let mut opts = opts.clone();
+    if let Some(name) = &opts.pipeline.link_name {
+        if targets.len() != 1 {
+            bail!(CloakError::Invalid(
+                "--link-name needs exactly one target".into()
+            ));
+        }
+        validate_target(name)?;
+        if *name == targets[0] {
+            opts.pipeline.link_name = None;
+        }
+    }
+    if cfg!(not(unix)) && opts.pipeline.chmod.take().is_some() {
+        eprintln!(
+            "{} --chmod is only supported on Unix; leaving permissions unchanged.",
+            "Warning:".yellow().bold()
+        );
+    }
+    if cfg!(not(unix)) && std::mem::take(&mut opts.immutable) {
+        eprintln!(
+            "{} --immutable is only supported on Unix; storage stays writable.",
+            "Warning:".yellow().bold()
+        );
+    }
+    opts.pipeline.no_gitignore = skips_gitignore(root, opts.pipeline.no_gitignore, opts.git)?;
+    opts.pipeline.local_ignore = uses_local_ignore(root, opts.pipeline.local_ignore)?;
+    let opts = &opts;
+
+    if opts.assume_initialized && !core::mover::storage_dir(root).is_dir() {
+        bail!(CloakError::NotInitialized(format!(
+            "cloak is not initialized in {} (.cloak/storage is missing); \
+             run `cloak init` or drop --assume-initialized",
+            root.display()
+        )));
+    } else if opts.batch.dry_run {
+        preview_init(root, !opts.pipeline.no_gitignore);
+    } else if !opts.assume_initialized {
+        ensure_initialized(root, !opts.pipeline.no_gitignore)?;
+    }
+    hide_targets(root, &targets, Vec::new(), opts)?;
+
+    if opts.batch.dry_run {
+        say!("{}", "Dry run: nothing was changed.".dimmed());
+    } else if !opts.batch.summary {
+        say!("{}", "Done. Your root directory is now pristine.".green());
+    }
+    Ok(())
+}
+
+/// Run the hide pipeline for a batch of validated targets.
+///
+/// Moving, linking, and OS-hiding touch only per-target paths, so they run on up
+/// to `opts.concurrency` worker threads. The IDE settings and `.gitignore` edits
+/// share files and always run afterwards on this thread, in target order.
+/// Every target is attempted; the batch fails if any target failed. With
+/// `opts.atomic`, a failure also rolls back every target this batch hid.
+/// `left_out` names targets the caller dropped beforehand; they are counted (and
+/// reported) as skipped.
+fn hide_targets(
+    root: &Path,
+    targets: &[String],
+    left_out: Vec<String>,
+    opts: &HideOptions,
+) -> Result<()> {
+    if opts.batch.dry_run {
+        return preview_hide(root, targets, opts);
+    }
+    let project = config::project::ProjectConfig::load(root)?;
+    let mut meta = core::meta::Meta::load(root)?;
+    let shared = if opts.atomic {
+        Some(SharedFiles::capture(root)?)
+    } else {
+        None
+    };
+
+    // Targets whose ghost link is already in place are skipped, not failed.
+    let (skipped, pending): (Vec<String>, Vec<String>) =
+        targets.iter().cloned().partition(|target| {
+            is_already_hidden(root, target, &opts.pipeline.storage_key(target))
+                && stale_link(root, target, &opts.pipeline).is_none()
+        });
+
+    let staged = utils::parallel::map(&pending, opts.concurrency, |target| {
+        stage_hide(root, target, &opts.pipeline, &project)
+    });
+
+    let mut summary = BatchSummary::default();
+    let mut exported = 0;
+    if !opts.batch.summary {
+        for target in &skipped {
+            say!("{} {}", "Hiding".bold(), target.yellow());
+            say!("  {} {} (already hidden)", "-".dimmed(), target);
+        }
+    }
+    summary.skipped = left_out;
+    summary.skipped.extend(skipped);
+
+    for (target, result) in pending.iter().zip(staged) {
+        if !opts.batch.summary {
+            say!("{} {}", "Hiding".bold(), target.yellow());
+        }
+
+        let result = result.and_then(|staged| {
+            let (notes, edits) =
+                core::pipeline::finish_hide(root, &mut meta, target, staged, &opts.pipeline)?;
+            if edits.contains(&SharedEdit::Env) {
+                exported += 1;
+            }
+            Ok(notes)
+        });
+
+        match result {
+            Ok(notes) => {
+                if !opts.batch.summary {
+                    for note in notes {
+                        say!("  {}", note.dimmed());
+                    }
+                    say!("  {} {}", "✓".green(), target);
+                }
+                summary.succeeded.push(target.clone());
+            }
+            Err(e) => {
+                if !opts.batch.summary {
+                    say!("  {} {}", "✗".red(), target);
+                }
+                summary.failed.push((target.clone(), e));
+            }
+        }
+    }
+
+    match shared {
+        Some(shared) if !summary.failed.is_empty() => {
+            roll_back_hides(root, shared, opts, &mut summary);
+            exported = 0;
+        }
+        // Rolled-back targets never reach meta.json.
+        _ => {
+            #[cfg(unix)]
+            if opts.immutable {
+                lock_hidden(root, &mut meta, opts, &mut summary);
+            }
+            meta.save(root)?;
+        }
+    }
+
+    if opts.batch.summary {
+        summary.print("hidden");
+    }
+    if exported > 0 {
+        say!(
+            "{}",
+            "Run `source .cloak/env` (or `eval \"$(cloak env)\"`) so these tools read config from storage."
+                .dimmed()
+        );
+    }
+    if let Some(path) = &opts.batch.report {
+        let mut steps = vec!["move"];
+        if opts.pipeline.snapshot {
+            steps.push("snapshot");
+        }
+        steps.extend(["link", "os-hide"]);
+        if opts.pipeline.verify {
+            steps.push("verify");
+        }
+        steps.push("ide-exclude");
+        if !opts.pipeline.no_gitignore {
+            steps.push("gitignore");
+        }
+        summary.write_report(path, root, "hide", &steps, |target| {
+            (
+                opts.pipeline.link(target).to_string(),
+                opts.pipeline.storage_key(target),
+            )
+        })?;
+    }
+    summary.finish("hide", root, &opts.batch)
+}
+
+/// `--dry-run` counterpart of `hide_targets`: run each target's checks and print
+/// the steps it would take. A target that would fail fails the batch.
+fn preview_hide(root: &Path, targets: &[String], opts: &HideOptions) -> Result<()> {
+    let mut summary = BatchSummary::default();
+    for target in targets {
+        say!("{} {}", "Hiding".bold(), target.yellow());
+        if is_already_hidden(root, target, &opts.pipeline.storage_key(target)) {
+            if let Some(reason) = stale_link(root, target, &opts.pipeline) {
+                let link = opts.pipeline.link(target);
+                say!(
+                    "  {}",
+                    format!("would recreate the {reason} ghost link {link}").dimmed()
+                );
+                summary.succeeded.push(target.clone());
+            } else {
+                say!("  {} {} (already hidden)", "-".dimmed(), target);
+                summary.skipped.push(target.clone());
+            }
+            continue;
+        }
+        match preview_hide_target(root, target, opts) {
+            Ok(steps) => {
+                for step in steps {
+                    say!("  {}", step.dimmed());
+                }
+                summary.succeeded.push(target.clone());
+            }
+            Err(e) => {
+                say!("  {} {}", "✗".red(), target);
+                summary.failed.push((target.clone(), e));
+            }
+        }
+    }
+    finish_preview(summary, "hide", &opts.batch)
+}
+
+/// The checks `stage_hide` and `ingest` make before changing anything, and the
+/// steps the hide pipeline would then take for `target`.
+fn preview_hide_target(root: &Path, target: &str, opts: &HideOptions) -> Result<Vec<String>> {
+    let key = opts.pipeline.storage_key(target);
+    let link = opts.pipeline.link(target);
+    let src = root.join(target);
+    if src.symlink_metadata().is_err() {
+        bail!(CloakError::Invalid(format!(
+            "target does not exist: {}",
+            src.display()
+        )));
+    }
+    core::linker::ensure_no_case_variant(root, target)?;
+    core::mover::ensure_hideable(&src)?;
+
+    let mut steps = Vec::new();
+    let stored = core::mover::storage_dir(root).join(&key);
+    if stored.symlink_metadata().is_ok() {
+        if !opts.pipeline.update {
+            bail!(CloakError::Conflict(format!(
+                "target already exists in storage: {} (already hidden?)",
+                stored.display()
+            )));
+        }
+        steps.push(format!("would snapshot and replace .cloak/storage/{key}"));
+    }
+    if link != target && root.join(link).symlink_metadata().is_ok() {
+        bail!(CloakError::Conflict(format!(
+            "cannot create symlink: path already exists at {}",
+            root.join(link).display()
+        )));
+    }
+
+    steps.push(format!("would move {target} -> .cloak/storage/{key}"));
+    if let Some(mode) = opts.pipeline.chmod {
+        steps.push(format!("would set mode {mode:04o} on .cloak/storage/{key}"));
+    }
+    if opts.pipeline.snapshot {
+        steps.push(format!("would snapshot .cloak/storage/{key}"));
+    }
+    let text = core::linker::link_target_text(root, link, &key, opts.pipeline.link_style);
+    steps.push(format!("would create symlink {link} -> {}", text.display()));
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        steps.push(format!("would set the hidden flag on {link}"));
+    }
+    let excluded = config::ide::exclude_status(root, link)?;
+    for dir in config::ide::present_dirs(root) {
+        if excluded.contains(&(dir.clone(), true)) {
+            continue;
+        }
+        steps.push(if dir == config::jetbrains::IDEA_DIR {
+            format!("would add an excludeFolder for {link} to the {dir} project modules")
+        } else {
+            format!("would add **/{link} to {dir}/settings.json files.exclude and search.exclude")
+        });
+    }
+    if !opts.pipeline.no_gitignore && !utils::git::has_ignore_entry(root, link)? {
+        let file = if opts.pipeline.local_ignore {
+            ".git/info/exclude"
+        } else {
+            ".gitignore"
+        };
+        steps.push(format!("would add /{link} to {file}"));
+    }
+    if opts.pipeline.env_detect
+        && let Some(var) = config::tool_env::var_for(&key)
+    {
+        steps.push(format!("would export {var} in .cloak/env"));
+    }
+    if opts.immutable {
+        steps.push(format!(
+            "would set the immutable flag on .cloak/storage/{key}"
+        ));
+    }
+    Ok(steps)
+}
+
+/// Report a `--dry-run` batch: the `--after` command it would run, then the
+/// same result the real batch would have.
+fn finish_preview(summary: BatchSummary, verb: &str, batch: &BatchOptions) -> Result<()> {
+    if let Some(command) = &batch.after
+        && (summary.failed.is_empty() || batch.after_always)
+    {
+        say!("{}", format!("Would run `{command}`").dimmed());
+    }
+    summary.into_result(verb)
+}
+
+/// Undo an atomic batch: put every target it hid back at the root, then restore
+/// the shared files byte for byte. Targets that had succeeded are reported as
+/// failed since they are no longer hidden; failed ones already undid themselves.
+fn roll_back_hides(
+    root: &Path,
+    shared: SharedFiles,
+    opts: &HideOptions,
+    summary: &mut BatchSummary,
+) {
+    let hidden = std::mem::take(&mut summary.succeeded);
+    if !opts.batch.summary {
+        say!(
+            "{} {} hidden target(s)",
+            "Rolling back".bold(),
+            hidden.len()
+        );
+    }
+    for target in hidden.iter().rev() {
+        let undone = undo_stage_hide(
+            root,
+            opts.pipeline.link(target),
+            target,
+            &opts.pipeline.storage_key(target),
+        );
+        if !opts.batch.summary {
+            match &undone {
+                Ok(()) => say!("  {} {}", "↺".yellow(), target),
+                Err(_) => say!("  {} {}", "✗".red(), target),
+            }
+        }
+        let error = match undone {
+            Ok(()) => anyhow!("rolled back because another target failed (--atomic)"),
+            Err(e) => e.context("rollback failed; the target is still in storage"),
+        };
+        summary.failed.push((target.clone(), error));
+    }
+    if let Err(e) = shared.restore() {
+        summary
+            .failed
+            .push((".gitignore/IDE settings".to_string(), e));
+    }
+}
+
+/// Files every target in a hide batch may edit (`.gitignore`, IDE settings,
+/// `.cloak/env`), captured as they were so an atomic batch can put them back.
+struct SharedFiles(Vec<(PathBuf, Option<Vec<u8>>)>);
+
+impl SharedFiles {
+    fn capture(root: &Path) -> Result<Self> {
+        let mut paths = utils::git::rule_paths(root);
+        paths.extend(config::ide::settings_files(root));
+        paths.push(config::tool_env::env_file(root));
+
+        let mut files = Vec::with_capacity(paths.len());
+        for path in paths {
+            let content = match std::fs::read(&path) {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to read {}", path.display()));
+                }
+            };
+            files.push((path, content));
+        }
+        Ok(Self(files))
+    }
+
+    /// Write each file back, or remove it if it did not exist when captured.
+    fn restore(self) -> Result<()> {
+        for (path, content) in self.0 {
+            match content {
+                Some(content) => std::fs::write(&path, content)
+                    .with_context(|| format!("failed to restore {}", path.display()))?,
+                None if path.exists() => std::fs::remove_file(&path)
+                    .with_context(|| format!("failed to remove {}", path.display()))?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome tally for a batch of targets (`hide`, `unhide`, `tidy`).
+#[derive(Default)]
+struct BatchSummary {
+    succeeded: Vec<String>,
+    skipped: Vec<String>,
+    failed: Vec<(String, anyhow::Error)>,
+}
+
+impl BatchSummary {
+    fn print(&self, done: &str) {
+        say!(
+            "{}: {} {done}, {} skipped, {} failed",
+            "Summary".bold(),
+            self.succeeded.len().to_string().green(),
+            self.skipped.len().to_string().dimmed(),
+            self.failed.len().to_string().red()
+        );
+        for (target, e) in &self.failed {
+            say!("  {} {target}: {e:#}", "✗".red());
+        }
+    }
+
+    /// Write the outcome as JSON: one record per target with its status, the
+    /// pipeline steps it went through, and where it now lives. `locate` gives a
+    /// target's ghost link name and storage key.
+    fn write_report(
+        &self,
+        path: &Path,
+        root: &Path,
+        command: &str,
+        steps: &[&str],
+        locate: impl Fn(&str) -> (String, String),
+    ) -> Result<()> {
+        let record = |target: &str, status: &str, steps: &[&str], error: Option<String>| {
+            let (link, key) = locate(target);
+            serde_json::json!({
+                "target": target,
+                "status": status,
+                "success": error.is_none(),
+                "steps": steps,
+                "link_path": root.join(link),
+                "storage_path": core::mover::storage_dir(root).join(key),
+                "error": error,
+            })
+        };
+
+        let targets: Vec<serde_json::Value> = self
+            .succeeded
+            .iter()
+            .map(|t| record(t, "succeeded", steps, None))
+            .chain(self.skipped.iter().map(|t| record(t, "skipped", &[], None)))
+            .chain(
+                self.failed
+                    .iter()
+                    .map(|(t, e)| record(t, "failed", &[], Some(format!("{e:#}")))),
+            )
+            .collect();
+
+        let report = serde_json::json!({
+            "command": command,
+            "root": root,
+            "succeeded": self.succeeded.len(),
+            "skipped": self.skipped.len(),
+            "failed": self.failed.len(),
+            "targets": targets,
+        });
+        let mut content =
+            serde_json::to_string_pretty(&report).context("failed to serialize report")?;
+        content.push('\n');
+        std::fs::write(path, content)
+            .with_context(|| format!("failed to write report {}", path.display()))
+    }
+
+    /// Run the batch's `--after` command, then turn the outcome into a result. The
+    /// batch's own failure wins; an after-command failure is then only printed.
+    fn finish(self, verb: &str, root: &Path, batch: &BatchOptions) -> Result<()> {
+        let after = batch.run_after(root, self.failed.is_empty());
+        match self.into_result(verb) {
+            Ok(()) => after,
+            Err(e) => {
+                if let Err(after) = after {
+                    eprintln!("{} {after:#}", "Error:".red().bold());
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// One failure is returned as-is; several are listed and reported as a count.
+    fn into_result(mut self, verb: &str) -> Result<()> {
+        let total = self.succeeded.len() + self.skipped.len() + self.failed.len();
+        match self.failed.len() {
+            0 => Ok(()),
+            1 => Err(self.failed.remove(0).1),
+            n => {
+                for (target, e) in &self.failed {
+                    eprintln!("{} {target}: {e:#}", "Error:".red().bold());
+                }
+                let mut codes = self.failed.iter().map(|(_, e)| error::exit_code(e));
+                let first = codes.next().unwrap_or(1);
+                let code = if codes.all(|c| c == first) { first } else { 1 };
+                bail!(CloakError::Batch {
+                    message: format!("{n} of {total} targets failed to {verb}"),
+                    code,
+                })
+            }
+        }
+    }
+}
+
+/// Set the immutable flag on every target the batch hid. This runs last because
+/// IDE folders such as `.cursor` hold settings files that later targets' exclude
+/// edits write to. A target that cannot be locked stays hidden but counts as failed.
+#[cfg(unix)]
+fn lock_hidden(
+    root: &Path,
+    meta: &mut core::meta::Meta,
+    opts: &HideOptions,
+    summary: &mut BatchSummary,
+) {
+    for target in std::mem::take(&mut summary.succeeded) {
+        let key = opts.pipeline.storage_key(&target);
+        let stored = core::mover::storage_dir(root).join(&key);
+        match core::hider::set_immutable(&stored, true) {
+            Ok(()) => {
+                meta.entry_mut(&key).immutable = true;
+                if !opts.batch.summary {
+                    say!("{} {}", "Locked".bold(), target.yellow());
+                }
+                summary.succeeded.push(target);
+            }
+            Err(e) => {
+                // Leave no partly locked tree behind.
+                let _ = core::hider::set_immutable(&stored, false);
+                let e = e.context(format!(
+                    "{target} is hidden, but could not be made immutable"
+                ));
+                summary.failed.push((target, e));
+            }
+        }
+    }
+}
+
+/// Every hidden target by the root path it was hidden from, for `unhide --all`.
+fn hidden_targets(root: &Path) -> Result<Vec<String>> {
+    if !core::mover::storage_dir(root).is_dir() {
+        bail!(CloakError::NotInitialized(format!(
+            "cloak is not initialized in {} (.cloak/storage is missing)",
+            root.display()
+        )));
+    }
+    let meta = core::meta::Meta::load(root)?;
+    Ok(core::storage::entry_names(root)?
+        .iter()
+        .map(|key| meta.origin(key).to_string())
+        .collect())
+}
+
+fn cmd_unhide(root: &Path, targets: &[String], opts: &UnhideOptions) -> Result<()> {
+    let targets = resolve_targets(root, targets)?;
+    for target in &targets {
+        validate_target(target)?;
+    }
+    let mut opts = opts.clone();
+    opts.pipeline.no_gitignore = skips_gitignore(root, opts.pipeline.no_gitignore, opts.git)?;
+    let opts = &opts;
+    let batch = &opts.batch;
+    // Restoring drops each target's record, so the report reads where it lived from here.
+    let recorded = core::meta::Meta::load(root)?;
+
+    let mut summary = BatchSummary::default();
+    if opts.all {
+        let meta = core::meta::Meta::load(root)?;
+        for key in meta.missing_from_storage(root) {
+            let target = meta.origin(key);
+            if !batch.summary {
+                say!("{} {}", "Restoring".bold(), target.yellow());
+                say!(
+                    "  {} {target} (recorded in meta.json but missing from storage)",
+                    "-".dimmed()
+                );
+            }
+            summary.skipped.push(target.to_string());
+        }
+    }
+    for target in &targets {
+        if !batch.summary {
+            say!("{} {}", "Restoring".bold(), target.yellow());
+        }
+
+        let result = if batch.dry_run {
+            preview_unhide_target(root, target, opts)
+        } else {
+            unhide_target(root, target, &opts.pipeline)
+        };
+        match result {
+            Ok(notes) => {
+                if !batch.summary {
+                    for note in notes {
+                        say!("  {}", note.dimmed());
+                    }
+                    if !batch.dry_run {
+                        say!("  {} {}", "✓".green(), target);
+                    }
+                }
+                summary.succeeded.push(target.clone());
+            }
+            Err(e) => {
+                if !batch.summary {
+                    say!("  {} {}", "✗".red(), target);
+                }
+                summary.failed.push((target.clone(), e));
+            }
+        }
+    }
+
+    if batch.dry_run {
+        finish_preview(summary, "unhide", batch)?;
+        say!("{}", "Dry run: nothing was changed.".dimmed());
+        return Ok(());
+    }
+    if batch.summary || opts.all {
+        summary.print("restored");
+    }
+    if let Some(path) = &batch.report {
+        let mut steps = vec!["ide-exclude"];
+        if !opts.pipeline.no_gitignore {
+            steps.push("gitignore");
+        }
+        steps.extend(["unlink", "move", "os-unhide"]);
+        summary.write_report(path, root, "unhide", &steps, |target| {
+            let key = recorded.storage_key(target);
+            (recorded.link_name(key).to_string(), key.to_string())
+        })?;
+    }
+    summary.finish("unhide", root, batch)?;
+
+    if !batch.summary {
+        say!(
+            "{}",
+            "Done. Configs restored to their original locations.".green()
+        );
+    }
+    Ok(())
+}
+
+/// The checks `unhide_target` makes before changing anything, and the steps it
+/// would then take for `target`.
+fn preview_unhide_target(root: &Path, target: &str, opts: &UnhideOptions) -> Result<Vec<String>> {
+    let meta = core::meta::Meta::load(root)?;
+    let key = meta.storage_key(target);
+    let target = meta.origin(key);
+    let link = meta.link_name(key);
+
+    let stored = core::mover::storage_dir(root).join(key);
+    if stored.symlink_metadata().is_err() {
+        bail!("target not found in storage: {}", stored.display());
+    }
+    let mut steps = Vec::new();
+    if let Some(parent) = core::mover::missing_parent(root, target) {
+        if !opts.pipeline.parents {
+            bail!(
+                "parent directory does not exist at root: {} (pass --parents to create it)",
+                parent.display()
+            );
+        }
+        steps.push(format!("would create {}", parent.display()));
+    }
+    if meta.get(key).is_some_and(|e| e.immutable) {
+        steps.push(format!(
+            "would clear the immutable flag on .cloak/storage/{key}"
+        ));
+    }
+
+    let live = root.join(target);
+    let occupied = live
+        .symlink_metadata()
+        .is_ok_and(|m| !m.file_type().is_symlink());
+    if occupied {
+        if !opts.pipeline.force {
+            bail!(CloakError::Conflict(format!(
+                "path is not a symlink (refusing to overwrite): {} \
+                 (pass --force to back it up to {}.cloak-bak and restore over it)",
+                live.display(),
+                target
+            )));
+        }
+        let backup = core::mover::backup_path(&live);
+        steps.push(format!(
+            "would back up {target} to {}",
+            backup.strip_prefix(root).unwrap_or(&backup).display()
+        ));
+    }
+    for (dir, excluded) in config::ide::exclude_status(root, link)? {
+        if excluded {
+            steps.push(if dir == config::jetbrains::IDEA_DIR {
+                format!("would remove the {link} excludeFolder from the {dir} project modules")
+            } else {
+                format!(
+                    "would remove **/{link} from {dir}/settings.json files.exclude and search.exclude"
+                )
+            });
+        }
+    }
+    if !opts.pipeline.no_gitignore
+        && let Some(file) = utils::git::ignore_entry_file(root, link)?
+    {
+        let file = file.strip_prefix(root).unwrap_or(&file);
+        steps.push(format!("would remove /{link} from {}", file.display()));
+    }
+    if config::tool_env::is_recorded(root, key)? {
+        let var = config::tool_env::var_for(key).unwrap_or_default();
+        steps.push(format!("would drop {var} from .cloak/env"));
+    }
+    if root
+        .join(link)
+        .symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink())
+    {
+        steps.push(format!("would remove symlink {link}"));
+    }
+    steps.push(format!("would move .cloak/storage/{key} -> {target}"));
+    if cfg!(any(target_os = "macos", target_os = "windows")) {
+        steps.push(format!("would clear the hidden flag on {target}"));
+    }
+    Ok(steps)
+}
+
+fn cmd_status(root: &Path, tree: bool, group: bool) -> Result<()> {
+    let storage = root.join(".cloak").join("storage");
+
+    if !storage.exists() {
+        say!(
+            "{}",
+            "Cloak is not initialized in this directory. Run `cloak init` first.".yellow()
+        );
+        return Ok(());
+    }
+
+    // 1. Show items in storage; entries that cannot even be listed are reported, not dropped
+    let mut entries = Vec::new();
+    let mut unlisted = Vec::new();
+    for entry in std::fs::read_dir(&storage)? {
+        match entry {
+            Ok(e) if e.file_name() == core::snapshot::SNAPSHOT_DIR => {}
+            Ok(e) => entries.push(e),
+            Err(e) => unlisted.push(e),
+        }
+    }
+
+    let meta = core::meta::Meta::load(root)?;
+    let missing = meta.missing_from_storage(root);
+    // Nested entries (`config/ai`) by their full name; if some grouping directory
+    // cannot be walked, fall back to the top-level names rather than failing.
+    let stored = core::storage::entry_names(root).unwrap_or_else(|_| {
+        entries
+            .iter()
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect()
+    });
+    // meta.json says what is hidden; the storage walk only cross-checks it.
+    let mut names: Vec<String> = meta
+        .targets()
+        .filter(|target| !missing.contains(target))
+        .map(str::to_string)
+        .collect();
+    let unrecorded: Vec<&String> = stored.iter().filter(|n| meta.get(n).is_none()).collect();
+    if names.is_empty()
+        && unrecorded.is_empty()
+        && unlisted.is_empty()
+        && missing.is_empty()
+        && find_orphaned_links(root, &storage).is_empty()
+    {
+        say!("{}", "No configs are currently hidden.".dimmed());
+        return Ok(());
+    }
+
+    if !entries.is_empty() && tree {
+        say!("{}", ".cloak/storage".bold());
+        print_storage_tree(&core::storage::walk(root)?, "");
+    } else if !names.is_empty() && group {
+        names.sort_by_key(|name| (ToolCategory::of(name), name.clone()));
+
+        say!("{}", "Hidden configs:".bold());
+        let mut current = None;
+        for name in &names {
+            let category = ToolCategory::of(name);
+            if current != Some(category) {
+                current = Some(category);
+                say!("  {}", category.title().bold());
+            }
+            say!("    {}", status_line(root, &meta, name));
+        }
+    } else if !names.is_empty() {
+        say!("{}", "Hidden configs:".bold());
+        for name in &names {
+            say!("  {}", status_line(root, &meta, name));
+        }
+    }
+    for err in &unlisted {
+        say!("  ? [{}]", format!("unreadable: {err}").red());
+    }
+
+    // Storage entries meta.json has no record of, e.g. in a fresh clone
+    if !unrecorded.is_empty() && !tree {
+        say!(
+            "\n{}",
+            "In storage but not recorded in .cloak/meta.json:"
+                .yellow()
+                .bold()
+        );
+        for name in &unrecorded {
+            say!("  {}", status_line(root, &meta, name));
+        }
+        say!(
+            "{}",
+            "  Tip: `cloak relink` records them (e.g. after a fresh clone).".dimmed()
+        );
+    }
+
+    // 2. Targets meta.json says are hidden but whose storage entry is gone
+    if !missing.is_empty() {
+        say!(
+            "\n{}",
+            "Recorded as hidden but missing from storage:".red().bold()
+        );
+        for name in &missing {
+            say!("  {name}");
+        }
+        say!(
+            "{}",
+            "  Tip: restore .cloak/storage from git, or `cloak prune` the leftovers.".dimmed()
+        );
+    }
+
+    // 3. Detect orphaned symlinks: into .cloak/storage/ at nothing, or into another storage
+    let orphans = find_orphaned_links(root, &storage);
+
+    if !orphans.is_empty() {
+        say!(
+            "\n{}",
+            "Orphaned symlinks (no storage entry of this project):"
+                .red()
+                .bold()
+        );
+        for orphan in &orphans {
+            let state = match &orphan.wrong_target {
+                Some(to) => format!("wrong target: {}", to.display()),
+                None => "broken".to_string(),
+            };
+            say!("  {} [{}]", orphan.name.to_string_lossy(), state.red());
+        }
+        say!(
+            "{}",
+            "  Tip: remove these with `rm <name>` or re-hide the original files.".dimmed()
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_list(root: &Path, stale: bool) -> Result<()> {
+    if !core::mover::storage_dir(root).exists() {
+        say!(
+            "{}",
+            "Cloak is not initialized in this directory. Run `cloak init` first.".yellow()
+        );
+        return Ok(());
+    }
+
+    let names = core::storage::entry_names(root)?;
+    if !stale {
+        for name in &names {
+            println!("{name}");
+        }
+        return Ok(());
+    }
+
+    let project = config::project::ProjectConfig::load(root)?;
+    let stale: Vec<(String, &str)> = names
+        .into_iter()
+        .filter_map(|name| {
+            let command = config::tool_cmd::command_for(&name, &project)?;
+            (!config::tool_cmd::is_installed(command)).then_some((name, command))
+        })
+        .collect();
+
+    if stale.is_empty() {
+        say!("{}", "No stale targets found.".dimmed());
+        return Ok(());
+    }
+
+    say!("{}", "Stale targets (tool not found on PATH):".bold());
+    for (name, command) in &stale {
+        say!(
+            "  {} {}",
+            name.yellow(),
+            format!("(`{command}` not found)").dimmed()
+        );
+    }
+    say!(
+        "{}",
+        "  Tip: if the tool is gone for good, `cloak unhide` the target and delete it.".dimmed()
+    );
+    Ok(())
+}
+
+/// `status --format json`: every recorded target in storage, then storage entries
+/// without a record, then every recorded entry missing from storage, then every orphaned link, as one array of `status_item` objects.
+/// An uninitialized root prints an empty array.
+fn cmd_status_json(root: &Path) -> Result<()> {
+    let storage = core::mover::storage_dir(root);
+    let mut items = Vec::new();
+    if storage.exists() {
+        let meta = core::meta::Meta::load(root)?;
+        let missing = meta.missing_from_storage(root);
+        // Recorded targets first, then storage entries meta.json does not know.
+        let stored = core::storage::entry_names(root)?;
+        let names = meta
+            .targets()
+            .filter(|target| !missing.contains(target))
+            .map(str::to_string)
+            .chain(stored.into_iter().filter(|name| meta.get(name).is_none()));
+        for name in names {
+            let link = meta.link_name(&name);
+            let link_status = if core::storage::read_error(root, &name).is_some() {
+                "unreadable"
+            } else {
+                match core::linker::link_health_as(root, link, &name) {
+                    Some(LinkHealth::Linked) => "linked",
+                    Some(LinkHealth::Broken) => "broken",
+                    Some(LinkHealth::Misdirected(_)) => "misdirected",
+                    None => "missing",
+                }
+            };
+            let recorded = meta.get(&name).is_some();
+            items.push(status_item(root, &name, link, link_status, true, recorded));
+        }
+        for name in &missing {
+            let link = meta.link_name(name);
+            let link_status = match core::linker::link_health_as(root, link, name) {
+                Some(LinkHealth::Misdirected(_)) => "misdirected",
+                Some(_) => "broken",
+                None => "missing",
+            };
+            items.push(status_item(root, name, link, link_status, false, true));
+        }
+        for orphan in find_orphaned_links(root, &storage) {
+            let name = orphan.name.to_string_lossy();
+            if missing.contains(&name.as_ref()) {
+                continue;
+            }
+            let link_status = if orphan.wrong_target.is_some() {
+                "misdirected"
+            } else {
+                "broken"
+            };
+            items.push(status_item(root, &name, &name, link_status, false, false));
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&items)?);
+    Ok(())
+}
+
+/// One object of `status --format json`. Every key is always present, so scripts
+/// can rely on the schema: `broken` is set for any link not `linked`, `orphaned`
+/// for a name without a storage entry.
+fn status_item(
+    root: &Path,
+    name: &str,
+    link: &str,
+    link_status: &str,
+    storage_exists: bool,
+    recorded: bool,
+) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "link": link,
+        "link_path": root.join(link),
+        "link_status": link_status,
+        "link_target": core::linker::resolve_link(&root.join(link)),
+        "storage_path": core::mover::storage_dir(root).join(name),
+        "storage_exists": storage_exists,
+        "recorded": recorded,
+        "broken": link_status != "linked",
+        "orphaned": !storage_exists,
+    })
+}
+
+/// One `status` line: the target, its bracketed link state, and the link's root
+/// name when `hide --link-name` chose a different one.
+fn status_line(root: &Path, meta: &core::meta::Meta, name: &str) -> String {
+    let link = meta.link_name(name);
+    let state = if let Some(err) = core::storage::read_error(root, name) {
+        format!("unreadable: {err}").red()
+    } else {
+        match core::linker::link_health_as(root, link, name) {
+            Some(LinkHealth::Linked) => "linked".green(),
+            Some(LinkHealth::Broken) => "link broken".red(),
+            Some(LinkHealth::Misdirected(to)) => format!("wrong target: {}", to.display()).red(),
+            None => "link missing".red(),
+        }
+    };
+    let mut line = if link == name {
+        format!("{name} [{state}]")
+    } else {
+        format!("{name} [{state}] via {link}")
+    };
+    if let Some(label) = meta.get(name).and_then(|e| e.label.as_deref()) {
+        line.push_str(&format!(" {}", format!("— {label}").dimmed()));
+    }
+    line
+}
+
+/// Print storage nodes with `tree`-style connectors.
+fn print_storage_tree(nodes: &[core::storage::Node], prefix: &str) {
+    for (i, node) in nodes.iter().enumerate() {
+        let last = i + 1 == nodes.len();
+        let connector = if last { "└── " } else { "├── " };
+
+        match node.state {
+            Some(state) => {
+                let label = match state {
+                    core::storage::LinkState::Linked => state.label().green(),
+                    core::storage::LinkState::Missing => state.label().red(),
+                };
+                say!("{prefix}{connector}{} [{}]", node.name, label);
+            }
+            None => say!("{prefix}{connector}{}", node.name.bold()),
+        }
+
+        let child_prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+        print_storage_tree(&node.children, &child_prefix);
+    }
+}
+
+/// A root symlink meant as a ghost link that no storage entry accounts for.
+struct Orphan {
+    name: std::ffi::OsString,
+    /// Where the link leads when its `.cloak/storage` text resolves outside this
+    /// project's storage (e.g. an old storage location); `None` when it points
+    /// into this storage at an entry that is gone.
+    wrong_target: Option<PathBuf>,
+}
+
+/// Find symlinks in root that point into .cloak/storage/ but whose targets no
+/// longer exist, and ghost links of no entry that point at another storage.
+fn find_orphaned_links(root: &Path, storage: &Path) -> Vec<Orphan> {
+    let storage_prefix = storage.canonicalize().unwrap_or(storage.to_path_buf());
+    // A misdirected entry link is reported with its entry, not here.
+    let meta = core::meta::Meta::load(root).unwrap_or_default();
+    let entry_links: Vec<String> = core::storage::entry_names(root)
+        .unwrap_or_default()
+        .iter()
+        .map(|name| meta.link_name(name).to_string())
+        .collect();
+
+    let mut orphans = Vec::new();
+    let Ok(dir) = std::fs::read_dir(root) else {
+        return orphans;
+    };
+    for entry in dir.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            continue;
+        }
+        let Ok(text) = std::fs::read_link(&path) else {
+            continue;
+        };
+        // Relative link text is relative to the link's own directory.
+        let target = root.join(&text);
+        let wrong_target = if target.starts_with(&storage_prefix) || target.starts_with(storage) {
+            if target.exists() {
+                continue;
+            }
+            None
+        } else if core::linker::mentions_storage(&text)
+            && !entry_links.contains(&entry.file_name().to_string_lossy().into_owned())
+        {
+            Some(core::linker::resolve_link(&path).unwrap_or(target))
+        } else {
+            continue;
+        };
+        orphans.push(Orphan {
+            name: entry.file_name(),
+            wrong_target,
+        });
+    }
+    orphans
+}
+
+/// Why `tidy` leaves out a config the user's own `.gitignore` rules already cover.
+const USER_IGNORED: &str = "already listed in .gitignore";
+
+/// Why `tidy` leaves out a config named by `--except` or `tidy_exclude`.
+const EXCLUDED: &str = "excluded";
+
+/// What `tidy` decided about one known config name, and why.
+struct TidyScan {
+    name: String,
+    offer: bool,
+    reason: &'static str,
+}
+
+/// Check every name in `KNOWN_DOTFILES`, then the project's own `known_dotfiles`
+/// from `.cloak/cloak.toml`, against the root and storage. Names in `except` or
+/// the config's `tidy_exclude` are never offered.
+fn scan_known_dotfiles(
+    root: &Path,
+    respect_gitignore: bool,
+    except: &[String],
+) -> Result<Vec<TidyScan>> {
+    let storage = root.join(".cloak").join("storage");
+    let project = config::project::ProjectConfig::load(root)?;
+    let excluded = |name: &str| {
+        except
+            .iter()
+            .chain(&project.tidy_exclude)
+            .any(|e| e.trim_end_matches('/') == name)
+    };
+    let mut names: Vec<String> = KNOWN_DOTFILES
+        .iter()
+        .flat_map(|(_, names)| names.iter().map(|name| name.to_string()))
+        .collect();
+    for name in project.known_dotfiles {
+        validate_target(&name).context("invalid `known_dotfiles` entry in .cloak/cloak.toml")?;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let mut scanned = Vec::new();
+    for name in names {
+        let path = root.join(&name);
+        let (offer, reason) = if storage.join(&name).exists() {
+            (false, "already hidden")
+        } else if !path.exists() {
+            (false, "not present")
+        } else if path
+            .symlink_metadata()
+            .is_ok_and(|m| m.file_type().is_symlink())
+        {
+            (false, "is a symlink")
+        } else if excluded(&name) {
+            (false, EXCLUDED)
+        } else if respect_gitignore && utils::git::is_user_ignored(root, &name)? {
+            // A plain rule of the user's own suggests they manage it deliberately.
+            (false, USER_IGNORED)
+        } else if path.is_dir() {
+            (true, "real dir at root")
+        } else {
+            (true, "real file at root")
+        };
+        scanned.push(TidyScan {
+            name,
+            offer,
+            reason,
+        });
+    }
+    Ok(scanned)
+}
+
+fn cmd_tidy(
+    root: &Path,
+    skip_confirm: bool,
+    respect_gitignore: bool,
+    explain: bool,
+    except: &[String],
+    opts: &HideOptions,
+) -> Result<()> {
+    let scanned = scan_known_dotfiles(root, respect_gitignore, except)?;
+    if explain {
+        for scan in &scanned {
+            let verb = if scan.offer {
+                "offer".green()
+            } else {
+                "skip".dimmed()
+            };
+            say!("{verb} {} ({})", scan.name, scan.reason);
+        }
+        return Ok(());
+    }
+
+    let mut opts = opts.clone();
+    opts.pipeline.no_gitignore = skips_gitignore(root, opts.pipeline.no_gitignore, opts.git)?;
+    opts.pipeline.local_ignore = uses_local_ignore(root, opts.pipeline.local_ignore)?;
+    let opts = &opts;
+    if opts.batch.dry_run {
+        preview_init(root, !opts.pipeline.no_gitignore);
+    } else {
+        ensure_initialized(root, !opts.pipeline.no_gitignore)?;
+    }
+
+    let mut discovered: Vec<&str> = Vec::new();
+    let mut excluded = Vec::new();
+    for scan in &scanned {
+        if scan.offer {
+            discovered.push(&scan.name);
+        } else if scan.reason == USER_IGNORED || scan.reason == EXCLUDED {
+            say!(
+                "{}",
+                format!("Skipping {} ({})", scan.name, scan.reason).dimmed()
+            );
+            if scan.reason == EXCLUDED {
+                excluded.push(scan.name.clone());
+            }
+        }
+    }
+
+    if discovered.is_empty() {
+        say!("{}", "No known dotfiles/configs found to hide.".dimmed());
+        return Ok(());
+    }
+
+    say!("{}", "Discovered configs:".bold());
+    let interactive = !skip_confirm && !opts.batch.dry_run && io::stdin().is_terminal();
+    if interactive {
+        let Some(picked) =
+            utils::prompt::multi_select(&discovered, io::stdin().lock(), io::stdout())?
+        else {
+            say!("{}", "Aborted.".dimmed());
+            return Ok(());
+        };
+        for (i, name) in discovered.iter().enumerate() {
+            if !picked.contains(&i) {
+                say!("{}", format!("Leaving {name} visible").dimmed());
+            }
+        }
+        discovered = picked.into_iter().map(|i| discovered[i]).collect();
+        if discovered.is_empty() {
+            say!("{}", "Nothing selected.".dimmed());
+            return Ok(());
+        }
+    } else {
+        for name in &discovered {
+            say!("  {}", name.yellow());
+        }
+    }
+
+    if !skip_confirm && !opts.batch.dry_run && !interactive {
+        print!("\nHide all {} items? [y/N] ", discovered.len());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let input = input.trim().to_lowercase();
+        if input != "y" && input != "yes" {
+            say!("{}", "Aborted.".dimmed());
+            return Ok(());
+        }
+    }
+
+    say!();
+    let targets: Vec<String> = discovered.iter().map(|s| s.to_string()).collect();
+    hide_targets(root, &targets, excluded, opts)?;
+
+    if opts.batch.dry_run {
+        say!("{}", "Dry run: nothing was changed.".dimmed());
+    } else if !opts.batch.summary {
+        say!(
+            "{}",
+            format!("Done. {} configs hidden.", targets.len()).green()
+        );
+    }
+    Ok(())
+}
+
+fn cmd_snapshots(root: &Path, target: &str) -> Result<()> {
+    let target = &resolve_target(root, target)?;
+    validate_target(target)?;
+
+    let snapshots = core::snapshot::list(root, target)?;
+    if snapshots.is_empty() {
+        say!("{}", format!("No snapshots of {target}.").dimmed());
+        return Ok(());
+    }
+
+    say!("{}", format!("Snapshots of {target}:").bold());
+    for snap in &snapshots {
+        say!(
+            "  {}  {}",
+            snap.timestamp.to_string().yellow(),
+            snap.path.display().to_string().dimmed()
+        );
+    }
+    Ok(())
+}
+
+fn cmd_rollback(root: &Path, target: &str, timestamp: u64) -> Result<()> {
+    let target = &resolve_target(root, target)?;
+    validate_target(target)?;
+
+    say!(
+        "{} {} to snapshot {}",
+        "Rolling back".bold(),
+        target.yellow(),
+        timestamp
+    );
+    core::snapshot::rollback(root, target, timestamp)?;
+    say!("  {} {}", "✓".green(), target);
+    Ok(())
+}
+
+fn cmd_info(root: &Path, target: &str, json: bool) -> Result<()> {
+    let target = resolve_target(root, target)?;
+    validate_target(&target)?;
+
+    // A flattened target may be named by its root path or by its storage key.
+    let meta = core::meta::Meta::load(root)?;
+    let key = meta.storage_key(&target);
+    let storage_path = core::mover::storage_dir(root).join(key);
+    let entry = meta.get(key);
+    let link = meta.link_name(key);
+    let link_path = root.join(link);
+
+    if storage_path.symlink_metadata().is_err() && entry.is_none() {
+        bail!("{target} is not hidden by cloak");
+    }
+
+    let link_target = std::fs::read_link(&link_path).ok();
+    let link_state = match core::linker::link_health_as(root, link, key) {
+        Some(LinkHealth::Linked) => "linked",
+        Some(LinkHealth::Broken) => "broken",
+        Some(LinkHealth::Misdirected(_)) => "misdirected",
+        None if link_path.symlink_metadata().is_ok() => "conflict (not a symlink)",
+        None => "missing",
+    };
+
+    let size = core::mover::size_of(&storage_path);
+    let hidden_at = entry.and_then(|e| e.hidden_at);
+    let link_style = entry.and_then(|e| e.link_style);
+    let chmod = entry.and_then(|e| e.chmod);
+    let original_mode = entry.and_then(|e| e.mode);
+    let immutable = entry.is_some_and(|e| e.immutable);
+    let label = entry.and_then(|e| e.label.as_deref());
+    let gitignored = utils::git::has_ignore_entry(root, link)?;
+    let ide_excludes = config::ide::exclude_status(root, link)?;
+
+    if json {
+        let excludes: serde_json::Map<String, serde_json::Value> = ide_excludes
+            .iter()
+            .map(|(dir, on)| (dir.to_string(), serde_json::Value::Bool(*on)))
+            .collect();
+        let info = serde_json::json!({
+            "target": target,
+            "storage_path": storage_path,
+            "storage_exists": storage_path.exists(),
+            "link_path": link_path,
+            "link_state": link_state,
+            "link_target": link_target,
+            "link_style": link_style.map(LinkStyle::as_str),
+            "size_bytes": size,
+            "hidden_at": hidden_at,
+            "chmod": chmod.map(|mode| format!("{mode:04o}")),
+            "original_mode": original_mode.map(|mode| format!("{mode:04o}")),
+            "immutable": immutable,
+            "label": label,
+            "gitignore_entry": gitignored,
+            "ide_excludes": excludes,
+        });
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    let yes_no = |on: bool| if on { "yes".green() } else { "no".red() };
+    let state = if link_state == "linked" {
+        link_state.green()
+    } else {
+        link_state.red()
+    };
+
+    say!("{}", target.bold());
+    if let Some(label) = label {
+        say!("  {:<16}{label}", "label");
+    }
+    say!("  {:<16}{}", "storage path", storage_path.display());
+    say!("  {:<16}{}", "link path", link_path.display());
+    say!("  {:<16}{}", "link state", state);
+    say!(
+        "  {:<16}{}",
+        "link target",
+        link_target.map_or_else(|| "-".to_string(), |t| t.display().to_string())
+    );
+    say!(
+        "  {:<16}{}",
+        "link style",
+        link_style.map_or("-", LinkStyle::as_str)
+    );
+    say!("  {:<16}{} bytes", "size", size);
+    say!(
+        "  {:<16}{}",
+        "hidden at",
+        hidden_at.map_or_else(|| "-".to_string(), utils::time::format_utc)
+    );
+    if let Some(mode) = original_mode {
+        say!("  {:<16}{mode:04o}", "original mode");
+    }
+    if let Some(mode) = chmod {
+        say!("  {:<16}{mode:04o}", "chmod");
+    }
+    if immutable {
+        say!("  {:<16}yes", "immutable");
+    }
+    say!("  {:<16}{}", "gitignore", yes_no(gitignored));
+    for (dir, on) in &ide_excludes {
+        say!("  {:<16}{}", format!("{dir} exclude"), yes_no(*on));
+    }
+    Ok(())
+}
+
+fn cmd_label(root: &Path, target: &str, text: Option<String>) -> Result<()> {
+    let target = resolve_target(root, target)?;
+    validate_target(&target)?;
+
+    let mut meta = core::meta::Meta::load(root)?;
+    let target = meta.storage_key(&target).to_string();
+    let stored = core::mover::storage_dir(root).join(&target);
+    if stored.symlink_metadata().is_err() && meta.get(&target).is_none() {
+        bail!(CloakError::Invalid(format!(
+            "{target} is not hidden by cloak"
+        )));
+    }
+
+    match &text {
+        Some(text) => say!("{} {} — {text}", "Labeled".bold(), target.yellow()),
+        None => say!("{} {}", "Cleared label of".bold(), target.yellow()),
+    }
+    meta.entry_mut(&target).label = text;
+    meta.save(root)
+}
+
+fn cmd_doctor(root: &Path, fix: bool) -> Result<()> {
+    use core::doctor::Severity;
+    use core::repair::Action;
+
+    let mut fixed = 0;
+    if fix && core::mover::storage_dir(root).is_dir() {
+        // Only what can be done without touching real files: links, ignore rules
+        // and IDE excludes, never data.
+        for item in core::repair::repair(root, false)? {
+            let detail = match item.action {
+                Action::Created => "recreated the missing link".to_string(),
+                Action::Recreated(reason) => format!("re-anchored a {reason}"),
+                Action::Untouched | Action::Failed(_) => continue,
+            };
+            fixed += 1;
+            say!(
+                "  [{}] {} — {detail} to .cloak/storage/{}",
+                "fixed".green().bold(),
+                item.link,
+                item.target
+            );
+        }
+        for item in core::doctor::fix(root)? {
+            fixed += 1;
+            say!(
+                "  [{}] {} — {}",
+                "fixed".green().bold(),
+                item.subject,
+                item.detail
+            );
+        }
+    }
+
+    let findings = core::doctor::diagnose(root)?;
+    if findings.is_empty() {
+        if fixed > 0 {
+            say!("{}", format!("Fixed {fixed} problem(s).").green());
+        } else {
+            say!("{}", "No problems found.".green());
+        }
+        return Ok(());
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    if fix && errors > 0 {
+        say!("{}", "Still broken:".red().bold());
+    }
+    let mut current = None;
+    for finding in &findings {
+        if current != Some(finding.category) {
+            current = Some(finding.category);
+            say!("{}", format!("{}:", finding.category.title()).bold());
+        }
+        let marker = match finding.severity {
+            Severity::Error => "error".red().bold(),
+            Severity::Warning => "warning".yellow().bold(),
+        };
+        say!("  [{}] {} — {}", marker, finding.subject, finding.detail);
+    }
+
+    if errors == 0 {
+        say!(
+            "{}",
+            format!("No errors; {} warning(s).", findings.len()).yellow()
+        );
+        return Ok(());
+    }
+    if fix {
+        bail!("{errors} problem(s) could not be fixed safely; resolve them by hand")
+    }
+    bail!("doctor found {errors} problem(s)")
+}
+
+/// `repair`, and with `edits` (for `relink`) also each linked target's IDE
+/// excludes and ignore rule.
+fn cmd_repair(
+    root: &Path,
+    normalize: bool,
+    edits: Option<&core::pipeline::HideOptions>,
+) -> Result<()> {
+    use core::repair::Action;
+
+    if !core::mover::storage_dir(root).is_dir() {
+        bail!(CloakError::NotInitialized(format!(
+            "cloak is not initialized in {} (.cloak/storage is missing)",
+            root.display()
+        )));
+    }
+
+    let repaired = core::repair::repair(root, normalize)?;
+    if repaired.is_empty() {
+        say!("{}", "Nothing hidden; no links to repair.".dimmed());
+        return Ok(());
+    }
+
+    let mut meta = core::meta::Meta::load(root)?;
+    let mut recorded = false;
+    let mut failed = 0;
+    for item in &repaired {
+        let name = if item.link == item.target {
+            item.target.clone()
+        } else {
+            format!("{} (via {})", item.target, item.link)
+        };
+        match &item.action {
+            Action::Untouched => say!("  {} {} {}", "-".dimmed(), name, "untouched".dimmed()),
+            Action::Created => say!("  {} {} created", "✓".green(), name),
+            Action::Recreated(reason) => {
+                say!("  {} {} recreated ({reason})", "✓".green(), name)
+            }
+            Action::Failed(e) => {
+                failed += 1;
+                say!("  {} {} failed: {e}", "✗".red(), name);
+                continue;
+            }
+        }
+
+        let Some(opts) = edits else {
+            continue;
+        };
+        let (mut made, mut notes) = (Vec::new(), Vec::new());
+        match apply_shared_edits(root, &item.target, &item.link, opts, &mut made, &mut notes) {
+            Ok(()) => {
+                for edit in made {
+                    match edit {
+                        SharedEdit::IdeExclude => say!("    {}", "+ IDE excludes".dimmed()),
+                        SharedEdit::Gitignore => {
+                            say!(
+                                "    {}",
+                                format!("+ ignore rule for /{}", item.link).dimmed()
+                            )
+                        }
+                        SharedEdit::Env => {}
+                    }
+                }
+            }
+            Err(e) => {
+                failed += 1;
+                say!("    {} {e:#}", "✗".red());
+                continue;
+            }
+        }
+        // meta.json is local state, so a fresh clone has no record of what it hid.
+        if meta.get(&item.target).is_none() {
+            let entry = meta.entry_mut(&item.target);
+            entry.link_style = Some(LinkStyle::default());
+            entry.link_kind = core::linker::link_kind(&root.join(&item.link));
+            recorded = true;
+            say!("    {}", "+ recorded in .cloak/meta.json".dimmed());
+        }
+    }
+    if recorded {
+        meta.save(root)?;
+    }
+
+    if failed > 0 {
+        bail!(
+            "{failed} of {} target(s) could not be {}",
+            repaired.len(),
+            if edits.is_some() {
+                "relinked"
+            } else {
+                "repaired"
+            }
+        );
+    }
+    say!(
+        "{}",
+        "Done. Every hidden target has a working ghost link.".green()
+    );
+    Ok(())
+}
+
+fn cmd_prune(root: &Path, restore: bool, delete: bool, yes: bool) -> Result<()> {
+    if !core::mover::storage_dir(root).is_dir() {
+        bail!(CloakError::NotInitialized(format!(
+            "cloak is not initialized in {} (.cloak/storage is missing)",
+            root.display()
+        )));
+    }
+    let orphans = core::storage::unlinked_entries(root)?;
+    if orphans.is_empty() {
+        say!("{}", "No orphaned storage entries.".dimmed());
+        return Ok(());
+    }
 
-    /// Hide specified config files/directories into .cloak/storage
-    Hide {
-        /// Config paths to hide (e.g. .cursor .vscode .idea)
-        #[arg(required = true)]
-        targets: Vec<String>,
-    },
+    say!(
+        "{}",
+        "Orphaned storage entries (no ghost link at the root):".bold()
+    );
+    for key in &orphans {
+        let size = core::mover::size_of(&core::mover::storage_dir(root).join(key));
+        say!(
+            "  {} {}",
+            format!(".cloak/storage/{key}").yellow(),
+            format!("({size} bytes)").dimmed()
+        );
+    }
 
-    /// Restore hidden configs back to their original locations
-    Unhide {
-        /// Config paths to restore (e.g. .cursor .vscode)
-        #[arg(required = true)]
-        targets: Vec<String>,
-    },
+    let verb = match (restore, delete) {
+        (true, _) => "restore",
+        (_, true) => "delete",
+        _ => {
+            say!(
+                "{}",
+                "Pass --restore to move them back to the root, or --delete to remove them."
+                    .dimmed()
+            );
+            return Ok(());
+        }
+    };
 
-    /// Show current cloak status and managed items
-    Status,
+    if !yes {
+        let mut title = verb.to_string();
+        title[..1].make_ascii_uppercase();
+        print!("\n{title} {} entries? [y/N] ", orphans.len());
+        io::stdout().flush()?;
 
-    /// Auto-scan project root for common dotfiles and hide them all
-    Tidy {
-        /// Skip confirmation prompt
-        #[arg(short, long)]
-        yes: bool,
-    },
-}
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let input = input.trim().to_lowercase();
+        if input != "y" && input != "yes" {
+            say!("{}", "Aborted.".dimmed());
+            return Ok(());
+        }
+    }
 
-/// Known vibe coding tool config directories to auto-detect with `tidy`.
-const KNOWN_DOTFILES: &[&str] = &[
-    // AI IDEs / Editors
-    ".cursor",
-    ".vscode",
-    ".windsurf",
-    ".trae",
-    ".zed",
-    // JetBrains
-    ".idea",
-    ".junie",
-    // AI coding agents
-    ".claude",
-    ".codex",
-    ".gemini",
-    ".amazonq",
-    ".augment",
-    ".bolt",
-    ".tabnine",
-    // China AI coding tools (中国大模型代码工具)
-    ".codebuddy",
-    ".lingma",
-    ".comate",
-    ".kimi",
-    // VS Code AI extensions
-    ".cline",
-    ".roo",
-    ".kilocode",
-];
+    let opts = core::pipeline::UnhideOptions {
+        no_gitignore: skips_gitignore(root, false, false)?,
+        ..Default::default()
+    };
+    let mut summary = BatchSummary::default();
+    say!();
+    for key in orphans {
+        let result = if restore {
+            unhide_target(root, &key, &opts).map(|_| ())
+        } else {
+            delete_orphan(root, &key, opts.no_gitignore)
+        };
+        match result {
+            Ok(()) => {
+                say!("  {} {key}", "✓".green());
+                summary.succeeded.push(key);
+            }
+            Err(e) => {
+                say!("  {} {key}", "✗".red());
+                summary.failed.push((key, e));
+            }
+        }
+    }
+    summary.into_result(verb)
+}
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let root = cli
-        .root
-        .unwrap_or_else(|| std::env::current_dir().expect("failed to get current directory"));
+/// Drop storage entry `key` and everything cloak recorded for it: IDE excludes,
+/// its `.gitignore` rule, its `.cloak/env` export and its meta record.
+fn delete_orphan(root: &Path, key: &str, no_gitignore: bool) -> Result<()> {
+    let mut meta = core::meta::Meta::load(root)?;
+    let link = meta.link_name(key).to_string();
+    #[cfg(unix)]
+    if meta.get(key).is_some_and(|e| e.immutable) {
+        core::hider::set_immutable(&core::mover::storage_dir(root).join(key), false)
+            .context("cannot clear the immutable flag set by --immutable")?;
+    }
 
-    match cli.command {
-        Commands::Init => cmd_init(&root),
-        Commands::Hide { targets } => cmd_hide(&root, &targets),
-        Commands::Unhide { targets } => cmd_unhide(&root, &targets),
-        Commands::Status => cmd_status(&root),
-        Commands::Tidy { yes } => cmd_tidy(&root, yes),
+    config::ide::remove_ide_exclude(root, &link)?;
+    if !no_gitignore {
+        utils::git::remove_ignore_entry(root, &link)?;
     }
+    config::tool_env::forget(root, key)?;
+    core::mover::discard(root, key)?;
+    if meta.remove(key).is_some() {
+        meta.save(root)?;
+    }
+    Ok(())
 }
 
-/// Validate a target name before hiding.
-fn validate_target(target: &str) -> Result<()> {
-    if target.is_empty() {
-        bail!("target name cannot be empty");
+/// Entries whose storage cannot be read are left out with a warning; entries whose
+/// ghost link is broken or missing are still exported, with a warning.
+fn cmd_export(root: &Path, file: &Path) -> Result<()> {
+    if !core::mover::storage_dir(root).is_dir() {
+        bail!(CloakError::NotInitialized(format!(
+            "cloak is not initialized in {} (.cloak/storage is missing)",
+            root.display()
+        )));
     }
 
-    if target.starts_with('/') || target.starts_with('\\') {
-        bail!("absolute paths are not allowed: {target}");
+    let meta = core::meta::Meta::load(root)?;
+    let mut names = Vec::new();
+    for name in core::storage::entry_names(root)? {
+        if let Some(e) = core::storage::read_error(root, &name) {
+            eprintln!(
+                "{} skipping {name}: its storage entry cannot be read ({e})",
+                "Warning:".yellow().bold()
+            );
+            continue;
+        }
+        let link = meta.link_name(&name);
+        let problem = match core::linker::link_health_as(root, link, &name) {
+            Some(LinkHealth::Linked) => None,
+            Some(LinkHealth::Broken) => Some("is broken"),
+            Some(LinkHealth::Misdirected(_)) => Some("resolves outside storage"),
+            None => Some("is missing"),
+        };
+        if let Some(problem) = problem {
+            eprintln!(
+                "{} the ghost link {link} {problem}; exporting {name} from storage anyway",
+                "Warning:".yellow().bold()
+            );
+        }
+        names.push(name);
     }
 
-    // Reject Windows-style absolute paths like C:\foo
-    if target.len() >= 2 && target.as_bytes()[1] == b':' {
-        bail!("absolute paths are not allowed: {target}");
+    if names.is_empty() {
+        say!("{}", "No hidden configs to export.".dimmed());
+        return Ok(());
     }
+    core::bundle::export(root, &names, file)?;
+    for name in &names {
+        say!("  {} {name}", "✓".green());
+    }
+    say!(
+        "{} {} entries to {}",
+        "Exported".bold(),
+        names.len(),
+        file.display()
+    );
+    Ok(())
+}
 
-    if target == ".."
-        || target.contains("/../")
-        || target.starts_with("../")
-        || target.ends_with("/..")
-    {
-        bail!("path traversal is not allowed: {target}");
+/// Run the post-ingest half of `hide` for every entry of a bundle: move it into
+/// storage, link and OS-hide it, then add its IDE excludes and `.gitignore` rule.
+/// Entries whose root path or storage name is taken are skipped.
+fn cmd_import(root: &Path, file: &Path, no_gitignore: bool, git: bool) -> Result<()> {
+    let opts = core::pipeline::HideOptions {
+        no_gitignore: skips_gitignore(root, no_gitignore, git)?,
+        local_ignore: uses_local_ignore(root, false)?,
+        ..Default::default()
+    };
+    ensure_initialized(root, !opts.no_gitignore)?;
+
+    let unpacked = core::bundle::unpack(root, file)?;
+    let mut meta = core::meta::Meta::load(root)?;
+    let mut summary = BatchSummary::default();
+
+    for entry in &unpacked.entries {
+        let core::bundle::Bundled { name, link } = entry;
+        say!("{} {}", "Importing".bold(), name.yellow());
+        if root.join(link).symlink_metadata().is_ok() {
+            say!("  {} {link} (already exists at the root)", "-".dimmed());
+            summary.skipped.push(name.clone());
+            continue;
+        }
+        if core::mover::storage_dir(root)
+            .join(name)
+            .symlink_metadata()
+            .is_ok()
+        {
+            say!("  {} {name} (already in storage)", "-".dimmed());
+            summary.skipped.push(name.clone());
+            continue;
+        }
+
+        match import_entry(root, &unpacked.dir, name, link, &opts) {
+            Ok(notes) => {
+                let record = meta.entry_mut(name);
+                record.link_style = Some(opts.link_style);
+                record.hidden_at = Some(utils::time::unix_now());
+                record.link_name = (link != name).then(|| link.clone());
+                record.link_kind = core::linker::link_kind(&root.join(link));
+                for note in notes {
+                    say!("  {}", note.dimmed());
+                }
+                say!("  {} {name}", "✓".green());
+                summary.succeeded.push(name.clone());
+            }
+            Err(e) => {
+                say!("  {} {name}", "✗".red());
+                summary.failed.push((name.clone(), e));
+            }
+        }
+    }
+    meta.save(root)?;
+
+    if summary.succeeded.is_empty() && summary.failed.is_empty() {
+        say!("{}", "Nothing to import.".dimmed());
     }
+    summary.into_result("import")
+}
+
+/// Import one unpacked entry; on failure, everything it did is undone and the
+/// entry is dropped from storage.
+fn import_entry(
+    root: &Path,
+    unpacked: &Path,
+    name: &str,
+    link: &str,
+    opts: &core::pipeline::HideOptions,
+) -> Result<Vec<String>> {
+    validate_target(name)?;
+    validate_target(link)?;
+    core::mover::adopt(root, &unpacked.join(name), name)?;
+
+    let mut edits = Vec::new();
+    let mut notes = Vec::new();
+    let imported = (|| -> Result<()> {
+        core::linker::create_ghost_link_as(root, link, name, opts.link_style)?;
+        core::hider::hide_path(root, link)?;
+        if link != name {
+            notes.push(format!("+ linked as {link}"));
+        }
+        apply_shared_edits(root, name, link, opts, &mut edits, &mut notes)
+    })();
+    let Err(err) = imported else {
+        return Ok(notes);
+    };
+
+    let undone = edits
+        .iter()
+        .rev()
+        .try_for_each(|edit| match edit {
+            SharedEdit::IdeExclude => config::ide::remove_ide_exclude(root, link),
+            SharedEdit::Gitignore => utils::git::remove_ignore_entry(root, link),
+            SharedEdit::Env => config::tool_env::forget(root, name),
+        })
+        .and_then(|()| {
+            if core::linker::link_health_as(root, link, name).is_some() {
+                core::linker::remove_ghost_link(root, link)?;
+            }
+            core::mover::discard(root, name)
+        });
+    Err(match undone {
+        Ok(()) => err.context(format!("import of {name} rolled back")),
+        Err(undo) => err.context(format!(
+            "import of {name} could not be rolled back ({undo:#})"
+        )),
+    })
+}
 
-    if target == ".cloak" || target.starts_with(".cloak/") || target.starts_with(".cloak\\") {
-        bail!("cannot hide the .cloak directory itself");
+fn cmd_gc(root: &Path, dry_run: bool) -> Result<()> {
+    let project = config::project::ProjectConfig::load(root)?;
+    let reclaimed = core::gc::collect(root, &project, dry_run)?;
+
+    if reclaimed.is_empty() {
+        say!("{}", "Nothing to clean up.".dimmed());
+        return Ok(());
     }
 
-    if target.contains('/') || target.contains('\\') {
-        bail!("only top-level entries are allowed (no path separators): {target}");
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+    say!("{}", format!("{verb}:").bold());
+    for item in &reclaimed {
+        let display = item
+            .path
+            .strip_prefix(root)
+            .unwrap_or(&item.path)
+            .display()
+            .to_string();
+        say!(
+            "  {} {}",
+            display.yellow(),
+            format!("({})", item.reason).dimmed()
+        );
     }
 
+    let bytes: u64 = reclaimed.iter().map(|r| r.bytes).sum();
+    let summary = format!(
+        "{} {} item(s), {} bytes reclaimed.",
+        if dry_run { "Dry run:" } else { "Done." },
+        reclaimed.len(),
+        bytes
+    );
+    say!("{}", summary.green());
     Ok(())
 }
 
-/// Ensure cloak is initialized, auto-initializing if needed.
-fn ensure_initialized(root: &Path) -> Result<()> {
-    let storage = root.join(".cloak").join("storage");
-    if !storage.exists() {
-        println!("{}", "Auto-initializing cloak...".dimmed());
-        core::mover::ensure_storage_dir(root)?;
-        utils::git::ensure_gitignore_entry(root)?;
+fn cmd_env(root: &Path) -> Result<()> {
+    for name in core::storage::entry_names(root)? {
+        if let Some((var, value)) = config::tool_env::export_for(root, &name) {
+            println!("{}", config::tool_env::export_line(var, &value));
+        }
     }
     Ok(())
 }
 
-fn cmd_init(root: &Path) -> Result<()> {
-    println!("{}", "Initializing cloak...".bold());
+fn cmd_storage_verify(root: &Path) -> Result<()> {
+    if !core::mover::storage_dir(root).is_dir() {
+        bail!(CloakError::NotInitialized(format!(
+            "cloak is not initialized in {} (.cloak/storage is missing)",
+            root.display()
+        )));
+    }
 
-    core::mover::ensure_storage_dir(root)?;
-    utils::git::ensure_gitignore_entry(root)?;
+    let suspects = core::storage::verify(root)?;
+    if suspects.is_empty() {
+        say!("{}", "Storage looks complete.".green());
+        return Ok(());
+    }
 
-    println!(
+    for suspect in &suspects {
+        say!(
+            "  {} {} — {}",
+            "?".yellow().bold(),
+            suspect.target,
+            suspect.reason
+        );
+    }
+    say!(
         "{}",
-        "Cloak initialized. Use `cloak hide <target>` to start hiding configs.".green()
+        "Compare these with a backup or snapshot (`cloak snapshots <target>`) before \
+         relinking with `cloak repair` or deleting them."
+            .dimmed()
+    );
+    bail!("found {} suspicious storage item(s)", suspects.len())
+}
+
+fn cmd_storage_move(root: &Path, path: &str) -> Result<()> {
+    ensure_initialized(root, !skips_gitignore(root, false, false)?)?;
+
+    let expanded = root.join(utils::env::expand(path)?);
+    let inline = core::mover::storage_dir(root);
+    let dest = if core::linker::normalize(&expanded) == core::linker::normalize(&inline) {
+        inline
+    } else {
+        expanded
+    };
+
+    // Remember every working link (and its style) so it can be rebuilt afterwards.
+    let meta = core::meta::Meta::load(root)?;
+    let linked: Vec<(String, String, LinkStyle)> = core::storage::entry_names(root)?
+        .into_iter()
+        .filter_map(|name| {
+            let link = meta.link_name(&name).to_string();
+            if core::linker::link_health_as(root, &link, &name) != Some(LinkHealth::Linked) {
+                return None;
+            }
+            let style = meta
+                .get(&name)
+                .and_then(|e| e.link_style)
+                .unwrap_or_default();
+            Some((name, link, style))
+        })
+        .collect();
+
+    core::mover::check_relocation(root, &dest)?;
+    say!("{} storage to {}", "Moving".bold(), dest.display());
+    let relink = |links: &[(String, String, LinkStyle)]| -> Result<()> {
+        for (name, link, style) in links {
+            core::linker::create_ghost_link_as(root, link, name, *style)?;
+            core::hider::hide_path(root, link)?;
+        }
+        Ok(())
+    };
+    let mut removed = Vec::new();
+    let moved = (|| -> Result<usize> {
+        for entry in &linked {
+            core::linker::remove_ghost_link(root, &entry.1)?;
+            removed.push(entry.clone());
+        }
+        core::mover::relocate_storage(root, &dest)
+    })();
+    let moved = match moved {
+        Ok(moved) => moved,
+        Err(e) => {
+            // Put back the links taken down, wherever storage ended up.
+            return Err(match relink(&removed) {
+                Ok(()) => e,
+                Err(undo) => e.context(format!(
+                    "the ghost links could not all be recreated ({undo:#}); run `cloak repair`"
+                )),
+            });
+        }
+    };
+    let mut meta = meta;
+    let is_inline = dest == core::mover::storage_dir(root);
+    meta.set_external_storage((!is_inline).then(|| dest.clone()));
+    meta.save(root)?;
+    for (name, link, style) in &linked {
+        core::linker::create_ghost_link_as(root, link, name, *style)?;
+        core::hider::hide_path(root, link)?;
+        say!("  {} {}", "✓".green(), name);
+    }
+
+    say!(
+        "{}",
+        format!(
+            "Done. Moved {moved} entries and re-linked {} targets.",
+            linked.len()
+        )
+        .green()
     );
     Ok(())
 }
 
-fn cmd_hide(root: &Path, targets: &[String]) -> Result<()> {
-    for target in targets {
-        validate_target(target)?;
+/// Move each known tool's storage entry under its category directory, recording
+/// the new key in meta.json and relinking it at its unchanged root path. Entries
+/// already grouped are left alone, so running it again changes nothing.
+fn cmd_storage_regroup(root: &Path) -> Result<()> {
+    ensure_initialized(root, !skips_gitignore(root, false, false)?)?;
+
+    let mut meta = core::meta::Meta::load(root)?;
+    let mut moved = 0;
+    for key in core::storage::entry_names(root)? {
+        let origin = meta.origin(&key).to_string();
+        let link = meta.link_name(&key).to_string();
+        let Some(dir) = ToolCategory::of(&origin).storage_dir() else {
+            say!(
+                "  {} {key} {}",
+                "-".dimmed(),
+                "not a known tool; left in place".dimmed()
+            );
+            continue;
+        };
+        let grouped = format!("{dir}/{origin}");
+        let entry = meta.get(&key).cloned().unwrap_or_default();
+        let skip = if key == grouped {
+            continue;
+        } else if link != origin {
+            format!("has a custom link name ({link})")
+        } else if key != origin {
+            format!("is stored under a custom key ({key})")
+        } else if entry.immutable {
+            "is immutable; unhide it or hide it again without --immutable".to_string()
+        } else if core::linker::link_health_as(root, &link, &key) != Some(LinkHealth::Linked) {
+            "has no working ghost link; run `cloak doctor` first".to_string()
+        } else {
+            String::new()
+        };
+        if !skip.is_empty() {
+            say!(
+                "  {} {origin} {}",
+                "-".dimmed(),
+                format!("skipped: {skip}").dimmed()
+            );
+            continue;
+        }
+
+        let style = entry.link_style.unwrap_or_default();
+        core::linker::remove_ghost_link(root, &link)?;
+        if let Err(e) = core::mover::rekey(root, &key, &grouped) {
+            core::linker::create_ghost_link_as(root, &link, &key, style)?;
+            core::hider::hide_path(root, &link)?;
+            return Err(e.context(format!("failed to regroup {origin}")));
+        }
+        meta.remove(&key);
+        *meta.entry_mut(&grouped) = core::meta::Entry {
+            link_name: Some(link.clone()),
+            origin: Some(origin.clone()),
+            ..entry
+        };
+        meta.save(root)?;
+        core::linker::create_ghost_link_as(root, &link, &grouped, style)?;
+        core::hider::hide_path(root, &link)?;
+        moved += 1;
+        say!("  {} {origin} -> .cloak/storage/{grouped}", "✓".green());
+    }
+
+    if moved == 0 {
+        say!("{}", "Storage is already grouped.".green());
+    } else {
+        say!("{}", format!("Done. Regrouped {moved} entries.").green());
     }
+    Ok(())
+}
 
-    ensure_initialized(root)?;
+fn cmd_watch(root: &Path, debounce: u64) -> Result<()> {
+    use notify_debouncer_mini::notify::RecursiveMode;
 
-    for target in targets {
-        println!("{} {}", "Hiding".bold(), target.yellow());
+    ensure_initialized(root, !skips_gitignore(root, false, false)?)?;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut debouncer =
+        notify_debouncer_mini::new_debouncer(std::time::Duration::from_millis(debounce), tx)
+            .context("failed to start the file watcher")?;
 
-        core::mover::ingest(root, target)?;
-        core::linker::create_ghost_link(root, target)?;
-        core::hider::hide_path(root, target)?;
-        config::ide::add_ide_exclude(root, target)?;
-        utils::git::add_ignore_entry(root, target)?;
+    say!(
+        "{} {} (Ctrl-C to stop)",
+        "Watching".cyan().bold(),
+        root.display()
+    );
+
+    // Relink whatever was clobbered while nothing was watching, then again after
+    // every settled batch of changes. The watched set is rebuilt each time, since
+    // hides and unhides move links and a tool may delete and recreate a parent.
+    let mut watched = std::collections::BTreeSet::<PathBuf>::new();
+    loop {
+        relink_clobbered(root)?;
+
+        for dir in &watched {
+            let _ = debouncer.watcher().unwatch(dir);
+        }
+        watched = core::watch::watched_dirs(root)?;
+        for dir in &watched {
+            debouncer
+                .watcher()
+                .watch(dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("failed to watch {}", dir.display()))?;
+        }
 
-        println!("  {} {}", "✓".green(), target);
+        match rx.recv() {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => eprintln!("{} watcher error: {e}", "✗".red()),
+            Err(_) => bail!("the file watcher stopped unexpectedly"),
+        }
     }
+}
 
-    println!("{}", "Done. Your root directory is now pristine.".green());
+fn relink_clobbered(root: &Path) -> Result<()> {
+    for target in core::watch::clobbered(root)? {
+        let stamp = utils::time::format_utc(utils::time::unix_now());
+        match core::watch::relink(root, &target) {
+            Ok(relinked) => say!(
+                "[{stamp}] {} {} was replaced by a real path; merged {} file(s) into storage and re-linked",
+                "✓".green(),
+                relinked.link.bold(),
+                relinked.merged
+            ),
+            Err(e) => eprintln!(
+                "[{stamp}] {} {}: failed to re-link: {e:#}",
+                "✗".red(),
+                target.bold()
+            ),
+        }
+    }
     Ok(())
 }
 
-fn cmd_unhide(root: &Path, targets: &[String]) -> Result<()> {
-    for target in targets {
-        validate_target(target)?;
+/// Platform-specific capabilities compiled into this binary.
+fn build_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(unix) {
+        features.push("symlink");
+    }
+    if cfg!(windows) {
+        features.extend(["symlink", "junction", "hidden-attribute"]);
     }
+    if cfg!(target_os = "macos") {
+        features.push("chflags-hidden");
+    }
+    features
+}
 
-    for target in targets {
-        println!("{} {}", "Restoring".bold(), target.yellow());
+fn cmd_version(json: bool) -> Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+    let target = env!("CLOAK_BUILD_TARGET");
+    let commit = option_env!("CLOAK_GIT_COMMIT");
+    let features = build_features();
+
+    if json {
+        let info = serde_json::json!({
+            "version": version,
+            "target": target,
+            "commit": commit,
+            "features": features,
+        });
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
 
-        config::ide::remove_ide_exclude(root, target)?;
-        utils::git::remove_ignore_entry(root, target)?;
-        core::hider::unhide_path(root, target)?;
-        core::linker::remove_ghost_link(root, target)?;
-        core::mover::egest(root, target)?;
+    println!("cloak {version}");
+    println!("  target:   {target}");
+    println!("  commit:   {}", commit.unwrap_or("unknown"));
+    println!("  features: {}", features.join(", "));
+    Ok(())
+}
 
-        println!("  {} {}", "✓".green(), target);
-    }
+fn cmd_completions(shell: utils::completions::Shell, bin_name: Option<String>) -> Result<()> {
+    let cmd = Cli::command();
+    // Aliased or renamed binaries (e.g. `ck`) should complete under the name the user types.
+    let name = bin_name.unwrap_or_else(|| utils::completions::invoked_name(cmd.get_name()));
+    print!("{}", utils::completions::generate(&cmd, &name, shell));
+    Ok(())
+}
 
-    println!(
-        "{}",
-        "Done. Configs restored to their original locations.".green()
-    );
+/// Unlike `list`, stays silent without storage so a shell never shows a message as
+/// a candidate.
+fn cmd_complete_targets(root: &Path) -> Result<()> {
+    if !core::mover::storage_dir(root).exists() {
+        return Ok(());
+    }
+    // Offer the root paths the commands take, not storage keys (`--flatten`, `storage regroup`).
+    let meta = core::meta::Meta::load(root)?;
+    for key in core::storage::entry_names(root)? {
+        println!("{}", meta.origin(&key));
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::validate_target;
+    use super::{resolve_target, validate_target};
     use std::fs;
     use std::path::PathBuf;
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::time::{SystemTime, UNIX_EPOCH};
 
     #[cfg(unix)]
-    use super::cmd_unhide;
+    use super::{UnhideOptions, cmd_unhide};
 
     fn make_temp_dir(prefix: &str) -> PathBuf {
         static COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -246,6 +3419,39 @@ mod tests {
         assert!(validate_target("../outside").is_err());
     }
 
+    #[test]
+    fn validate_target_allows_nested_but_not_reserved_dirs() {
+        assert!(validate_target("packages/web/.env").is_ok());
+        assert!(validate_target(".snapshots/.cursor").is_err());
+        assert!(validate_target("packages/../../x").is_err());
+    }
+
+    #[test]
+    fn resolve_target_maps_in_root_absolute_path_and_rejects_outside() {
+        let base = make_temp_dir("resolve-target");
+        let root = base.join("root");
+        fs::create_dir_all(&root).expect("failed to create root");
+
+        let inside = root.join(".cursor");
+        let resolved = resolve_target(&root, &inside.to_string_lossy()).expect("resolve failed");
+        assert_eq!(resolved, ".cursor");
+
+        let outside = base.join(".cursor");
+        let err = resolve_target(&root, &outside.to_string_lossy()).unwrap_err();
+        assert!(err.to_string().contains("outside the project root"));
+
+        assert_eq!(
+            resolve_target(&root, ".vscode").expect("resolve failed"),
+            ".vscode"
+        );
+        assert_eq!(
+            resolve_target(&root, ".config/ai//").expect("resolve failed"),
+            ".config/ai"
+        );
+
+        fs::remove_dir_all(base).expect("cleanup failed");
+    }
+
     #[cfg(unix)]
     #[test]
     fn cmd_unhide_rejects_traversal_before_touching_outside_path() {
@@ -257,7 +3463,7 @@ mod tests {
         std::os::unix::fs::symlink("/tmp", &outside_link).expect("failed to create outside link");
 
         let targets = vec!["../outside-link".to_string()];
-        let result = cmd_unhide(&root, &targets);
+        let result = cmd_unhide(&root, &targets, &UnhideOptions::default());
         assert!(result.is_err());
         assert!(
             outside_link.symlink_metadata().is_ok(),
@@ -267,159 +3473,3 @@ mod tests {
         fs::remove_dir_all(base).expect("cleanup failed");
     }
 }
-
-fn cmd_status(root: &Path) -> Result<()> {
-    let storage = root.join(".cloak").join("storage");
-
-    if !storage.exists() {
-        println!(
-            "{}",
-            "Cloak is not initialized in this directory. Run `cloak init` first.".yellow()
-        );
-        return Ok(());
-    }
-
-    // 1. Show items in storage
-    let entries: Vec<_> = std::fs::read_dir(&storage)?
-        .filter_map(|e| e.ok())
-        .collect();
-
-    if entries.is_empty() && find_orphaned_links(root, &storage).is_empty() {
-        println!("{}", "No configs are currently hidden.".dimmed());
-        return Ok(());
-    }
-
-    if !entries.is_empty() {
-        println!("{}", "Hidden configs:".bold());
-        for entry in &entries {
-            let name = entry.file_name();
-            let link_path = root.join(&name);
-            let link_ok = link_path
-                .symlink_metadata()
-                .map(|m| m.file_type().is_symlink())
-                .unwrap_or(false);
-
-            let status = if link_ok {
-                "linked".green()
-            } else {
-                "link missing".red()
-            };
-
-            println!("  {} [{}]", name.to_string_lossy(), status);
-        }
-    }
-
-    // 2. Detect orphaned symlinks pointing into .cloak/storage/ whose targets are gone
-    let orphans = find_orphaned_links(root, &storage);
-
-    if !orphans.is_empty() {
-        println!(
-            "\n{}",
-            "Orphaned symlinks (storage target missing):".red().bold()
-        );
-        for name in &orphans {
-            println!("  {} [{}]", name.to_string_lossy(), "broken".red());
-        }
-        println!(
-            "{}",
-            "  Tip: remove these with `rm <name>` or re-hide the original files.".dimmed()
-        );
-    }
-
-    Ok(())
-}
-
-/// Find symlinks in root that point into .cloak/storage/ but whose targets no longer exist.
-fn find_orphaned_links(root: &Path, storage: &Path) -> Vec<std::ffi::OsString> {
-    let storage_prefix = storage.canonicalize().unwrap_or(storage.to_path_buf());
-    let mut orphans = Vec::new();
-    let Ok(dir) = std::fs::read_dir(root) else {
-        return orphans;
-    };
-    for entry in dir.filter_map(|e| e.ok()) {
-        let path = entry.path();
-        let is_orphan = path
-            .symlink_metadata()
-            .is_ok_and(|m| m.file_type().is_symlink())
-            && std::fs::read_link(&path).is_ok_and(|target| {
-                (target.starts_with(&storage_prefix) || target.starts_with(storage))
-                    && !target.exists()
-            });
-        if is_orphan {
-            orphans.push(entry.file_name());
-        }
-    }
-    orphans
-}
-
-fn cmd_tidy(root: &Path, skip_confirm: bool) -> Result<()> {
-    ensure_initialized(root)?;
-
-    let storage = root.join(".cloak").join("storage");
-
-    // Scan root for known dotfiles that exist and aren't already hidden
-    let mut discovered: Vec<&str> = Vec::new();
-    for pattern in KNOWN_DOTFILES {
-        let path = root.join(pattern);
-        let already_hidden = storage.join(pattern).exists();
-
-        // Skip if already hidden or doesn't exist at root
-        if already_hidden {
-            continue;
-        }
-
-        // Check if it exists as a real file/dir (not a symlink pointing to storage)
-        if path.exists() {
-            // If it's a symlink to our storage, skip it
-            if let Ok(meta) = path.symlink_metadata()
-                && meta.file_type().is_symlink()
-            {
-                continue;
-            }
-            discovered.push(pattern);
-        }
-    }
-
-    if discovered.is_empty() {
-        println!("{}", "No known dotfiles/configs found to hide.".dimmed());
-        return Ok(());
-    }
-
-    println!("{}", "Discovered configs:".bold());
-    for name in &discovered {
-        println!("  {}", name.yellow());
-    }
-
-    if !skip_confirm {
-        print!("\nHide all {} items? [y/N] ", discovered.len());
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let input = input.trim().to_lowercase();
-        if input != "y" && input != "yes" {
-            println!("{}", "Aborted.".dimmed());
-            return Ok(());
-        }
-    }
-
-    println!();
-    let targets: Vec<String> = discovered.iter().map(|s| s.to_string()).collect();
-    for target in &targets {
-        println!("{} {}", "Hiding".bold(), target.yellow());
-
-        core::mover::ingest(root, target)?;
-        core::linker::create_ghost_link(root, target)?;
-        core::hider::hide_path(root, target)?;
-        config::ide::add_ide_exclude(root, target)?;
-        utils::git::add_ignore_entry(root, target)?;
-
-        println!("  {} {}", "✓".green(), target);
-    }
-
-    println!(
-        "{}",
-        format!("Done. {} configs hidden.", targets.len()).green()
-    );
-    Ok(())
-}
@@ -0,0 +1,316 @@
+use crate::config::project::ProjectConfig;
+use crate::core::linker::{self, LinkHealth};
+use crate::core::meta::Meta;
+use crate::core::pipeline::{self, HideOptions, UnhideOptions};
+use crate::core::{lock, mover, storage};
+use crate::error::CloakError;
+use crate::utils::git;
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+
+/// A project root cloak manages, for callers that hide and restore configs
+/// programmatically.
+///
+/// Each operation takes the project lock, so it is safe to use alongside the CLI,
+/// and runs the same per-target pipeline as `cloak hide` and `cloak unhide`, one
+/// target at a time: `.cloak/cloak.toml` is honored, and `.gitignore` is only
+/// edited inside a git repository that has not opted out with `gitignore = false`
+/// or `manage_gitignore = false`.
+#[derive(Debug, Clone)]
+pub struct CloakProject {
+    root: PathBuf,
+}
+
+impl CloakProject {
+    /// Use the existing directory `root` as a project root. Nothing is created
+    /// until a target is hidden.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        if !root.is_dir() {
+            bail!(CloakError::Invalid(format!(
+                "project root is not a directory: {}",
+                root.display()
+            )));
+        }
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether `.cloak/storage` exists.
+    pub fn is_initialized(&self) -> bool {
+        mover::storage_dir(&self.root).is_dir()
+    }
+
+    /// Create `.cloak/storage`, its README and the `.cloak` ignore rules, as
+    /// `cloak init` does. Safe to call on an initialized project.
+    pub fn init(&self) -> Result<()> {
+        let _lock = lock::acquire(&self.root, true)?;
+        self.init_unlocked()
+    }
+
+    fn init_unlocked(&self) -> Result<()> {
+        mover::ensure_storage_dir(&self.root)?;
+        mover::ensure_readme(&self.root)?;
+        if self.manages_gitignore()? {
+            git::ensure_gitignore_entry(&self.root)?;
+        }
+        Ok(())
+    }
+
+    /// Every hidden target, by the root path it was hidden from.
+    pub fn hidden(&self) -> Result<Vec<String>> {
+        if !self.is_initialized() {
+            return Ok(Vec::new());
+        }
+        let meta = Meta::load(&self.root)?;
+        Ok(storage::entry_names(&self.root)?
+            .iter()
+            .map(|key| meta.origin(key).to_string())
+            .collect())
+    }
+
+    /// The state of `target`'s ghost link, or `None` when there is no symlink at
+    /// its root path.
+    pub fn link_health(&self, target: &str) -> Result<Option<LinkHealth>> {
+        let meta = Meta::load(&self.root)?;
+        let key = meta.storage_key(target);
+        Ok(linker::link_health_as(&self.root, meta.link_name(key), key))
+    }
+
+    /// Move `target` into storage and leave a hidden ghost link in its place,
+    /// excluded from IDE search and ignored by git. Initializes the project first
+    /// if needed. On failure the target is put back where it was.
+    pub fn hide(&self, target: &str) -> Result<()> {
+        self.hide_with(target, &HideOptions::default())
+    }
+
+    /// [`hide`](Self::hide) with the options `cloak hide` takes per target, such
+    /// as `flatten` or `link_name`. The ignore rule is still skipped wherever
+    /// `hide` would skip it.
+    pub fn hide_with(&self, target: &str, opts: &HideOptions) -> Result<()> {
+        storage::validate_target(target)?;
+        if let Some(name) = &opts.link_name {
+            storage::validate_target(name)?;
+        }
+        let _lock = lock::acquire(&self.root, true)?;
+        if !self.is_initialized() {
+            self.init_unlocked()?;
+        }
+        let root = &self.root;
+        let mut opts = opts.clone();
+        opts.no_gitignore |= !self.manages_gitignore()?;
+        opts.local_ignore |= ProjectConfig::load(root)?.local_ignore;
+
+        pipeline::ensure_outside_hidden(root, target)?;
+        if pipeline::is_already_hidden(root, target, &opts.storage_key(target))
+            && pipeline::stale_link(root, target, &opts).is_none()
+        {
+            bail!(CloakError::Invalid(format!("{target} is already hidden")));
+        }
+        let mut meta = Meta::load(root)?;
+        let staged = pipeline::stage_hide(root, target, &opts, &ProjectConfig::load(root)?)?;
+        pipeline::finish_hide(root, &mut meta, target, staged, &opts)?;
+        meta.save(root)
+    }
+
+    /// Put a hidden target back at its root path and drop its ghost link, IDE
+    /// excludes and ignore rule. A real file or directory at that path is never
+    /// overwritten.
+    pub fn unhide(&self, target: &str) -> Result<()> {
+        self.unhide_with(target, &UnhideOptions::default())
+    }
+
+    /// [`unhide`](Self::unhide) with the options `cloak unhide` takes per target;
+    /// `force` moves a real path in the way aside to `<target>.cloak-bak` first.
+    pub fn unhide_with(&self, target: &str, opts: &UnhideOptions) -> Result<()> {
+        storage::validate_target(target)?;
+        let Some(_lock) = lock::acquire(&self.root, false)? else {
+            bail!(CloakError::NotInitialized(format!(
+                "cloak is not initialized in {}",
+                self.root.display()
+            )));
+        };
+        let root = &self.root;
+        let key = Meta::load(root)?.storage_key(target).to_string();
+        if mover::storage_dir(root)
+            .join(&key)
+            .symlink_metadata()
+            .is_err()
+        {
+            bail!(CloakError::Invalid(format!(
+                "{target} is not hidden by cloak"
+            )));
+        }
+        let mut opts = opts.clone();
+        opts.no_gitignore |= !self.manages_gitignore()?;
+        pipeline::unhide_target(root, target, &opts).map(|_| ())
+    }
+
+    /// `gitignore = false` or `manage_gitignore = false` in `.cloak/cloak.toml`,
+    /// or a root outside any git repository, leaves the ignore files alone.
+    fn manages_gitignore(&self) -> Result<bool> {
+        Ok(!ProjectConfig::load(&self.root)?.skip_gitignore && git::in_repository(&self.root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn make_temp_dir(prefix: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let mut dir = std::env::temp_dir();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock before epoch")
+            .as_nanos();
+        let pid = std::process::id();
+        let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+        dir.push(format!("cloak-{prefix}-{pid}-{nanos}-{seq}"));
+        fs::create_dir_all(&dir).expect("failed to create temp test dir");
+        dir
+    }
+
+    #[test]
+    fn project_hides_and_restores_a_target_like_the_cli() {
+        let root = make_temp_dir("library");
+        fs::create_dir_all(root.join(".git")).expect("mkdir failed");
+        fs::create_dir_all(root.join(".cursor")).expect("mkdir failed");
+        fs::write(root.join(".cursor").join("rules.md"), "rule").expect("write failed");
+
+        let project = CloakProject::open(&root).expect("open failed");
+        assert!(!project.is_initialized());
+        project.hide(".cursor").expect("hide failed");
+        assert!(project.is_initialized());
+        assert_eq!(project.hidden().unwrap(), [".cursor"]);
+        assert_eq!(
+            project.link_health(".cursor").unwrap(),
+            Some(LinkHealth::Linked)
+        );
+        let gitignore = fs::read_to_string(root.join(".gitignore")).unwrap();
+        assert!(gitignore.lines().any(|l| l == "/.cursor"), "{gitignore}");
+        assert!(project.hide(".cursor").is_err());
+
+        project.unhide(".cursor").expect("unhide failed");
+        assert!(project.hidden().unwrap().is_empty());
+        assert_eq!(
+            fs::read_to_string(root.join(".cursor").join("rules.md")).unwrap(),
+            "rule"
+        );
+        let gitignore = fs::read_to_string(root.join(".gitignore")).unwrap();
+        assert!(!gitignore.lines().any(|l| l == "/.cursor"), "{gitignore}");
+        assert!(project.unhide(".cursor").is_err());
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn project_leaves_gitignore_alone_when_manage_gitignore_is_off() {
+        let root = make_temp_dir("library-manage-gitignore");
+        fs::create_dir_all(root.join(".git")).expect("mkdir failed");
+        fs::create_dir_all(root.join(".cloak")).expect("mkdir failed");
+        fs::create_dir_all(root.join(".cursor")).expect("mkdir failed");
+        fs::write(
+            root.join(".cloak").join("cloak.toml"),
+            "manage_gitignore = false\n",
+        )
+        .expect("write failed");
+        fs::write(root.join(".gitignore"), "node_modules/\n").expect("write failed");
+
+        let project = CloakProject::open(&root).expect("open failed");
+        project.hide(".cursor").expect("hide failed");
+        assert_eq!(project.hidden().unwrap(), [".cursor"]);
+        assert_eq!(
+            fs::read_to_string(root.join(".gitignore")).unwrap(),
+            "node_modules/\n"
+        );
+        project.unhide(".cursor").expect("unhide failed");
+        assert_eq!(
+            fs::read_to_string(root.join(".gitignore")).unwrap(),
+            "node_modules/\n"
+        );
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn project_hides_a_flattened_target_under_its_flat_key() {
+        let root = make_temp_dir("library-flatten");
+        fs::create_dir_all(root.join(".git")).expect("mkdir failed");
+        fs::create_dir_all(root.join("a").join("b")).expect("mkdir failed");
+        fs::write(root.join("a").join("b").join(".env"), "TOKEN=1").expect("write failed");
+
+        let project = CloakProject::open(&root).expect("open failed");
+        let opts = HideOptions {
+            flatten: true,
+            ..HideOptions::default()
+        };
+        project.hide_with("a/b/.env", &opts).expect("hide failed");
+        assert!(mover::storage_dir(&root).join("a__b__.env").is_file());
+        assert_eq!(project.hidden().unwrap(), ["a/b/.env"]);
+        assert_eq!(
+            project.link_health("a/b/.env").unwrap(),
+            Some(LinkHealth::Linked)
+        );
+
+        project.unhide("a/b/.env").expect("unhide failed");
+        assert_eq!(
+            fs::read_to_string(root.join("a").join("b").join(".env")).unwrap(),
+            "TOKEN=1"
+        );
+        assert!(project.hidden().unwrap().is_empty());
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn project_refuses_to_restore_over_a_real_path_unless_forced() {
+        let root = make_temp_dir("library-conflict");
+        fs::create_dir_all(root.join(".git")).expect("mkdir failed");
+        fs::create_dir_all(root.join(".cursor")).expect("mkdir failed");
+        fs::write(root.join(".cursor").join("rules.md"), "stored").expect("write failed");
+
+        let project = CloakProject::open(&root).expect("open failed");
+        project.hide(".cursor").expect("hide failed");
+        fs::remove_file(root.join(".cursor")).expect("unlink failed");
+        fs::create_dir_all(root.join(".cursor")).expect("mkdir failed");
+        fs::write(root.join(".cursor").join("rules.md"), "local").expect("write failed");
+
+        let err = project
+            .unhide(".cursor")
+            .expect_err("unhide overwrote a real path");
+        assert!(matches!(
+            err.downcast_ref::<CloakError>(),
+            Some(CloakError::Conflict(_))
+        ));
+        assert_eq!(project.hidden().unwrap(), [".cursor"]);
+        assert_eq!(
+            fs::read_to_string(root.join(".cursor").join("rules.md")).unwrap(),
+            "local"
+        );
+
+        let force = UnhideOptions {
+            force: true,
+            ..UnhideOptions::default()
+        };
+        project
+            .unhide_with(".cursor", &force)
+            .expect("forced unhide failed");
+        assert_eq!(
+            fs::read_to_string(root.join(".cursor").join("rules.md")).unwrap(),
+            "stored"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join(".cursor.cloak-bak").join("rules.md")).unwrap(),
+            "local"
+        );
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+}
@@ -0,0 +1,305 @@
+use clap::Command;
+
+/// Shells we can generate completion scripts for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl From<Shell> for clap_complete::Shell {
+    fn from(shell: Shell) -> Self {
+        match shell {
+            Shell::Bash => clap_complete::Shell::Bash,
+            Shell::Zsh => clap_complete::Shell::Zsh,
+            Shell::Fish => clap_complete::Shell::Fish,
+            Shell::Powershell => clap_complete::Shell::PowerShell,
+        }
+    }
+}
+
+/// Hidden subcommand that prints the project's hidden targets, one per line. The
+/// scripts run it at completion time, so candidates follow `.cloak/storage`.
+pub const TARGETS_COMMAND: &str = "__complete-targets";
+
+/// Subcommands whose positional arguments name a hidden target.
+const TARGET_COMMANDS: &[&str] = &["unhide", "snapshots", "rollback", "info", "label"];
+
+/// Render a completion script for `cmd`, registered under `bin_name`.
+///
+/// Subcommands and flags come from `clap_complete`; on top of that, the commands
+/// in `TARGET_COMMANDS` complete hidden target names by running `TARGETS_COMMAND`.
+/// `bin_name` is whatever the user invoked (e.g. a renamed binary or alias), so the
+/// script completes the command the user actually types rather than a hard-coded name.
+pub fn generate(cmd: &Command, bin_name: &str, shell: Shell) -> String {
+    let mut cmd = visible(cmd);
+    let mut script = Vec::new();
+    clap_complete::generate(
+        clap_complete::Shell::from(shell),
+        &mut cmd,
+        bin_name,
+        &mut script,
+    );
+    let script = String::from_utf8_lossy(&script).into_owned();
+
+    let value_flags = value_flags(&cmd);
+    match shell {
+        Shell::Bash => bash(script, bin_name, &value_flags),
+        Shell::Zsh => zsh(script, bin_name, &value_flags),
+        Shell::Fish => fish(script, bin_name),
+        Shell::Powershell => powershell(script, bin_name),
+    }
+}
+
+/// `cmd` without its hidden subcommands, which `clap_complete` would otherwise offer.
+fn visible(cmd: &Command) -> Command {
+    let mut visible = Command::new(cmd.get_name().to_string())
+        .args(cmd.get_arguments().cloned())
+        .subcommands(cmd.get_subcommands().filter(|s| !s.is_hide_set()).cloned());
+    if let Some(version) = cmd.get_version() {
+        visible = visible.version(version.to_string());
+    }
+    if let Some(about) = cmd.get_about() {
+        visible = visible.about(about.clone());
+    }
+    visible
+}
+
+/// Top-level flags that take a value, so the scripts can skip that value while
+/// looking for the subcommand.
+fn value_flags(cmd: &Command) -> Vec<String> {
+    cmd.get_arguments()
+        .filter(|a| a.get_action().takes_values())
+        .flat_map(|a| {
+            let long = a.get_long().map(|l| format!("--{l}"));
+            let short = a.get_short().map(|s| format!("-{s}"));
+            long.into_iter().chain(short)
+        })
+        .collect()
+}
+
+/// Shell function names may only contain identifier characters.
+fn ident(bin_name: &str) -> String {
+    bin_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Wrap the generated function so target commands also offer hidden targets.
+fn bash(script: String, bin: &str, value_flags: &[String]) -> String {
+    let Some(generated) = script
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("complete -F "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(str::to_string)
+    else {
+        return script;
+    };
+    let func = format!("_{}_with_targets", ident(bin));
+    let skip = if value_flags.is_empty() {
+        String::new()
+    } else {
+        format!("            {}) skip=1 ;;\n", value_flags.join("|"))
+    };
+
+    let mut out = script;
+    out.push_str(&format!(
+        "
+{func}() {{
+    {generated} \"$@\"
+    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" cmd=\"\" skip=0 word
+    for word in \"${{COMP_WORDS[@]:1:COMP_CWORD-1}}\"; do
+        if (( skip )); then skip=0; continue; fi
+        case \"$word\" in
+{skip}            -*) ;;
+            *) cmd=\"$word\"; break ;;
+        esac
+    done
+    case \"$cmd\" in
+        {commands})
+            [[ \"$cur\" == -* ]] || COMPREPLY+=( $(compgen -W \"$({bin} {TARGETS_COMMAND} 2>/dev/null)\" -- \"$cur\") ) ;;
+    esac
+}}
+complete -F {func} -o bashdefault -o default {bin}
+",
+        commands = TARGET_COMMANDS.join("|"),
+    ));
+    out
+}
+
+/// Wrap the generated function so target commands also offer hidden targets, and
+/// register the wrapper both when sourced and when autoloaded from `fpath`.
+fn zsh(script: String, bin: &str, value_flags: &[String]) -> String {
+    let generated = format!("_{bin}");
+    let func = format!("_{}_with_targets", ident(bin));
+    let dispatch = format!("if [ \"$funcstack[1]\" = \"{generated}\" ]; then");
+    let body = match script.rfind(&dispatch) {
+        Some(at) => &script[..at],
+        None => script.as_str(),
+    };
+    let skip = if value_flags.is_empty() {
+        String::new()
+    } else {
+        format!("            ({}) skip=1 ;;\n", value_flags.join("|"))
+    };
+
+    format!(
+        "{body}{func}() {{
+    local word cmd skip=0
+    for word in \"${{(@)words[2,CURRENT-1]}}\"; do
+        if (( skip )); then skip=0; continue; fi
+        case $word in
+{skip}            (-*) ;;
+            (*) cmd=$word; break ;;
+        esac
+    done
+    case $cmd in
+        ({commands})
+            [[ $PREFIX == -* ]] || compadd -- ${{(f)\"$({bin} {TARGETS_COMMAND} 2>/dev/null)\"}} ;;
+    esac
+    {generated} \"$@\"
+}}
+
+compdef {func} {bin}
+if [ \"$funcstack[1]\" = \"{generated}\" ]; then
+    {func} \"$@\"
+fi
+",
+        commands = TARGET_COMMANDS.join("|"),
+    )
+}
+
+fn fish(mut script: String, bin: &str) -> String {
+    script.push_str(&format!(
+        "complete -c {bin} -n '__fish_seen_subcommand_from {}' -f -a '({bin} {TARGETS_COMMAND} 2>/dev/null)'\n",
+        TARGET_COMMANDS.join(" ")
+    ));
+    script
+}
+
+/// Add hidden targets to the generated completer's candidates before it filters them.
+fn powershell(mut script: String, bin: &str) -> String {
+    const FILTER: &str = "    $completions.Where{";
+    let Some(at) = script.rfind(FILTER) else {
+        return script;
+    };
+    let quoted = bin.replace('\'', "''");
+    let commands = TARGET_COMMANDS
+        .iter()
+        .map(|c| format!("'{quoted};{c}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    script.insert_str(
+        at,
+        &format!(
+            "    if ($command -in @({commands}) -and -not $wordToComplete.StartsWith('-')) {{
+        $completions += @(& '{quoted}' {TARGETS_COMMAND} 2>$null | ForEach-Object {{
+            [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_)
+        }})
+    }}
+
+"
+        ),
+    );
+    script
+}
+
+/// The name this process was invoked as (argv[0] without directories or `.exe`),
+/// falling back to `default` when argv[0] is unavailable.
+pub fn invoked_name(default: &str) -> String {
+    std::env::args_os()
+        .next()
+        .and_then(|arg0| {
+            std::path::Path::new(&arg0)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| default.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction};
+
+    fn sample() -> Command {
+        Command::new("cloak")
+            .arg(
+                Arg::new("root")
+                    .long("root")
+                    .short('r')
+                    .help("Project root"),
+            )
+            .subcommand(Command::new("init").about("Initialize cloak"))
+            .subcommand(
+                Command::new("unhide")
+                    .about("Restore targets")
+                    .arg(Arg::new("targets").num_args(1..)),
+            )
+            .subcommand(
+                Command::new("hide").about("Hide targets").arg(
+                    Arg::new("snapshot")
+                        .long("snapshot")
+                        .action(ArgAction::SetTrue),
+                ),
+            )
+            .subcommand(Command::new(TARGETS_COMMAND).hide(true))
+    }
+
+    #[test]
+    fn generators_use_the_given_binary_name() {
+        let bash = generate(&sample(), "ck-dev", Shell::Bash);
+        assert!(bash.contains("_ck_dev_with_targets() {"));
+        assert!(bash.contains("complete -F _ck_dev_with_targets -o bashdefault -o default ck-dev"));
+        assert!(bash.contains("--snapshot"));
+        assert!(bash.contains("--root|-r) skip=1"));
+
+        let zsh = generate(&sample(), "ck-dev", Shell::Zsh);
+        assert!(zsh.starts_with("#compdef ck-dev\n"));
+        assert!(zsh.contains("compdef _ck_dev_with_targets ck-dev"));
+        assert!(zsh.contains("(--root|-r) skip=1"));
+
+        let fish = generate(&sample(), "ck-dev", Shell::Fish);
+        assert!(fish.contains("complete -c ck-dev "));
+        assert!(fish.contains("-a \"hide\""));
+
+        let ps = generate(&sample(), "ck-dev", Shell::Powershell);
+        assert!(ps.contains("-CommandName 'ck-dev'"));
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::Powershell] {
+            let script = generate(&sample(), "ck-dev", shell);
+            assert!(
+                !script.contains(&format!("{TARGETS_COMMAND} -")),
+                "the helper must not be offered:\n{script}"
+            );
+        }
+    }
+
+    #[test]
+    fn target_commands_complete_hidden_targets_from_storage() {
+        let bash = generate(&sample(), "ck", Shell::Bash);
+        assert!(bash.contains("        unhide|snapshots|rollback|info|label)\n"));
+        assert!(bash.contains("$(ck __complete-targets 2>/dev/null)"));
+
+        let zsh = generate(&sample(), "ck", Shell::Zsh);
+        assert!(zsh.contains("compadd -- ${(f)\"$(ck __complete-targets 2>/dev/null)\"}"));
+        assert!(zsh.ends_with(
+            "if [ \"$funcstack[1]\" = \"_ck\" ]; then\n    _ck_with_targets \"$@\"\nfi\n"
+        ));
+
+        let fish = generate(&sample(), "ck", Shell::Fish);
+        assert!(fish.contains(
+            "complete -c ck -n '__fish_seen_subcommand_from unhide snapshots rollback info label' -f -a '(ck __complete-targets 2>/dev/null)'"
+        ));
+
+        let ps = generate(&sample(), "ck", Shell::Powershell);
+        assert!(ps.contains("if ($command -in @('ck;unhide', 'ck;snapshots'"));
+        assert!(ps.contains("$completions += @(& 'ck' __complete-targets 2>$null"));
+        assert!(ps.find("$completions +=") < ps.rfind("$completions.Where{"));
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{Result, bail};
+
+/// Expand a leading `~` and `$VAR` / `${VAR}` references using the process environment.
+///
+/// A referenced variable that is unset is an error rather than an empty string, so a
+/// typo never silently turns `$DATA/cloak` into `/cloak`.
+pub fn expand(input: &str) -> Result<String> {
+    expand_with(input, |name| std::env::var(name).ok())
+}
+
+fn expand_with(input: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::new();
+    let mut rest = input;
+
+    if rest == "~" || rest.starts_with("~/") || rest.starts_with("~\\") {
+        let Some(home) = lookup("HOME").or_else(|| lookup("USERPROFILE")) else {
+            bail!("cannot expand `~` in `{input}`: HOME is not set");
+        };
+        out.push_str(&home);
+        rest = &rest[1..];
+    }
+
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            let Some(end) = braced.find('}') else {
+                bail!("unterminated `${{` in `{input}`");
+            };
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+
+        if name.is_empty() {
+            // A bare `$` is kept literally.
+            out.push('$');
+            rest = after;
+            continue;
+        }
+
+        let Some(value) = lookup(name) else {
+            bail!("environment variable `{name}` is not set (referenced in `{input}`)");
+        };
+        out.push_str(&value);
+        rest = remainder;
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/me".into()),
+            "XDG_DATA_HOME" => Some("/data".into()),
+            "PROJECT" => Some("web".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_tilde_and_both_variable_forms() {
+        assert_eq!(
+            expand_with("~/cloak/$PROJECT", lookup).unwrap(),
+            "/home/me/cloak/web"
+        );
+        assert_eq!(
+            expand_with("${XDG_DATA_HOME}/cloak-${PROJECT}.d", lookup).unwrap(),
+            "/data/cloak-web.d"
+        );
+        assert_eq!(expand_with("/a/$/b~", lookup).unwrap(), "/a/$/b~");
+
+        let err = expand_with("$XDG_DATA_HOME/$MISSING", lookup).unwrap_err();
+        assert!(err.to_string().contains("`MISSING` is not set"));
+        assert!(expand_with("${PROJECT", lookup).is_err());
+    }
+}
@@ -1,15 +1,134 @@
+use crate::error::CloakError;
+use crate::utils::output::trace;
 use anyhow::{Context, Result};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 const GITIGNORE: &str = ".gitignore";
 const CLOAK_SECTION_START: &str = "# >>> cloak managed";
 const CLOAK_SECTION_END: &str = "# <<< cloak managed";
+/// Paths under the ignored `.cloak/` that are meant to be committed.
+const WHITELIST: &[&str] = &["!/.cloak/storage/", "!/.cloak/README.md"];
+
+/// Where `hide` writes a target's ignore rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RuleFile {
+    /// The project's tracked `.gitignore`.
+    #[default]
+    Gitignore,
+    /// The repository's untracked `.git/info/exclude`, seen only on this machine.
+    LocalExclude,
+}
+
+/// A file holding a managed section. `info/exclude` patterns are anchored at the
+/// repository's top level, so `prefix` is the path from there down to the project
+/// root (`packages/app/`), and empty for `.gitignore`.
+struct Rules {
+    path: PathBuf,
+    prefix: String,
+}
+
+impl Rules {
+    fn of(root: &Path, file: RuleFile) -> Result<Self> {
+        match file {
+            RuleFile::Gitignore => Ok(Self {
+                path: root.join(GITIGNORE),
+                prefix: String::new(),
+            }),
+            RuleFile::LocalExclude => local_exclude(root).ok_or_else(|| {
+                CloakError::Invalid(format!(
+                    "local ignore rules need a git repository, and {} is not inside one",
+                    root.display()
+                ))
+                .into()
+            }),
+        }
+    }
+
+    /// Both files for `root`; the exclude file only inside a repository.
+    fn all(root: &Path) -> Vec<Self> {
+        let mut all = vec![Self {
+            path: root.join(GITIGNORE),
+            prefix: String::new(),
+        }];
+        all.extend(local_exclude(root));
+        all
+    }
+
+    fn anchored(&self, target: &str) -> String {
+        format!("/{}{target}", self.prefix)
+    }
 
-/// Ensure the cloak gitignore block exists: ignore `.cloak/*` but whitelist `.cloak/storage/`.
+    /// The file's content, empty when it does not exist yet.
+    fn read(&self) -> Result<String> {
+        if !self.path.exists() {
+            return Ok(String::new());
+        }
+        fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))
+    }
+
+    fn write(&self, content: &str) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        fs::write(&self.path, content.as_bytes())
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+}
+
+/// The repository's `info/exclude` for a project at `root`, found the way git finds
+/// its directory: a `.git` directory, or a `.git` file naming one (worktrees and
+/// submodules). Linked worktrees share the main repository's exclude file.
+fn local_exclude(root: &Path) -> Option<Rules> {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let top = work_tree_top(&root)?;
+    let dot_git = top.join(".git");
+    let mut git_dir = if dot_git.is_dir() {
+        dot_git
+    } else {
+        let text = fs::read_to_string(&dot_git).ok()?;
+        top.join(text.trim().strip_prefix("gitdir:")?.trim())
+    };
+    if let Ok(common) = fs::read_to_string(git_dir.join("commondir")) {
+        git_dir = git_dir.join(common.trim());
+    }
+
+    let prefix = root
+        .strip_prefix(top)
+        .ok()?
+        .components()
+        .map(|c| format!("{}/", c.as_os_str().to_string_lossy()))
+        .collect();
+    Some(Rules {
+        path: git_dir.join("info").join("exclude"),
+        prefix,
+    })
+}
+
+/// The closest directory at or above `root` holding a `.git` entry.
+fn work_tree_top(root: &Path) -> Option<&Path> {
+    root.ancestors()
+        .find(|dir| dir.join(".git").symlink_metadata().is_ok())
+}
+
+/// Every file a target's rule may live in: `.gitignore`, plus the repository's
+/// exclude file inside one. Neither needs to exist yet.
+pub fn rule_paths(root: &Path) -> Vec<PathBuf> {
+    Rules::all(root)
+        .into_iter()
+        .map(|rules| rules.path)
+        .collect()
+}
+
+/// Ensure the cloak gitignore block exists: ignore `.cloak/*` but whitelist
+/// `.cloak/storage/` and `.cloak/README.md`.
 ///
-/// This allows real configs inside `.cloak/storage/` to be committed to git,
-/// while cloak internals (e.g. metadata files) are ignored.
+/// This allows real configs inside `.cloak/storage/` (and the README explaining
+/// them to collaborators) to be committed to git, while cloak internals (e.g.
+/// metadata files) are ignored.
 pub fn ensure_gitignore_entry(root: &Path) -> Result<()> {
     let gitignore_path = root.join(GITIGNORE);
     let mut content = if gitignore_path.exists() {
@@ -19,52 +138,52 @@ pub fn ensure_gitignore_entry(root: &Path) -> Result<()> {
         String::new()
     };
 
-    let has_ignore = content.lines().any(|line| line.trim() == "/.cloak/*");
-    let has_whitelist = content
-        .lines()
-        .any(|line| line.trim() == "!/.cloak/storage/");
+    let has_line = |want: &str| content.lines().any(|line| line.trim() == want);
+    let has_ignore = has_line("/.cloak/*");
+    let missing: Vec<&str> = WHITELIST.iter().copied().filter(|w| !has_line(w)).collect();
 
-    // Already has both required patterns — nothing to do
-    if has_ignore && has_whitelist {
+    // Already has every required pattern — nothing to do
+    if has_ignore && missing.is_empty() {
         return Ok(());
     }
 
-    // If ignore exists but whitelist is missing, append just the whitelist.
-    if has_ignore && !has_whitelist {
+    trace!("gitignore: add cloak rules to {}", gitignore_path.display());
+    let eol = line_ending(&content);
+
+    // If ignore exists but a whitelist line is missing, append just those.
+    if has_ignore {
         if !content.ends_with('\n') {
-            content.push('\n');
+            content.push_str(eol);
+        }
+        for line in missing {
+            content.push_str(line);
+            content.push_str(eol);
         }
-        content.push_str("!/.cloak/storage/\n");
         fs::write(&gitignore_path, content.as_bytes())
             .with_context(|| format!("failed to write {}", gitignore_path.display()))?;
         return Ok(());
     }
 
-    // Migrate legacy pattern: replace bare `.cloak/` with the new block
+    // Migrate legacy pattern: replace bare `.cloak/` with the new block. Each kept
+    // line keeps its own ending.
     if content.contains(".cloak/") {
         content = content
-            .lines()
+            .split_inclusive('\n')
             .filter(|line| {
                 let t = line.trim();
                 t != ".cloak/" && t != "/.cloak/" && t != "# Cloak storage"
             })
-            .collect::<Vec<_>>()
-            .join("\n");
-        // Ensure trailing newline after filtering
-        if !content.ends_with('\n') {
-            content.push('\n');
-        }
+            .collect();
     }
 
     if !content.is_empty() && !content.ends_with('\n') {
-        content.push('\n');
+        content.push_str(eol);
     }
 
-    content.push_str(
-        "\n# --- Cloak ---\n\
-         /.cloak/*\n\
-         !/.cloak/storage/\n",
-    );
+    for line in ["", "# --- Cloak ---", "/.cloak/*"].iter().chain(WHITELIST) {
+        content.push_str(line);
+        content.push_str(eol);
+    }
 
     fs::write(&gitignore_path, content.as_bytes())
         .with_context(|| format!("failed to write {}", gitignore_path.display()))?;
@@ -72,59 +191,204 @@ pub fn ensure_gitignore_entry(root: &Path) -> Result<()> {
     Ok(())
 }
 
-/// Add a symlink target to the cloak-managed section in `.gitignore`.
+/// Add a symlink target to the cloak-managed section of `file`.
 ///
 /// Entries are root-anchored (e.g. `/.cursor`) so only the symlink at the
 /// project root is ignored, not nested occurrences.
-pub fn add_ignore_entry(root: &Path, target: &str) -> Result<()> {
-    let gitignore_path = root.join(GITIGNORE);
-    let content = if gitignore_path.exists() {
-        fs::read_to_string(&gitignore_path)
-            .with_context(|| format!("failed to read {}", gitignore_path.display()))?
-    } else {
-        String::new()
-    };
-
+pub fn add_ignore_entry(root: &Path, target: &str, file: RuleFile) -> Result<()> {
+    let rules = Rules::of(root, file)?;
+    let content = rules.read()?;
     let mut entries = parse_managed_section(&content);
-    let anchored = format!("/{target}");
+    let anchored = rules.anchored(target);
 
     // Don't duplicate (check both anchored and legacy bare forms)
     if entries.contains(&anchored) || entries.contains(&target.to_string()) {
         return Ok(());
     }
 
+    trace!("gitignore: add {anchored} to {}", rules.path.display());
     entries.push(anchored);
-    let new_content = rebuild_gitignore(&content, &entries);
+    rules.write(&rebuild_gitignore(&content, &entries))
+}
 
-    fs::write(&gitignore_path, new_content.as_bytes())
-        .with_context(|| format!("failed to write {}", gitignore_path.display()))?;
+/// Whether a managed section (`.gitignore` or the repository's exclude file)
+/// already ignores `target`, anchored or in the legacy bare form.
+pub fn has_ignore_entry(root: &Path, target: &str) -> Result<bool> {
+    Ok(ignore_entry_file(root, target)?.is_some())
+}
 
-    Ok(())
+/// The file whose managed section ignores `target`, if any.
+pub fn ignore_entry_file(root: &Path, target: &str) -> Result<Option<PathBuf>> {
+    for rules in Rules::all(root) {
+        let anchored = rules.anchored(target);
+        if parse_managed_section(&rules.read()?)
+            .iter()
+            .any(|e| e == &anchored || e == target)
+        {
+            return Ok(Some(rules.path));
+        }
+    }
+    Ok(None)
 }
 
-/// Remove a symlink target from the cloak-managed section in `.gitignore`.
+/// Targets listed in either managed section, with the anchor stripped (`/.cursor` -> `.cursor`).
+pub fn managed_entries(root: &Path) -> Result<Vec<String>> {
+    let mut targets: Vec<String> = Vec::new();
+    for rules in Rules::all(root) {
+        let anchor = rules.anchored("");
+        for entry in parse_managed_section(&rules.read()?) {
+            let target = entry.strip_prefix(&anchor).unwrap_or(&entry).to_string();
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+    }
+    Ok(targets)
+}
+
+/// Remove a symlink target from whichever managed section lists it.
 pub fn remove_ignore_entry(root: &Path, target: &str) -> Result<()> {
-    let gitignore_path = root.join(GITIGNORE);
+    for rules in Rules::all(root) {
+        if !rules.path.exists() {
+            continue;
+        }
+        let content = rules.read()?;
+        let mut entries = parse_managed_section(&content);
+        let anchored = rules.anchored(target);
+
+        // Remove both anchored and legacy bare forms
+        let before = entries.len();
+        entries.retain(|e| e != &anchored && e != target);
+        if entries.len() == before {
+            continue;
+        }
+        trace!("gitignore: remove {anchored} from {}", rules.path.display());
+        rules.write(&rebuild_gitignore(&content, &entries))?;
+    }
+    Ok(())
+}
 
-    if !gitignore_path.exists() {
-        return Ok(());
+/// Whether `root` is inside a git work tree: it or one of its ancestors holds a
+/// `.git` entry (a directory, or the file a worktree or submodule uses).
+pub fn in_repository(root: &Path) -> bool {
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    work_tree_top(&root).is_some()
+}
+
+/// Ask git whether `.cloak/storage/` would still be ignored despite our whitelist
+/// (e.g. a broader `*` rule that ignores `.cloak` itself, which git cannot re-include).
+///
+/// Returns the offending rule as `source:line:pattern`, or `None` when storage is
+/// trackable, the project is not a git repository, or git is unavailable.
+pub fn storage_ignore_conflict(root: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(["check-ignore", "--verbose", "--no-index", ".cloak/storage/"])
+        .output()
+        .ok()?;
+
+    // Exit status 0 means the path is ignored; 1 means it is not; anything else is an error.
+    if !output.status.success() {
+        return None;
     }
 
-    let content = fs::read_to_string(&gitignore_path)
-        .with_context(|| format!("failed to read {}", gitignore_path.display()))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (rule, _path) = stdout.lines().next()?.split_once('\t')?;
+    let pattern = rule.rsplit(':').next().unwrap_or_default();
+    if pattern.starts_with('!') {
+        return None;
+    }
+    Some(rule.to_string())
+}
 
-    let mut entries = parse_managed_section(&content);
-    let anchored = format!("/{target}");
+/// How a storage path compares with the last commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageChange {
+    /// Matches `HEAD` exactly.
+    Unchanged,
+    /// `git status --porcelain` lines for everything that differs from `HEAD`.
+    Changed(Vec<String>),
+    /// Nothing under the path has been committed yet.
+    Uncommitted,
+}
 
-    // Remove both anchored and legacy bare forms
-    entries.retain(|e| e != &anchored && e != target);
+/// Compare `.cloak/storage/<target>` with `HEAD`.
+///
+/// Returns `None` when the project is not a git repository, git is unavailable,
+/// or storage lives outside the project (git does not follow the storage link).
+pub fn storage_changes(root: &Path, target: &str) -> Option<StorageChange> {
+    let storage = root.join(".cloak").join("storage");
+    if storage.symlink_metadata().ok()?.file_type().is_symlink() {
+        return None;
+    }
+    let path = format!(".cloak/storage/{target}");
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|out| out.status.success())
+    };
 
-    let new_content = rebuild_gitignore(&content, &entries);
+    let status = git(&[
+        "status",
+        "--porcelain",
+        "--untracked-files=all",
+        "--",
+        &path,
+    ])?;
+    // Fails without a first commit, which is the same as nothing committed.
+    let committed = git(&["ls-tree", "-r", "--name-only", "HEAD", "--", &path])
+        .is_some_and(|out| !out.stdout.is_empty());
+    if !committed {
+        return Some(StorageChange::Uncommitted);
+    }
 
-    fs::write(&gitignore_path, new_content.as_bytes())
-        .with_context(|| format!("failed to write {}", gitignore_path.display()))?;
+    let lines: Vec<String> = String::from_utf8_lossy(&status.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect();
+    Some(if lines.is_empty() {
+        StorageChange::Unchanged
+    } else {
+        StorageChange::Changed(lines)
+    })
+}
 
-    Ok(())
+/// Whether the user's own `.gitignore` rules (outside the managed section) list
+/// `name` as a plain root entry, in any of the forms `name`, `/name`, `name/`, `/name/`.
+pub fn is_user_ignored(root: &Path, name: &str) -> Result<bool> {
+    let gitignore_path = root.join(GITIGNORE);
+    if !gitignore_path.exists() {
+        return Ok(false);
+    }
+    let content = fs::read_to_string(&gitignore_path)
+        .with_context(|| format!("failed to read {}", gitignore_path.display()))?;
+
+    let mut in_section = false;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed == CLOAK_SECTION_START {
+            in_section = true;
+            continue;
+        }
+        if trimmed == CLOAK_SECTION_END {
+            in_section = false;
+            continue;
+        }
+        if in_section {
+            continue;
+        }
+        let bare = trimmed.strip_prefix('/').unwrap_or(trimmed);
+        let bare = bare.strip_suffix('/').unwrap_or(bare);
+        if bare == name {
+            return Ok(true);
+        }
+    }
+    Ok(false)
 }
 
 /// Extract entries from the `# >>> cloak managed` section.
@@ -152,45 +416,66 @@ fn parse_managed_section(content: &str) -> Vec<String> {
     entries
 }
 
+/// The line ending most of `content`'s lines use; `\n` for an empty file or a tie.
+fn line_ending(content: &str) -> &'static str {
+    let crlf = content.matches("\r\n").count();
+    let lf = content.matches('\n').count() - crlf;
+    if crlf > lf { "\r\n" } else { "\n" }
+}
+
 /// Rebuild the full `.gitignore` content, replacing the managed section.
+///
+/// The section is written back where it was (or appended if there was none), with
+/// the file's dominant line ending and the indentation its markers and entries
+/// already had. Every other line keeps its own ending, so a hand-edited, CRLF or
+/// mixed file only changes by the entries themselves.
 fn rebuild_gitignore(content: &str, entries: &[String]) -> String {
+    let eol = line_ending(content);
+    let indent = |line: &str| line[..line.len() - line.trim_start().len()].to_string();
+
     let mut out = String::new();
     let mut in_section = false;
-    let mut section_found = false;
+    // Where the first section started in `out`, its marker indent, and its entry indent.
+    let mut layout: Option<(usize, String, Option<String>)> = None;
 
-    for line in content.lines() {
-        if line.trim() == CLOAK_SECTION_START {
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        if trimmed == CLOAK_SECTION_START {
             in_section = true;
-            section_found = true;
+            layout.get_or_insert_with(|| (out.len(), indent(line), None));
             continue;
         }
-        if line.trim() == CLOAK_SECTION_END {
+        if trimmed == CLOAK_SECTION_END {
             in_section = false;
             continue;
         }
-        if !in_section {
-            out.push_str(line);
-            out.push('\n');
+        if in_section {
+            if let Some((_, _, entry_indent @ None)) = &mut layout
+                && !trimmed.is_empty()
+            {
+                *entry_indent = Some(indent(line));
+            }
+            continue;
+        }
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str(eol);
         }
     }
 
-    // Append managed section if there are entries
-    if !entries.is_empty() {
-        if !out.ends_with('\n') {
-            out.push('\n');
-        }
-        out.push_str(CLOAK_SECTION_START);
-        out.push('\n');
-        for entry in entries {
-            out.push_str(entry);
-            out.push('\n');
-        }
-        out.push_str(CLOAK_SECTION_END);
-        out.push('\n');
-    } else if section_found {
-        // Section existed but is now empty — already stripped above, nothing to add back.
+    // An emptied section is simply dropped.
+    if entries.is_empty() {
+        return out;
     }
 
+    let (at, marker_indent, entry_indent) = layout.unwrap_or((out.len(), String::new(), None));
+    let entry_indent = entry_indent.unwrap_or_else(|| marker_indent.clone());
+    let mut section = format!("{marker_indent}{CLOAK_SECTION_START}{eol}");
+    for entry in entries {
+        section.push_str(&format!("{entry_indent}{entry}{eol}"));
+    }
+    section.push_str(&format!("{marker_indent}{CLOAK_SECTION_END}{eol}"));
+    out.insert_str(at, &section);
     out
 }
 
@@ -225,6 +510,36 @@ mod tests {
         let content = fs::read_to_string(root.join(".gitignore")).expect("read .gitignore failed");
         assert!(content.contains("/.cloak/*"));
         assert!(content.contains("!/.cloak/storage/"));
+        assert!(content.contains("!/.cloak/README.md"));
+
+        ensure_gitignore_entry(&root).expect("second ensure_gitignore_entry failed");
+        let again = fs::read_to_string(root.join(".gitignore")).expect("read .gitignore failed");
+        assert_eq!(again, content);
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn storage_ignore_conflict_reports_shadowing_rule() {
+        let root = make_temp_dir("gitignore-shadow");
+        let git_ok = Command::new("git")
+            .arg("-C")
+            .arg(&root)
+            .args(["init", "-q"])
+            .status()
+            .is_ok_and(|s| s.success());
+        if !git_ok {
+            fs::remove_dir_all(root).expect("cleanup failed");
+            return;
+        }
+
+        ensure_gitignore_entry(&root).expect("ensure_gitignore_entry failed");
+        assert_eq!(storage_ignore_conflict(&root), None);
+
+        let content = fs::read_to_string(root.join(".gitignore")).expect("read .gitignore failed");
+        fs::write(root.join(".gitignore"), format!("*\n{content}")).expect("write failed");
+        let conflict = storage_ignore_conflict(&root).expect("conflict not detected");
+        assert!(conflict.ends_with(":1:*"), "unexpected rule: {conflict}");
 
         fs::remove_dir_all(root).expect("cleanup failed");
     }
@@ -234,7 +549,7 @@ mod tests {
         let root = make_temp_dir("gitignore-roundtrip");
         ensure_gitignore_entry(&root).expect("ensure_gitignore_entry failed");
 
-        add_ignore_entry(&root, ".cursor").expect("add_ignore_entry failed");
+        add_ignore_entry(&root, ".cursor", RuleFile::Gitignore).expect("add_ignore_entry failed");
         let content = fs::read_to_string(root.join(".gitignore")).expect("read .gitignore failed");
         assert!(content.contains("/.cursor"));
         assert!(content.contains(CLOAK_SECTION_START));
@@ -248,4 +563,104 @@ mod tests {
 
         fs::remove_dir_all(root).expect("cleanup failed");
     }
+
+    #[test]
+    fn crlf_indented_section_parses_and_rebuilds_in_place() {
+        let root = make_temp_dir("gitignore-crlf");
+        let original = "node_modules/\r\n  # >>> cloak managed\r\n    /.cursor\r\n  # <<< cloak managed\r\ndist/\r\n";
+        fs::write(root.join(".gitignore"), original).expect("write .gitignore failed");
+
+        assert_eq!(managed_entries(&root).unwrap(), vec![".cursor".to_string()]);
+        assert!(has_ignore_entry(&root, ".cursor").unwrap());
+
+        add_ignore_entry(&root, ".idea", RuleFile::Gitignore).expect("add_ignore_entry failed");
+        let content = fs::read_to_string(root.join(".gitignore")).expect("read .gitignore failed");
+        assert_eq!(
+            content,
+            "node_modules/\r\n  # >>> cloak managed\r\n    /.cursor\r\n    /.idea\r\n  # <<< cloak managed\r\ndist/\r\n"
+        );
+        assert_eq!(content.matches(CLOAK_SECTION_START).count(), 1);
+
+        remove_ignore_entry(&root, ".idea").expect("remove_ignore_entry failed");
+        let content = fs::read_to_string(root.join(".gitignore")).expect("read .gitignore failed");
+        assert_eq!(content, original);
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn crlf_file_keeps_its_line_endings_when_rules_are_added() {
+        let root = make_temp_dir("gitignore-crlf-add");
+        let original = "# deps\r\nnode_modules/\r\n.cloak/\r\ndist/\r\n";
+        fs::write(root.join(".gitignore"), original).expect("write .gitignore failed");
+
+        ensure_gitignore_entry(&root).expect("ensure_gitignore_entry failed");
+        add_ignore_entry(&root, ".cursor", RuleFile::Gitignore).expect("add_ignore_entry failed");
+        let content = fs::read_to_string(root.join(".gitignore")).expect("read .gitignore failed");
+        assert_eq!(
+            content,
+            "# deps\r\nnode_modules/\r\ndist/\r\n\r\n# --- Cloak ---\r\n/.cloak/*\r\n\
+             !/.cloak/storage/\r\n!/.cloak/README.md\r\n\
+             # >>> cloak managed\r\n/.cursor\r\n# <<< cloak managed\r\n"
+        );
+
+        // A stray LF line keeps its ending and does not flip the file's convention.
+        let mixed = "a\r\nb\nc\r\n";
+        let rebuilt = rebuild_gitignore(mixed, &["/.idea".to_string()]);
+        assert_eq!(
+            rebuilt,
+            "a\r\nb\nc\r\n# >>> cloak managed\r\n/.idea\r\n# <<< cloak managed\r\n"
+        );
+        assert_eq!(rebuild_gitignore(&rebuilt, &[]), mixed);
+        assert_eq!(line_ending("a\r\nb\r\nc\n"), "\r\n");
+        assert_eq!(line_ending("a\nb\nc\r\n"), "\n");
+        assert_eq!(line_ending(""), "\n");
+
+        fs::remove_dir_all(root).expect("cleanup failed");
+    }
+
+    #[test]
+    fn local_exclude_anchors_at_the_top_level_and_follows_worktree_git_files() {
+        let repo = make_temp_dir("local-exclude");
+        let project = repo.join("packages").join("app");
+        fs::create_dir_all(repo.join(".git")).expect("failed to create .git");
+        fs::create_dir_all(&project).expect("failed to create project");
+
+        add_ignore_entry(&project, ".cursor", RuleFile::LocalExclude).expect("add failed");
+        let exclude = repo.join(".git").join("info").join("exclude");
+        let content = fs::read_to_string(&exclude).expect("read exclude failed");
+        assert!(content.contains("/packages/app/.cursor\n"), "{content}");
+        assert!(!project.join(".gitignore").exists());
+        assert!(has_ignore_entry(&project, ".cursor").unwrap());
+        assert_eq!(
+            managed_entries(&project).unwrap(),
+            vec![".cursor".to_string()]
+        );
+
+        remove_ignore_entry(&project, ".cursor").expect("remove failed");
+        let content = fs::read_to_string(&exclude).expect("read exclude failed");
+        assert!(!content.contains(CLOAK_SECTION_START), "{content}");
+
+        // A linked worktree's `.git` file points at its own git dir, whose
+        // `commondir` leads back to the repository that owns `info/exclude`.
+        let worktree = make_temp_dir("local-exclude-worktree");
+        let wt_git = repo.join(".git").join("worktrees").join("wt");
+        fs::create_dir_all(&wt_git).expect("failed to create worktree git dir");
+        fs::write(wt_git.join("commondir"), "../..\n").expect("write failed");
+        fs::write(
+            worktree.join(".git"),
+            format!("gitdir: {}\n", wt_git.display()),
+        )
+        .expect("write failed");
+        add_ignore_entry(&worktree, ".idea", RuleFile::LocalExclude).expect("add failed");
+        let content = fs::read_to_string(&exclude).expect("read exclude failed");
+        assert!(content.contains("/.idea\n"), "{content}");
+
+        let outside = make_temp_dir("local-exclude-none");
+        assert!(add_ignore_entry(&outside, ".idea", RuleFile::LocalExclude).is_err());
+
+        for dir in [repo, worktree, outside] {
+            fs::remove_dir_all(dir).expect("cleanup failed");
+        }
+    }
 }
@@ -1 +1,9 @@
+pub mod completions;
+pub mod env;
 pub mod git;
+pub mod output;
+pub mod parallel;
+pub mod process;
+pub mod prompt;
+pub mod time;
+pub mod toml;
@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much cloak prints, set once from `--quiet`/`--verbose` before a command runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Only errors (stderr) and output the command exists to produce (JSON, lists).
+    Quiet,
+    Normal,
+    /// Normal output plus a line on stderr for every filesystem step.
+    Verbose,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> Level {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Level::Quiet,
+        2 => Level::Verbose,
+        _ => Level::Normal,
+    }
+}
+
+/// `println!` for progress and decoration, which `--quiet` silences.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::utils::output::level() != $crate::utils::output::Level::Quiet {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// One filesystem step, logged to stderr under `--verbose` so it never mixes
+/// into output meant for other programs.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::utils::output::level() == $crate::utils::output::Level::Verbose {
+            eprintln!("{} {}", colored::Colorize::dimmed("[cloak]"), format_args!($($arg)*));
+        }
+    };
+}
+
+#[doc(hidden)]
+pub use crate::{say, trace};
@@ -0,0 +1,67 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Upper bound for the default worker count; config moves are IO-bound and
+/// more threads than this only add contention.
+const MAX_DEFAULT_WORKERS: usize = 8;
+
+/// Default worker count: the number of CPUs, capped.
+pub fn default_workers() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_DEFAULT_WORKERS)
+}
+
+/// Apply `f` to every item using at most `workers` threads, returning results in input order.
+///
+/// With `workers <= 1` (or a single item) everything runs on the calling thread.
+pub fn map<T, R, F>(items: &[T], workers: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if workers <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers.min(items.len()) {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    let result = f(item);
+                    results.lock().expect("result lock poisoned")[i] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .expect("result lock poisoned")
+        .into_iter()
+        .map(|r| r.expect("worker did not produce a result"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_preserves_order_for_any_worker_count() {
+        let items: Vec<usize> = (0..20).collect();
+        for workers in [1, 3, 8] {
+            let doubled = map(&items, workers, |n| n * 2);
+            assert_eq!(doubled, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+use std::process::Command;
+
+/// Check whether a process with the given PID is still running.
+///
+/// Returns `None` on platforms where liveness cannot be determined, so callers
+/// can stay conservative instead of assuming the process is gone.
+pub fn is_alive(pid: u32) -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        Some(std::path::Path::new("/proc").join(pid.to_string()).exists())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return Some(false);
+        };
+        // Signal 0 performs the permission/existence check without sending anything.
+        let ret = unsafe { libc::kill(pid, 0) };
+        if ret == 0 {
+            return Some(true);
+        }
+        Some(std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+/// Run `command` through the platform shell (`sh -c`, or `cmd /C` on Windows) in
+/// `cwd`, inheriting stdio, and fail unless it exits successfully.
+pub fn run_shell(command: &str, cwd: &Path) -> Result<()> {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+
+    let status = cmd
+        .current_dir(cwd)
+        .status()
+        .with_context(|| format!("failed to run `{command}`"))?;
+    if !status.success() {
+        match status.code() {
+            Some(code) => bail!("`{command}` exited with status {code}"),
+            None => bail!("`{command}` was terminated by a signal"),
+        }
+    }
+    Ok(())
+}
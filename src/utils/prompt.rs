@@ -0,0 +1,88 @@
+use colored::Colorize;
+use std::io::{self, BufRead, Write};
+
+/// Let the user toggle which of `items` to keep, one line of input at a time:
+/// numbers flip entries (`2 3`, ranges like `1-4`), `a` selects all, `n` none,
+/// an empty line confirms and `q` aborts. Everything starts selected.
+///
+/// Returns the indices of the selected items in order, or `None` on abort or
+/// end of input.
+pub fn multi_select(
+    items: &[&str],
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<Option<Vec<usize>>> {
+    let mut selected = vec![true; items.len()];
+    loop {
+        writeln!(output)?;
+        for (i, item) in items.iter().enumerate() {
+            let mark = if selected[i] {
+                "[x]".green()
+            } else {
+                "[ ]".dimmed()
+            };
+            writeln!(output, "  {mark} {:>2}. {item}", i + 1)?;
+        }
+        write!(
+            output,
+            "Toggle by number (e.g. `2 3` or `1-4`), `a` all, `n` none, Enter to confirm, `q` to abort: "
+        )?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim() {
+            "" => {
+                let picked = (0..items.len()).filter(|&i| selected[i]).collect();
+                return Ok(Some(picked));
+            }
+            "q" => return Ok(None),
+            "a" => selected.fill(true),
+            "n" => selected.fill(false),
+            words => match parse_picks(words, items.len()) {
+                Ok(picks) => picks.into_iter().for_each(|i| selected[i] = !selected[i]),
+                Err(bad) => writeln!(output, "{} {bad}", "Not understood:".yellow())?,
+            },
+        }
+    }
+}
+
+/// Parse `2 3 5-7` into zero-based indices below `len`; the first bad word is
+/// returned as the error.
+fn parse_picks(words: &str, len: usize) -> Result<Vec<usize>, String> {
+    let number = |s: &str| s.parse::<usize>().ok().filter(|n| (1..=len).contains(n));
+    let mut picks = Vec::new();
+    for word in words.split([' ', ',']).filter(|w| !w.is_empty()) {
+        let range = match word.split_once('-') {
+            Some((from, to)) => number(from).zip(number(to)),
+            None => number(word).map(|n| (n, n)),
+        };
+        match range {
+            Some((from, to)) if from <= to => picks.extend(from - 1..to),
+            _ => return Err(word.to_string()),
+        }
+    }
+    Ok(picks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(items: &[&str], keys: &str) -> Option<Vec<usize>> {
+        multi_select(items, keys.as_bytes(), io::sink()).expect("prompt failed")
+    }
+
+    #[test]
+    fn multi_select_toggles_entries_until_confirmed() {
+        let items = [".idea", ".vscode", ".cursor", ".claude"];
+        assert_eq!(run(&items, "\n"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(run(&items, "2\n\n"), Some(vec![0, 2, 3]));
+        assert_eq!(run(&items, "n\n1,3-4\n\n"), Some(vec![0, 2, 3]));
+        assert_eq!(run(&items, "9\nx\n2 2\n\n"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(run(&items, "1\nq\n"), None);
+        assert_eq!(run(&items, "1\n"), None);
+    }
+}
@@ -0,0 +1,278 @@
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+
+/// A value in the small TOML subset cloak understands.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed document. Keys inside `[table]` headers are flattened to `table.key`.
+#[derive(Debug, Default)]
+pub struct Document {
+    entries: BTreeMap<String, Value>,
+}
+
+impl Document {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    /// Every key in the document, table keys as `table.key`.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Keys directly under `[name]`, with the `name.` prefix stripped.
+    pub fn table<'a>(&'a self, name: &str) -> impl Iterator<Item = (&'a str, &'a Value)> {
+        let prefix = format!("{name}.");
+        self.entries
+            .iter()
+            .filter_map(move |(key, value)| Some((key.strip_prefix(&prefix)?, value)))
+    }
+}
+
+/// Parse a minimal TOML document: `[table]` headers, `key = value` pairs with
+/// strings, integers, booleans, and (possibly multi-line) arrays, plus `#` comments.
+///
+/// Anything outside that subset is reported as an error rather than guessed at.
+pub fn parse(input: &str) -> Result<Document> {
+    let mut doc = Document::default();
+    let mut table = String::new();
+    let mut lines = input.lines().enumerate();
+
+    while let Some((idx, raw)) = lines.next() {
+        let line_no = idx + 1;
+        let mut line = strip_comment(raw).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            if line.starts_with("[[") || !line.ends_with(']') {
+                bail!("line {line_no}: unsupported table header: {line}");
+            }
+            table = line[1..line.len() - 1].trim().to_string();
+            if table.is_empty() {
+                bail!("line {line_no}: empty table name");
+            }
+            continue;
+        }
+
+        let Some(eq) = line.find('=') else {
+            bail!("line {line_no}: expected `key = value`");
+        };
+
+        // Multi-line arrays: keep reading until brackets balance.
+        while bracket_depth(&line[eq + 1..]) > 0 {
+            let Some((_, next)) = lines.next() else {
+                bail!("line {line_no}: unterminated array");
+            };
+            line.push(' ');
+            line.push_str(strip_comment(next).trim());
+        }
+
+        let key = unquote_key(line[..eq].trim());
+        if key.is_empty() {
+            bail!("line {line_no}: missing key");
+        }
+        let (value, rest) = parse_value(line[eq + 1..].trim(), line_no)?;
+        if !rest.trim().is_empty() {
+            bail!(
+                "line {line_no}: unexpected trailing content: {}",
+                rest.trim()
+            );
+        }
+
+        let full_key = if table.is_empty() {
+            key
+        } else {
+            format!("{table}.{key}")
+        };
+        if doc.entries.insert(full_key.clone(), value).is_some() {
+            bail!("line {line_no}: duplicate key: {full_key}");
+        }
+    }
+
+    Ok(doc)
+}
+
+fn unquote_key(key: &str) -> String {
+    key.trim_matches('"').trim_matches('\'').to_string()
+}
+
+/// Remove a trailing `#` comment, ignoring `#` inside strings.
+fn strip_comment(line: &str) -> &str {
+    let mut in_basic = false;
+    let mut in_literal = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\\' if in_basic && !escaped => {
+                escaped = true;
+                continue;
+            }
+            '"' if !in_literal && !escaped => in_basic = !in_basic,
+            '\'' if !in_basic => in_literal = !in_literal,
+            '#' if !in_basic && !in_literal => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Net count of open `[` outside strings.
+fn bracket_depth(s: &str) -> i32 {
+    let mut depth = 0;
+    let mut in_basic = false;
+    let mut in_literal = false;
+    let mut escaped = false;
+    for c in s.chars() {
+        match c {
+            '\\' if in_basic && !escaped => {
+                escaped = true;
+                continue;
+            }
+            '"' if !in_literal && !escaped => in_basic = !in_basic,
+            '\'' if !in_basic => in_literal = !in_literal,
+            '[' if !in_basic && !in_literal => depth += 1,
+            ']' if !in_basic && !in_literal => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth
+}
+
+/// Parse one value from the start of `s`, returning it and the unconsumed remainder.
+fn parse_value(s: &str, line_no: usize) -> Result<(Value, &str)> {
+    if let Some(rest) = s.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(out), &rest[i + 1..])),
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => out.push('\n'),
+                    Some((_, 't')) => out.push('\t'),
+                    Some((_, '"')) => out.push('"'),
+                    Some((_, '\\')) => out.push('\\'),
+                    Some((_, other)) => bail!("line {line_no}: unsupported escape: \\{other}"),
+                    None => break,
+                },
+                _ => out.push(c),
+            }
+        }
+        bail!("line {line_no}: unterminated string");
+    }
+
+    if let Some(rest) = s.strip_prefix('\'') {
+        let Some(end) = rest.find('\'') else {
+            bail!("line {line_no}: unterminated string");
+        };
+        return Ok((Value::String(rest[..end].to_string()), &rest[end + 1..]));
+    }
+
+    if let Some(mut rest) = s.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_value(rest, line_no)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                bail!("line {line_no}: expected `,` or `]` in array");
+            }
+        }
+    }
+
+    let end = s
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(s.len());
+    let (token, rest) = s.split_at(end);
+    let value = match token {
+        "true" => Value::Boolean(true),
+        "false" => Value::Boolean(false),
+        _ => match token.replace('_', "").parse::<i64>() {
+            Ok(n) => Value::Integer(n),
+            Err(_) => bail!("line {line_no}: unsupported value: {token}"),
+        },
+    };
+    Ok((value, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_supports_scalars_arrays_and_tables() {
+        let doc = parse(
+            r#"
+# top-level settings
+retention = 3
+enabled = true # trailing comment
+name = "a # not a comment"
+list = [
+  ".foo",
+  '.bar',
+]
+
+[tool.cloak]
+targets = [".cursor"]
+"#,
+        )
+        .expect("parse failed");
+
+        assert_eq!(doc.get("retention").and_then(Value::as_integer), Some(3));
+        assert_eq!(doc.get("enabled"), Some(&Value::Boolean(true)));
+        assert_eq!(
+            doc.get("name"),
+            Some(&Value::String("a # not a comment".to_string()))
+        );
+        assert_eq!(
+            doc.get("list"),
+            Some(&Value::Array(vec![
+                Value::String(".foo".to_string()),
+                Value::String(".bar".to_string()),
+            ]))
+        );
+        assert!(doc.get("tool.cloak.targets").is_some());
+        assert_eq!(
+            doc.table("tool").map(|(k, _)| k).collect::<Vec<_>>(),
+            ["cloak.targets"]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unsupported_syntax() {
+        assert!(parse("key").is_err());
+        assert!(parse("key = 1.5").is_err());
+        assert!(parse("key = \"open").is_err());
+    }
+}
@@ -19,10 +19,19 @@ impl TempDir {
         let pid = std::process::id();
         let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
         path.push(format!("cloak-it-{prefix}-{pid}-{nanos}-{seq}"));
-        fs::create_dir_all(&path).expect("failed to create temp dir");
+        // Projects are git repositories unless a test says otherwise; cloak only
+        // looks for the `.git` entry, and `git init` fills it in where needed.
+        fs::create_dir_all(path.join(".git")).expect("failed to create temp dir");
         Self { path }
     }
 
+    /// A project directory that is not inside any git repository.
+    fn without_git(prefix: &str) -> Self {
+        let dir = Self::new(prefix);
+        fs::remove_dir(dir.path().join(".git")).expect("failed to remove .git");
+        dir
+    }
+
     fn path(&self) -> &Path {
         &self.path
     }
@@ -120,6 +129,100 @@ fn init_creates_storage_and_gitignore_rules() {
     assert!(gitignore.contains("!/.cloak/storage/"));
 }
 
+#[test]
+fn init_writes_readme_that_git_tracks() {
+    let root = TempDir::new("init-readme");
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(root.path())
+            .args(args)
+            .output()
+    };
+    if !git(&["init", "-q"]).is_ok_and(|o| o.status.success()) {
+        return;
+    }
+
+    assert_success(&run_cloak(root.path(), &["init"]));
+    let readme = root.path().join(".cloak").join("README.md");
+    let content = fs::read_to_string(&readme).expect("README.md not written");
+    assert!(content.contains("cloak relink"));
+
+    fs::write(root.path().join(".cloak").join("meta.json"), "{}").expect("write failed");
+    let added = git(&["add", "-A"]).expect("git add failed");
+    assert!(added.status.success(), "{}", output_text(&added));
+    let files = git(&["ls-files"]).expect("git ls-files failed");
+    let tracked = String::from_utf8_lossy(&files.stdout);
+    assert!(
+        tracked.lines().any(|f| f == ".cloak/README.md"),
+        "{tracked}"
+    );
+    assert!(!tracked.contains(".cloak/meta.json"), "{tracked}");
+
+    // An edited README survives a second init.
+    fs::write(&readme, "ours\n").expect("write failed");
+    assert_success(&run_cloak(root.path(), &["init"]));
+    assert_eq!(fs::read_to_string(&readme).expect("read failed"), "ours\n");
+}
+
+#[test]
+fn init_excludes_cloak_dir_in_ide_settings_unless_disabled() {
+    let root = TempDir::new("init-ide-cloak");
+    fs::create_dir_all(root.path().join(".vscode")).expect("failed to create .vscode");
+    assert_success(&run_cloak(root.path(), &["init"]));
+    let settings = fs::read_to_string(root.path().join(".vscode").join("settings.json"))
+        .expect("settings.json not written");
+    let json: serde_json::Value = serde_json::from_str(&settings).expect("invalid settings.json");
+    assert_eq!(
+        json["files.exclude"]["**/.cloak"],
+        serde_json::Value::Bool(true)
+    );
+
+    let opted_out = TempDir::new("init-ide-cloak-off");
+    fs::create_dir_all(opted_out.path().join(".vscode")).expect("failed to create .vscode");
+    fs::create_dir_all(opted_out.path().join(".cloak")).expect("failed to create .cloak");
+    fs::write(
+        opted_out.path().join(".cloak").join("cloak.toml"),
+        "ide_exclude_cloak = false\n",
+    )
+    .expect("failed to write cloak.toml");
+    assert_success(&run_cloak(opted_out.path(), &["init"]));
+    assert!(
+        !opted_out
+            .path()
+            .join(".vscode")
+            .join("settings.json")
+            .exists()
+    );
+}
+
+#[test]
+fn unhide_all_restores_everything_and_reports_failures() {
+    let root = TempDir::new("unhide-all");
+    for dir in [".cursor", ".claude", ".idea"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".claude", ".idea"],
+    ));
+    remove_path_entry(&root.path().join(".claude"));
+    fs::create_dir_all(root.path().join(".claude")).expect("failed to recreate .claude");
+
+    let out = run_cloak(root.path(), &["unhide", "--all"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = output_text(&out);
+    assert!(text.contains(".claude"), "{text}");
+    for dir in [".cursor", ".idea"] {
+        let meta = root.path().join(dir).symlink_metadata().unwrap();
+        assert!(meta.is_dir(), "{dir} was not restored:\n{text}");
+    }
+    assert!(root.path().join(".cloak/storage/.claude").exists());
+
+    let both = run_cloak(root.path(), &["unhide", "--all", ".claude"]);
+    assert_eq!(both.status.code(), Some(2), "{}", output_text(&both));
+}
+
 #[test]
 fn unhide_refuses_when_original_path_is_not_link() {
     let root = TempDir::new("unhide-conflict");
@@ -140,6 +243,7 @@ fn unhide_refuses_when_original_path_is_not_link() {
         "unhide should fail when root target is not a symlink:\n{}",
         output_text(&unhide_out)
     );
+    assert_eq!(unhide_out.status.code(), Some(4), "conflicts exit with 4");
 
     let combined = output_text(&unhide_out);
     assert!(
@@ -159,6 +263,84 @@ fn unhide_refuses_when_original_path_is_not_link() {
     );
 }
 
+#[cfg(unix)]
+#[test]
+fn status_flags_links_to_an_old_storage_location_as_wrong_target() {
+    use std::os::unix::fs::symlink;
+
+    let root = TempDir::new("status-wrong-target");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    // Left behind by a copy of the project that used to live elsewhere.
+    let cursor = root.path().join(".cursor");
+    fs::remove_file(&cursor).expect("failed to remove link");
+    symlink("/old/project/.cloak/storage/.cursor", &cursor).expect("symlink failed");
+    symlink("/old/project/.cloak/storage/.zed", root.path().join(".zed")).expect("symlink failed");
+    symlink("/usr", root.path().join("usr-link")).expect("symlink failed");
+
+    let out = run_cloak(root.path(), &["status"]);
+    assert_success(&out);
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        text.contains(".cursor [wrong target: /old/project/.cloak/storage/.cursor]"),
+        "{text}"
+    );
+    assert!(
+        text.contains(".zed [wrong target: /old/project/.cloak/storage/.zed]"),
+        "{text}"
+    );
+    assert_eq!(text.matches(".cursor [").count(), 1, "{text}");
+    assert!(!text.contains("usr-link"), "{text}");
+}
+
+#[cfg(unix)]
+#[test]
+fn status_json_lists_entries_and_orphans() {
+    let root = TempDir::new("status-json");
+    for dir in [".cursor", ".claude", ".idea"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".claude", ".idea"],
+    ));
+    remove_path_entry(&root.path().join(".claude"));
+    fs::remove_dir_all(root.path().join(".cloak/storage/.idea")).expect("failed to remove");
+
+    let out = run_cloak(root.path(), &["status", "--json"]);
+    assert_success(&out);
+    let items: serde_json::Value = serde_json::from_slice(&out.stdout).expect("not JSON");
+    let find = |name: &str| {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["name"] == name)
+            .unwrap_or_else(|| panic!("{name} missing from {items}"))
+            .clone()
+    };
+
+    let cursor = find(".cursor");
+    assert_eq!(cursor["link_status"], "linked");
+    assert_eq!(cursor["storage_exists"], true);
+    assert!(
+        cursor["link_target"]
+            .as_str()
+            .is_some_and(|t| Path::new(t).ends_with(".cloak/storage/.cursor"))
+    );
+    let claude = find(".claude");
+    assert_eq!(claude["link_status"], "missing");
+    assert!(claude["link_target"].is_null());
+    let idea = find(".idea");
+    assert_eq!(idea["link_status"], "broken");
+    assert_eq!(idea["storage_exists"], false);
+
+    let empty = TempDir::new("status-json-empty");
+    let out = run_cloak(empty.path(), &["status", "--json"]);
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "[]");
+}
+
 #[cfg(unix)]
 #[test]
 fn status_reports_orphaned_symlink() {
@@ -189,6 +371,34 @@ fn status_reports_orphaned_symlink() {
     );
 }
 
+#[cfg(unix)]
+#[test]
+fn status_reports_unreadable_storage_entry() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = TempDir::new("unreadable-status");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".cursor/rules.md"), "x\n").expect("failed to write rules");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    let stored = root.path().join(".cloak/storage/.cursor");
+    fs::set_permissions(&stored, fs::Permissions::from_mode(0o000)).expect("chmod failed");
+    // Root ignores permission bits, so there is nothing unreadable to report.
+    let readable = fs::read_dir(&stored).is_ok();
+    let status_out = run_cloak(root.path(), &["status"]);
+    fs::set_permissions(&stored, fs::Permissions::from_mode(0o755)).expect("chmod failed");
+    if readable {
+        return;
+    }
+
+    assert_success(&status_out);
+    let text = String::from_utf8_lossy(&status_out.stdout);
+    assert!(
+        text.contains(".cursor [unreadable: "),
+        "status did not report the unreadable entry:\n{text}"
+    );
+}
+
 #[cfg(target_os = "linux")]
 #[test]
 fn hide_and_unhide_work_with_cross_device_storage_symlink() {
@@ -254,3 +464,3311 @@ fn hide_and_unhide_work_with_cross_device_storage_symlink() {
 
     let _ = fs::remove_dir_all(external_storage);
 }
+
+#[test]
+fn hide_snapshot_then_rollback_restores_original_content() {
+    let root = TempDir::new("snapshot");
+    let cursor = root.path().join(".cursor");
+    fs::create_dir_all(&cursor).expect("failed to create .cursor");
+    fs::write(cursor.join("settings.json"), "{\"v\":\"original\"}\n")
+        .expect("failed to write settings");
+
+    let hide_out = run_cloak(root.path(), &["hide", "--snapshot", ".cursor"]);
+    assert_success(&hide_out);
+
+    let stored = root
+        .path()
+        .join(".cloak")
+        .join("storage")
+        .join(".cursor")
+        .join("settings.json");
+    fs::write(&stored, "{\"v\":\"mutated\"}\n").expect("failed to mutate stored settings");
+
+    let list_out = run_cloak(root.path(), &["snapshots", ".cursor"]);
+    assert_success(&list_out);
+    let listing = String::from_utf8_lossy(&list_out.stdout);
+    let timestamp = listing
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .find(|word| word.chars().all(|c| c.is_ascii_digit()))
+        .expect("snapshot timestamp missing from listing")
+        .to_string();
+
+    let rollback_out = run_cloak(root.path(), &["rollback", ".cursor", &timestamp]);
+    assert_success(&rollback_out);
+    assert_eq!(
+        fs::read_to_string(&stored).expect("failed to read stored settings"),
+        "{\"v\":\"original\"}\n"
+    );
+
+    let status_out = run_cloak(root.path(), &["status"]);
+    assert_success(&status_out);
+    assert!(
+        !String::from_utf8_lossy(&status_out.stdout).contains(".snapshots"),
+        "status should not list the snapshot directory as a hidden config"
+    );
+}
+
+#[test]
+fn hide_update_absorbs_content_that_replaced_the_ghost_link() {
+    let root = TempDir::new("hide-update");
+    let cursor = root.path().join(".cursor");
+    fs::create_dir_all(&cursor).expect("failed to create .cursor");
+    fs::write(cursor.join("rules"), "old\n").expect("failed to write rules");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    // The tool deletes the ghost link and writes a fresh config dir in its place.
+    remove_path_entry(&cursor);
+    fs::create_dir_all(&cursor).expect("failed to recreate .cursor");
+    fs::write(cursor.join("rules"), "new\n").expect("failed to write rules");
+
+    let plain = run_cloak(root.path(), &["hide", ".cursor"]);
+    assert_eq!(plain.status.code(), Some(4), "{}", output_text(&plain));
+
+    let out = run_cloak(root.path(), &["hide", "--update", ".cursor"]);
+    assert_success(&out);
+    assert!(
+        output_text(&out).contains("replaced the previous storage copy"),
+        "{}",
+        output_text(&out)
+    );
+    let storage = root.path().join(".cloak/storage");
+    assert_eq!(
+        fs::read_to_string(storage.join(".cursor/rules")).expect("missing stored rules"),
+        "new\n"
+    );
+    assert!(
+        cursor
+            .symlink_metadata()
+            .expect("missing ghost link")
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(
+        fs::read_to_string(cursor.join("rules")).expect("link does not resolve"),
+        "new\n"
+    );
+
+    let snapshot = fs::read_dir(storage.join(".snapshots"))
+        .expect("old copy was not snapshotted")
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_name().to_string_lossy().starts_with(".cursor."))
+        .expect("missing .cursor snapshot");
+    assert_eq!(
+        fs::read_to_string(snapshot.path().join("rules")).expect("missing snapshot rules"),
+        "old\n"
+    );
+}
+
+#[test]
+fn hide_accepts_absolute_path_inside_root() {
+    let root = TempDir::new("absolute-target");
+    let cursor = root.path().join(".cursor");
+    fs::create_dir_all(&cursor).expect("failed to create .cursor");
+    fs::write(cursor.join("settings.json"), "{}\n").expect("failed to write settings");
+
+    let absolute = cursor.to_string_lossy().to_string();
+    let hide_out = run_cloak(root.path(), &["hide", &absolute]);
+    assert_success(&hide_out);
+
+    assert!(
+        root.path()
+            .join(".cloak")
+            .join("storage")
+            .join(".cursor")
+            .is_dir(),
+        "absolute target should be stored under its root-relative name"
+    );
+    let gitignore =
+        fs::read_to_string(root.path().join(".gitignore")).expect("failed to read .gitignore");
+    assert!(gitignore.lines().any(|line| line == "/.cursor"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn gc_removes_empty_dirs_and_stale_lock() {
+    let root = TempDir::new("gc");
+    let init_out = run_cloak(root.path(), &["init"]);
+    assert_success(&init_out);
+
+    let cloak = root.path().join(".cloak");
+    fs::create_dir_all(cloak.join("backups").join("old")).expect("failed to create empty dirs");
+    fs::create_dir_all(cloak.join("storage").join(".snapshots"))
+        .expect("failed to create snapshot dir");
+    fs::create_dir_all(cloak.join("storage").join(".empty-config"))
+        .expect("failed to create hidden empty config");
+    // PIDs above the kernel's pid_max never exist.
+    fs::write(cloak.join(".lock"), "4294967295\n").expect("failed to write lock");
+
+    let dry_out = run_cloak(root.path(), &["gc", "--dry-run"]);
+    assert_success(&dry_out);
+    let dry_text = String::from_utf8_lossy(&dry_out.stdout);
+    assert!(dry_text.contains("Would remove"), "{dry_text}");
+    assert!(cloak.join(".lock").exists(), "dry run must not delete");
+
+    let gc_out = run_cloak(root.path(), &["gc"]);
+    assert_success(&gc_out);
+    assert!(!cloak.join(".lock").exists());
+    assert!(!cloak.join("backups").exists());
+    assert!(!cloak.join("storage").join(".snapshots").exists());
+    assert!(
+        cloak.join("storage").join(".empty-config").exists(),
+        "gc must never remove hidden entries in storage"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn status_tree_renders_nested_storage_layout() {
+    use std::os::unix::fs::symlink;
+
+    let root = TempDir::new("status-tree");
+    let storage = root.path().join(".cloak").join("storage");
+    fs::create_dir_all(storage.join("config").join("ai")).expect("failed to create nested entry");
+    fs::create_dir_all(storage.join(".vscode")).expect("failed to create flat entry");
+    fs::create_dir_all(root.path().join("config")).expect("failed to create root parent");
+    symlink(
+        storage.join("config").join("ai"),
+        root.path().join("config").join("ai"),
+    )
+    .expect("failed to link nested entry");
+
+    let status_out = run_cloak(root.path(), &["status", "--tree"]);
+    assert_success(&status_out);
+
+    let text = String::from_utf8_lossy(&status_out.stdout);
+    let expected = ".cloak/storage\n├── .vscode [link missing]\n└── config\n    └── ai [linked]\n";
+    assert!(text.contains(expected), "unexpected tree:\n{text}");
+}
+
+#[test]
+fn hide_with_concurrency_one_hides_every_target() {
+    let root = TempDir::new("concurrency");
+    let targets = [".cursor", ".idea", ".claude"];
+    for target in targets {
+        fs::create_dir_all(root.path().join(target)).expect("failed to create target");
+        fs::write(root.path().join(target).join("marker"), target).expect("failed to write");
+    }
+
+    let mut args = vec!["hide", "--concurrency", "1"];
+    args.extend(targets);
+    let hide_out = run_cloak(root.path(), &args);
+    assert_success(&hide_out);
+
+    let storage = root.path().join(".cloak").join("storage");
+    let gitignore =
+        fs::read_to_string(root.path().join(".gitignore")).expect("failed to read .gitignore");
+    for target in targets {
+        assert!(
+            storage.join(target).join("marker").exists(),
+            "{target} not stored"
+        );
+        assert!(
+            fs::symlink_metadata(root.path().join(target))
+                .expect("ghost link missing")
+                .file_type()
+                .is_symlink(),
+            "{target} should be a symlink"
+        );
+        assert!(gitignore.lines().any(|l| l == format!("/{target}")));
+    }
+}
+
+#[test]
+fn hide_rejects_zero_concurrency() {
+    let root = TempDir::new("concurrency-zero");
+    let out = run_cloak(root.path(), &["hide", "--concurrency", "0", ".cursor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+}
+
+// Not on Windows: without Developer Mode the directory links fall back to junctions.
+#[cfg(unix)]
+#[test]
+fn default_relative_links_survive_moving_the_project() {
+    let base = TempDir::new("relocate");
+    let before = base.path().join("before");
+    fs::create_dir_all(before.join(".cursor")).expect("failed to create .cursor");
+    fs::create_dir_all(before.join("config/ai")).expect("failed to create config/ai");
+    fs::write(before.join(".cursor/rules.md"), "rules").expect("failed to write rules");
+    fs::write(before.join("config/ai/prompt.md"), "prompt").expect("failed to write prompt");
+    assert_success(&run_cloak(&before, &["hide", ".cursor", "config/ai"]));
+
+    let after = base.path().join("after");
+    fs::rename(&before, &after).expect("failed to move the project");
+    assert_eq!(
+        fs::read_to_string(after.join(".cursor/rules.md")).expect("link broke after move"),
+        "rules"
+    );
+    assert_eq!(
+        fs::read_to_string(after.join("config/ai/prompt.md")).expect("nested link broke"),
+        "prompt"
+    );
+    assert_success(&run_cloak(&after, &["doctor"]));
+}
+
+#[cfg(unix)]
+#[test]
+fn hide_link_target_style_is_applied_and_recorded() {
+    let root = TempDir::new("link-style");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::create_dir_all(root.path().join(".idea")).expect("failed to create .idea");
+
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--link-target-style", "absolute", ".idea"],
+    ));
+
+    let relative = fs::read_link(root.path().join(".cursor")).expect("read_link failed");
+    assert!(
+        relative.is_relative(),
+        "default should be relative: {relative:?}"
+    );
+    let absolute = fs::read_link(root.path().join(".idea")).expect("read_link failed");
+    assert!(
+        absolute.is_absolute(),
+        "expected absolute link: {absolute:?}"
+    );
+
+    let meta = fs::read_to_string(root.path().join(".cloak").join("meta.json"))
+        .expect("failed to read meta.json");
+    let meta: serde_json::Value = serde_json::from_str(&meta).expect("meta is not JSON");
+    assert_eq!(meta["entries"][".cursor"]["link_style"], "relative");
+    assert_eq!(meta["entries"][".idea"]["link_style"], "absolute");
+}
+
+#[test]
+fn info_json_reflects_freshly_hidden_target() {
+    let root = TempDir::new("info");
+    let claude = root.path().join(".claude");
+    fs::create_dir_all(&claude).expect("failed to create .claude");
+    fs::write(claude.join("settings.json"), "0123456789").expect("failed to write settings");
+    fs::create_dir_all(root.path().join(".vscode")).expect("failed to create .vscode");
+
+    assert_success(&run_cloak(root.path(), &["hide", ".claude"]));
+
+    let out = run_cloak(root.path(), &["info", ".claude", "--json"]);
+    assert_success(&out);
+    let info: serde_json::Value =
+        serde_json::from_slice(&out.stdout).expect("info output is not JSON");
+
+    assert_eq!(info["target"], ".claude");
+    assert_eq!(info["link_state"], "linked");
+    assert_eq!(info["storage_exists"], true);
+    assert_eq!(info["size_bytes"], 10);
+    assert_eq!(info["gitignore_entry"], true);
+    assert_eq!(info["ide_excludes"][".vscode"], true);
+    assert!(info["hidden_at"].as_u64().is_some_and(|t| t > 0));
+
+    let missing = run_cloak(root.path(), &["info", ".idea"]);
+    assert!(!missing.status.success(), "{}", output_text(&missing));
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_reports_two_links_sharing_one_storage_entry() {
+    use std::os::unix::fs::symlink;
+
+    let root = TempDir::new("doctor-shared");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    let clean = run_cloak(root.path(), &["doctor"]);
+    assert_success(&clean);
+
+    symlink(
+        root.path().join(".cloak").join("storage").join(".cursor"),
+        root.path().join(".cursor-copy"),
+    )
+    .expect("failed to create second link");
+
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        text.contains("sharing one storage entry") && text.contains(".cursor, .cursor-copy"),
+        "unexpected doctor output:\n{text}"
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn unhide_clears_hidden_attribute_on_restored_directory() {
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::fileapi::{GetFileAttributesW, SetFileAttributesW};
+    use winapi::um::winnt::FILE_ATTRIBUTE_HIDDEN;
+
+    fn wide(path: &Path) -> Vec<u16> {
+        path.as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect()
+    }
+
+    let root = TempDir::new("windows-hidden");
+    let idea = root.path().join(".idea");
+    fs::create_dir_all(&idea).expect("failed to create .idea");
+    let idea_w = wide(&idea);
+    unsafe {
+        let attrs = GetFileAttributesW(idea_w.as_ptr());
+        assert_ne!(attrs, u32::MAX, "GetFileAttributesW failed");
+        assert_ne!(
+            SetFileAttributesW(idea_w.as_ptr(), attrs | FILE_ATTRIBUTE_HIDDEN),
+            0,
+            "SetFileAttributesW failed"
+        );
+    }
+
+    assert_success(&run_cloak(root.path(), &["hide", ".idea"]));
+    assert_success(&run_cloak(root.path(), &["unhide", ".idea"]));
+
+    assert!(
+        idea.is_dir(),
+        ".idea should be restored as a real directory"
+    );
+    let attrs = unsafe { GetFileAttributesW(idea_w.as_ptr()) };
+    assert_ne!(attrs, u32::MAX, "GetFileAttributesW failed");
+    assert_eq!(
+        attrs & FILE_ATTRIBUTE_HIDDEN,
+        0,
+        "restored directory should not keep the hidden attribute"
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn doctor_reports_junction_where_meta_recorded_symlink() {
+    let root = TempDir::new("doctor-link-kind");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    // Whatever hide managed to create, pretend it was a symlink that a later
+    // relink had to replace with a junction.
+    let meta_path = root.path().join(".cloak").join("meta.json");
+    let meta = fs::read_to_string(&meta_path).expect("failed to read meta.json");
+    fs::write(&meta_path, meta.replace("\"junction\"", "\"symlink\""))
+        .expect("failed to write meta.json");
+    let link = root.path().join(".cursor");
+    fs::remove_dir(&link).expect("failed to remove ghost link");
+    junction::create(
+        root.path().join(".cloak").join("storage").join(".cursor"),
+        &link,
+    )
+    .expect("failed to create junction");
+
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        text.contains("recreated as a different link type")
+            && text.contains("hidden as a symlink but is now a junction"),
+        "unexpected doctor output:\n{text}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_classifies_missing_conflicting_and_misdirected_links() {
+    let root = TempDir::new("doctor-entry-links");
+    for dir in [".cursor", ".idea", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".idea", ".claude"],
+    ));
+
+    remove_path_entry(&root.path().join(".cursor"));
+    remove_path_entry(&root.path().join(".idea"));
+    fs::create_dir_all(root.path().join(".idea")).expect("failed to recreate .idea");
+    remove_path_entry(&root.path().join(".claude"));
+    std::os::unix::fs::symlink(".cloak/storage/.cursor", root.path().join(".claude"))
+        .expect("symlink failed");
+
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    for expected in [
+        "Storage entries without a ghost link",
+        ".cursor — nothing at the root links to .cloak/storage/.cursor",
+        "Real files where a ghost link belongs",
+        ".idea — a real directory occupies the link path",
+        "Ghost links pointing at another storage entry",
+        ".claude — resolves to .cloak/storage/.cursor instead of .cloak/storage/.claude",
+    ] {
+        assert!(text.contains(expected), "missing `{expected}`:\n{text}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_fix_relinks_safe_cases_and_leaves_real_files_alone() {
+    let root = TempDir::new("doctor-fix");
+    for dir in [".cursor", ".idea", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".idea", ".claude"],
+    ));
+
+    remove_path_entry(&root.path().join(".cursor"));
+    // An absolute link left over from where the project used to live.
+    remove_path_entry(&root.path().join(".idea"));
+    std::os::unix::fs::symlink(
+        "/nonexistent/old/.cloak/storage/.idea",
+        root.path().join(".idea"),
+    )
+    .expect("symlink failed");
+    remove_path_entry(&root.path().join(".claude"));
+    fs::create_dir_all(root.path().join(".claude")).expect("failed to recreate .claude");
+    fs::write(root.path().join(".claude/keep.txt"), "mine").expect("failed to write file");
+
+    let out = run_cloak(root.path(), &["doctor", "--fix"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    for expected in [
+        "[fixed] .cursor — recreated the missing link",
+        "[fixed] .idea — re-anchored a dangling link",
+        "Still broken:",
+        ".claude — a real directory occupies the link path",
+    ] {
+        assert!(text.contains(expected), "missing `{expected}`:\n{text}");
+    }
+
+    for link in [".cursor", ".idea"] {
+        let path = root.path().join(link);
+        assert!(path.symlink_metadata().unwrap().file_type().is_symlink());
+        assert!(path.is_dir(), "{link} does not resolve");
+    }
+    let claude = root.path().join(".claude");
+    assert!(!claude.symlink_metadata().unwrap().file_type().is_symlink());
+    assert_eq!(fs::read_to_string(claude.join("keep.txt")).unwrap(), "mine");
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_reports_symlink_cycle_between_root_and_storage() {
+    let root = TempDir::new("doctor-cycle");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    // The storage entry becomes a link back to the ghost link that points at it.
+    let stored = root.path().join(".cloak/storage/.cursor");
+    fs::remove_dir_all(&stored).expect("failed to remove storage entry");
+    std::os::unix::fs::symlink("../../.cursor", &stored).expect("symlink failed");
+
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        text.contains("caught in a symlink cycle")
+            && text.contains(".cursor -> .cloak/storage/.cursor -> .cursor"),
+        "unexpected doctor output:\n{text}"
+    );
+    assert!(!text.contains("which is missing"), "{text}");
+}
+
+#[test]
+fn completions_use_invoked_binary_name() {
+    let root = TempDir::new("completions-alias");
+    let exe_name = if cfg!(windows) { "ck.exe" } else { "ck" };
+    let alias = root.path().join(exe_name);
+    fs::copy(cloak_bin(), &alias).expect("failed to copy cloak binary");
+
+    let output = Command::new(&alias)
+        .args(["completions", "bash"])
+        .output()
+        .expect("failed to execute aliased cloak");
+    assert_success(&output);
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        script.contains("complete -F _ck_with_targets -o bashdefault -o default ck\n"),
+        "script should register under the invoked name:\n{script}"
+    );
+
+    let output = Command::new(&alias)
+        .args(["completions", "fish", "--bin-name", "cloak-dev"])
+        .output()
+        .expect("failed to execute aliased cloak");
+    assert_success(&output);
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(script.contains("complete -c cloak-dev "));
+    assert!(!script.contains("complete -c ck "));
+}
+
+#[test]
+fn completion_scripts_offer_hidden_targets_from_storage() {
+    let root = TempDir::new("completions-targets");
+
+    let output = run_cloak(root.path(), &["__complete-targets"]);
+    assert_success(&output);
+    assert!(output.stdout.is_empty(), "{}", output_text(&output));
+
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".env.local"), "KEY=1").expect("failed to write .env.local");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".env.local"]));
+
+    let output = run_cloak(root.path(), &["__complete-targets"]);
+    assert_success(&output);
+    let mut names: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect();
+    names.sort();
+    assert_eq!(names, [".cursor", ".env.local"]);
+
+    // Flattened targets are offered by the root path the commands take.
+    fs::create_dir_all(root.path().join("a/b")).expect("failed to create a/b");
+    fs::write(root.path().join("a/b/.env"), "KEY=1").expect("failed to write a/b/.env");
+    assert_success(&run_cloak(root.path(), &["hide", "--flatten", "a/b/.env"]));
+    let output = run_cloak(root.path(), &["__complete-targets"]);
+    let names = String::from_utf8_lossy(&output.stdout);
+    assert!(names.lines().any(|l| l == "a/b/.env"), "{names}");
+    assert!(!names.contains("a__b__.env"), "{names}");
+
+    let output = run_cloak(root.path(), &["completions", "bash", "--bin-name", "cloak"]);
+    assert_success(&output);
+    let script = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        script.contains("$(cloak __complete-targets 2>/dev/null)"),
+        "{script}"
+    );
+    assert!(
+        !script.contains("__complete-targets)"),
+        "helper must not be offered:\n{script}"
+    );
+}
+
+#[test]
+fn hide_does_not_create_vscode_dir_when_absent() {
+    let root = TempDir::new("no-vscode");
+    fs::create_dir_all(root.path().join(".claude")).expect("failed to create .claude");
+
+    assert_success(&run_cloak(root.path(), &["hide", ".claude"]));
+    assert!(
+        !root.path().join(".vscode").exists(),
+        "hide should not create .vscode in a project that has none"
+    );
+
+    assert_success(&run_cloak(root.path(), &["unhide", ".claude"]));
+    assert!(!root.path().join(".vscode").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn init_expands_env_vars_in_external_storage_path() {
+    let root = TempDir::new("init-storage-env");
+    let external = TempDir::without_git("init-storage-external");
+
+    let out = Command::new(cloak_bin())
+        .arg("--root")
+        .arg(root.path())
+        .args([
+            "init",
+            "--storage",
+            "${CLOAK_IT_DATA}/cloak/$CLOAK_IT_PROJECT",
+        ])
+        .env("CLOAK_IT_DATA", external.path())
+        .env("CLOAK_IT_PROJECT", "web")
+        .output()
+        .expect("failed to execute cloak");
+    assert_success(&out);
+
+    let expected = external.path().join("cloak").join("web");
+    let storage = root.path().join(".cloak").join("storage");
+    assert_eq!(
+        fs::read_link(&storage).expect(".cloak/storage should be a symlink"),
+        expected
+    );
+    assert!(expected.is_dir());
+
+    let unset = Command::new(cloak_bin())
+        .arg("--root")
+        .arg(TempDir::new("init-storage-unset").path())
+        .args(["init", "--storage", "$CLOAK_IT_SURELY_UNSET/x"])
+        .env_remove("CLOAK_IT_SURELY_UNSET")
+        .output()
+        .expect("failed to execute cloak");
+    assert!(!unset.status.success());
+    assert!(output_text(&unset).contains("CLOAK_IT_SURELY_UNSET"));
+}
+
+#[test]
+fn hide_summary_counts_mixed_batch() {
+    let root = TempDir::new("hide-summary");
+    for dir in [".cursor", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    let out = run_cloak(
+        root.path(),
+        &["hide", "--summary", ".cursor", ".claude", ".gemini"],
+    );
+    assert!(!out.status.success(), "{}", output_text(&out));
+
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains("Summary: 1 hidden, 1 skipped, 1 failed"),
+        "{}",
+        output_text(&out)
+    );
+    assert!(stdout.contains(".gemini"), "{}", output_text(&out));
+    assert!(!stdout.contains("Hiding"), "{}", output_text(&out));
+}
+
+#[test]
+fn hide_with_trailing_slash_round_trips_cleanly() {
+    let root = TempDir::new("trailing-slash");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor/"]));
+    assert!(
+        root.path()
+            .join(".cloak")
+            .join("storage")
+            .join(".cursor")
+            .is_dir()
+    );
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).expect("read .gitignore");
+    assert!(gitignore.lines().any(|l| l == "/.cursor"), "{gitignore}");
+
+    assert_success(&run_cloak(root.path(), &["unhide", ".cursor"]));
+    assert!(root.path().join(".cursor").is_dir());
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).expect("read .gitignore");
+    assert!(!gitignore.contains(".cursor"), "{gitignore}");
+    let settings =
+        fs::read_to_string(root.path().join(".cursor").join("settings.json")).unwrap_or_default();
+    assert!(!settings.contains("**/.cursor"), "{settings}");
+}
+
+#[test]
+fn doctor_reports_ide_exclude_left_for_purged_target() {
+    let root = TempDir::new("doctor-orphan-exclude");
+    fs::create_dir_all(root.path().join(".vscode")).expect("failed to create .vscode");
+    fs::write(
+        root.path().join(".vscode").join("settings.json"),
+        "{ \"files.exclude\": { \"**/.env\": true } }",
+    )
+    .expect("failed to write settings");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+
+    // Purge the target by hand, leaving its exclude (and meta record) behind.
+    remove_path_entry(&root.path().join(".cursor"));
+    fs::remove_dir_all(root.path().join(".cloak").join("storage").join(".cursor"))
+        .expect("failed to purge storage entry");
+
+    // Leftover excludes and rules break nothing, so they warn without failing.
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert_success(&out);
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        text.contains("IDE excludes for targets cloak no longer manages")
+            && text.contains("[warning] .vscode")
+            && text.contains("excludes `**/.cursor`"),
+        "unexpected doctor output:\n{text}"
+    );
+    // The user's own exclude is not cloak's to report.
+    assert!(
+        !text.contains("`**/.env`"),
+        "unexpected doctor output:\n{text}"
+    );
+}
+
+#[test]
+fn doctor_reports_gitignore_drift_in_both_directions() {
+    let root = TempDir::new("doctor-ignore-drift");
+    for dir in [".cursor", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".claude"]));
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+
+    // Drop the rule for .cursor by hand and leave a rule with nothing behind it.
+    let gitignore_path = root.path().join(".gitignore");
+    let gitignore = fs::read_to_string(&gitignore_path).expect("read .gitignore");
+    let edited = gitignore
+        .replace("/.cursor\n", "")
+        .replace("/.claude\n", "/.claude\n/.gemini\n");
+    fs::write(&gitignore_path, edited).expect("write .gitignore");
+
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(text.contains("out of sync with storage"), "{text}");
+    assert!(
+        text.contains(".cursor — storage entry has no gitignore rule"),
+        "{text}"
+    );
+    assert!(
+        text.contains(".gemini — gitignore rule `/.gemini` has no storage entry"),
+        "{text}"
+    );
+    assert!(!text.contains(".claude —"), "{text}");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn cross_device_hide_preserves_per_file_permissions() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt, symlink};
+
+    if !Path::new("/dev/shm").exists() {
+        return;
+    }
+
+    let root = TempDir::new("cross-device-perms");
+    let root_dev = fs::metadata(root.path())
+        .expect("metadata root failed")
+        .dev();
+    let shm_dev = fs::metadata("/dev/shm")
+        .expect("metadata /dev/shm failed")
+        .dev();
+    if root_dev == shm_dev {
+        return;
+    }
+
+    let external =
+        PathBuf::from("/dev/shm").join(root.path().file_name().expect("temp dir has no file name"));
+    fs::create_dir_all(external.join("storage")).expect("failed to create shm storage");
+    fs::create_dir_all(root.path().join(".cloak")).expect("failed to create .cloak");
+    symlink(
+        external.join("storage"),
+        root.path().join(".cloak").join("storage"),
+    )
+    .expect("failed to link .cloak/storage to /dev/shm");
+
+    let hooks = root.path().join(".claude").join("hooks");
+    fs::create_dir_all(&hooks).expect("failed to create hooks dir");
+    let script = hooks.join("pre-commit.sh");
+    fs::write(&script, "#!/bin/sh\n").expect("failed to write hook");
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).expect("chmod hook failed");
+    fs::set_permissions(&hooks, fs::Permissions::from_mode(0o700)).expect("chmod dir failed");
+
+    let mode = |path: &Path| fs::metadata(path).expect("stat failed").mode() & 0o777;
+
+    assert_success(&run_cloak(root.path(), &["hide", ".claude"]));
+    let stored = external.join("storage").join(".claude").join("hooks");
+    assert_eq!(mode(&stored.join("pre-commit.sh")), 0o755);
+    assert_eq!(mode(&stored), 0o700);
+
+    assert_success(&run_cloak(root.path(), &["unhide", ".claude"]));
+    assert_eq!(mode(&script), 0o755);
+    assert_eq!(mode(&hooks), 0o700);
+
+    let _ = fs::remove_dir_all(external);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn cross_device_round_trip_keeps_exec_bits_mtimes_and_nested_symlinks() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt, symlink};
+    use std::time::{Duration, SystemTime};
+
+    if !Path::new("/dev/shm").exists() {
+        return;
+    }
+
+    let root = TempDir::new("cross-device-links");
+    let root_dev = fs::metadata(root.path())
+        .expect("metadata root failed")
+        .dev();
+    let shm_dev = fs::metadata("/dev/shm")
+        .expect("metadata /dev/shm failed")
+        .dev();
+    if root_dev == shm_dev {
+        return;
+    }
+
+    let external =
+        PathBuf::from("/dev/shm").join(root.path().file_name().expect("temp dir has no file name"));
+    fs::create_dir_all(external.join("storage")).expect("failed to create shm storage");
+    fs::create_dir_all(root.path().join(".cloak")).expect("failed to create .cloak");
+    symlink(
+        external.join("storage"),
+        root.path().join(".cloak").join("storage"),
+    )
+    .expect("failed to link .cloak/storage to /dev/shm");
+
+    let claude = root.path().join(".claude");
+    fs::create_dir_all(claude.join("hooks")).expect("failed to create hooks dir");
+    let script = claude.join("hooks").join("lint.sh");
+    fs::write(&script, "#!/bin/sh\n").expect("failed to write hook");
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o750)).expect("chmod hook failed");
+    let then = SystemTime::now() - Duration::from_secs(86_400);
+    fs::File::options()
+        .write(true)
+        .open(&script)
+        .and_then(|f| f.set_modified(then))
+        .expect("failed to set mtime");
+    symlink("hooks/lint.sh", claude.join("current")).expect("failed to create nested link");
+
+    let check = |dir: &Path| {
+        let hook = dir.join("hooks").join("lint.sh");
+        let meta = fs::metadata(&hook).expect("stat hook failed");
+        assert_eq!(meta.mode() & 0o777, 0o750, "{}", hook.display());
+        assert_eq!(
+            meta.modified().expect("no mtime"),
+            then,
+            "{}",
+            hook.display()
+        );
+        let link = dir.join("current");
+        assert!(link.is_symlink(), "{} was dereferenced", link.display());
+        assert_eq!(
+            fs::read_link(&link).expect("read_link failed"),
+            Path::new("hooks/lint.sh")
+        );
+    };
+
+    assert_success(&run_cloak(root.path(), &["hide", ".claude"]));
+    check(&external.join("storage").join(".claude"));
+
+    assert_success(&run_cloak(root.path(), &["unhide", ".claude"]));
+    check(&claude);
+
+    let _ = fs::remove_dir_all(external);
+}
+
+#[test]
+fn hide_report_records_each_target_outcome() {
+    let root = TempDir::new("hide-report");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    let report_path = root.path().join("report.json");
+
+    let out = run_cloak(
+        root.path(),
+        &[
+            "hide",
+            "--report",
+            &report_path.to_string_lossy(),
+            ".cursor",
+            ".gemini",
+        ],
+    );
+    assert!(!out.status.success(), "{}", output_text(&out));
+
+    let report = fs::read_to_string(&report_path).expect("report was not written");
+    let report: serde_json::Value = serde_json::from_str(&report).expect("report is not JSON");
+    assert_eq!(report["command"], "hide");
+    assert_eq!(report["succeeded"], 1);
+    assert_eq!(report["failed"], 1);
+
+    let targets = report["targets"]
+        .as_array()
+        .expect("targets is not an array");
+    assert_eq!(targets.len(), 2);
+    let by_name = |name: &str| {
+        targets
+            .iter()
+            .find(|t| t["target"] == name)
+            .unwrap_or_else(|| panic!("no report entry for {name}"))
+    };
+    assert_eq!(by_name(".cursor")["success"], true);
+    assert!(
+        by_name(".cursor")["steps"]
+            .as_array()
+            .is_some_and(|s| s.iter().any(|step| step == "link"))
+    );
+    assert_eq!(by_name(".gemini")["success"], false);
+    assert!(by_name(".gemini")["error"].is_string());
+
+    let unhide_report = root.path().join("unhide.json");
+    assert_success(&run_cloak(
+        root.path(),
+        &[
+            "unhide",
+            "--report",
+            &unhide_report.to_string_lossy(),
+            ".cursor",
+        ],
+    ));
+    let report = fs::read_to_string(&unhide_report).expect("unhide report was not written");
+    let report: serde_json::Value = serde_json::from_str(&report).expect("report is not JSON");
+    assert_eq!(report["targets"][0]["target"], ".cursor");
+    assert_eq!(report["targets"][0]["success"], true);
+
+    // Renamed and flattened targets report where their link and storage really are.
+    fs::create_dir_all(root.path().join("a/b")).expect("failed to create a/b");
+    fs::write(root.path().join("a/b/.env"), "TOKEN=1\n").expect("failed to write .env");
+    let path = |p: &str| root.path().join(p).to_string_lossy().into_owned();
+    for (args, link, stored) in [
+        (
+            vec!["--link-name", ".cursor-active", ".cursor"],
+            ".cursor-active",
+            ".cloak/storage/.cursor",
+        ),
+        (
+            vec!["--flatten", "a/b/.env"],
+            "a/b/.env",
+            ".cloak/storage/a__b__.env",
+        ),
+    ] {
+        let mut hide = vec!["hide", "--report"];
+        let report_arg = report_path.to_string_lossy().into_owned();
+        hide.push(&report_arg);
+        hide.extend(args);
+        assert_success(&run_cloak(root.path(), &hide));
+        let report = fs::read_to_string(&report_path).expect("report was not written");
+        let report: serde_json::Value = serde_json::from_str(&report).expect("not JSON");
+        let entry = &report["targets"][0];
+        assert_eq!(entry["link_path"], path(link).as_str(), "{report}");
+        assert_eq!(entry["storage_path"], path(stored).as_str(), "{report}");
+        assert!(root.path().join(link).symlink_metadata().is_ok());
+        assert!(root.path().join(stored).exists());
+    }
+
+    assert_success(&run_cloak(
+        root.path(),
+        &[
+            "unhide",
+            "--report",
+            &unhide_report.to_string_lossy(),
+            "a/b/.env",
+        ],
+    ));
+    let report = fs::read_to_string(&unhide_report).expect("unhide report was not written");
+    let report: serde_json::Value = serde_json::from_str(&report).expect("report is not JSON");
+    assert_eq!(
+        report["targets"][0]["storage_path"],
+        path(".cloak/storage/a__b__.env").as_str()
+    );
+}
+
+#[test]
+fn dry_run_previews_hide_and_unhide_without_changing_anything() {
+    let root = TempDir::new("dry-run");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+
+    let ok = run_cloak(root.path(), &["--dry-run", "hide", ".cursor"]);
+    assert_success(&ok);
+    let text = output_text(&ok);
+    for step in [
+        "would move .cursor -> .cloak/storage/.cursor",
+        "would create symlink .cursor -> ",
+        "would add /.cursor to .gitignore",
+    ] {
+        assert!(text.contains(step), "missing `{step}`:\n{text}");
+    }
+    assert!(!root.path().join(".cloak").exists());
+    assert!(!root.path().join(".gitignore").exists());
+
+    let invalid = run_cloak(root.path(), &["hide", ".cursor", ".missing", "--dry-run"]);
+    assert_eq!(invalid.status.code(), Some(2), "{}", output_text(&invalid));
+    assert!(!root.path().join(".cloak").exists());
+
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).expect("no .gitignore");
+    let unhide = run_cloak(root.path(), &["unhide", "--dry-run", ".cursor"]);
+    assert_success(&unhide);
+    let text = output_text(&unhide);
+    assert!(text.contains("would remove symlink .cursor"), "{text}");
+    assert!(
+        text.contains("would move .cloak/storage/.cursor -> .cursor"),
+        "{text}"
+    );
+    assert!(
+        root.path()
+            .join(".cursor")
+            .symlink_metadata()
+            .expect("ghost link removed")
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(
+        fs::read_to_string(root.path().join(".gitignore")).expect("no .gitignore"),
+        gitignore
+    );
+
+    let status = run_cloak(root.path(), &["status", "--dry-run"]);
+    assert_eq!(status.status.code(), Some(2), "{}", output_text(&status));
+}
+
+#[test]
+fn tidy_explain_reports_each_decision_without_hiding() {
+    let root = TempDir::new("tidy-explain");
+    for dir in [".vscode", ".cursor", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create config dir");
+    }
+    fs::write(root.path().join(".gitignore"), "/.claude/\n").expect("failed to write .gitignore");
+    assert_success(&run_cloak(root.path(), &["hide", ".vscode"]));
+
+    let out = run_cloak(root.path(), &["tidy", "--explain", "--respect-gitignore"]);
+    assert_success(&out);
+    let text = output_text(&out);
+    for line in [
+        "skip .vscode (already hidden)",
+        "offer .cursor (real dir at root)",
+        "skip .idea (not present)",
+        "skip .claude (already listed in .gitignore)",
+    ] {
+        assert!(text.lines().any(|l| l == line), "missing `{line}`:\n{text}");
+    }
+
+    assert!(
+        !root
+            .path()
+            .join(".cursor")
+            .symlink_metadata()
+            .expect(".cursor should remain")
+            .file_type()
+            .is_symlink()
+    );
+    assert!(!root.path().join(".cloak/storage/.cursor").exists());
+}
+
+#[test]
+fn tidy_uses_known_dotfiles_and_ide_dirs_from_cloak_toml() {
+    let root = TempDir::new("tidy-config");
+    for dir in [".mytool", ".zed", ".vscode", ".cloak"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create dir");
+    }
+    fs::write(
+        root.path().join(".cloak/cloak.toml"),
+        "known_dotfiles = [\".mytool\"]\nide_dirs = [\".zed\"]\n",
+    )
+    .expect("failed to write cloak.toml");
+
+    assert_success(&run_cloak(root.path(), &["tidy", "--yes"]));
+    for name in [".mytool", ".zed", ".vscode"] {
+        let meta = root.path().join(name).symlink_metadata().unwrap();
+        assert!(meta.file_type().is_symlink(), "{name} was not hidden");
+    }
+    let zed = fs::read_to_string(root.path().join(".zed/settings.json"))
+        .expect("ide_dirs entry was not managed");
+    assert!(zed.contains("**/.mytool"), "{zed}");
+    assert!(!root.path().join(".vscode/settings.json").exists());
+
+    fs::write(
+        root.path().join(".cloak/cloak.toml"),
+        "known_dotfiles = [\"../x\"]\n",
+    )
+    .expect("failed to write cloak.toml");
+    assert!(
+        !run_cloak(root.path(), &["tidy", "--explain"])
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn tidy_respect_gitignore_skips_user_listed_dirs() {
+    let root = TempDir::new("tidy-respect-gitignore");
+    for dir in [".vscode", ".cursor"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create config dir");
+    }
+    fs::write(root.path().join(".gitignore"), "node_modules\n/.vscode/\n")
+        .expect("failed to write .gitignore");
+
+    let out = run_cloak(root.path(), &["tidy", "--yes", "--respect-gitignore"]);
+    assert_success(&out);
+    assert!(
+        output_text(&out).contains("Skipping .vscode"),
+        "{}",
+        output_text(&out)
+    );
+
+    let storage = root.path().join(".cloak").join("storage");
+    assert!(storage.join(".cursor").is_dir());
+    assert!(!storage.join(".vscode").exists());
+    assert!(
+        !root
+            .path()
+            .join(".vscode")
+            .symlink_metadata()
+            .expect(".vscode should remain")
+            .file_type()
+            .is_symlink()
+    );
+}
+
+#[test]
+fn version_json_reports_build_metadata() {
+    let root = TempDir::new("version-json");
+    let out = run_cloak(root.path(), &["version", "--json"]);
+    assert_success(&out);
+
+    let info: serde_json::Value =
+        serde_json::from_slice(&out.stdout).expect("version output is not JSON");
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["target"].as_str().is_some_and(|t| !t.is_empty()));
+    assert!(info["commit"].is_string() || info["commit"].is_null());
+    assert!(info["features"].as_array().is_some_and(|f| !f.is_empty()));
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_and_status_resolve_relative_ghost_links() {
+    use std::os::unix::fs::symlink;
+
+    let root = TempDir::new("relative-links");
+    for dir in [".cursor", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &[
+            "hide",
+            "--link-target-style",
+            "relative",
+            ".cursor",
+            ".claude",
+        ],
+    ));
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+    let status = run_cloak(root.path(), &["status"]);
+    assert!(
+        String::from_utf8_lossy(&status.stdout).contains(".cursor [linked]"),
+        "{}",
+        output_text(&status)
+    );
+
+    // Malformed relative link that climbs out of the project.
+    let cursor = root.path().join(".cursor");
+    fs::remove_file(&cursor).expect("failed to remove link");
+    symlink("../../elsewhere/.cloak/storage/.cursor", &cursor).expect("failed to relink");
+
+    // Relative link whose storage entry has disappeared.
+    let stored_claude = root.path().join(".cloak").join("storage").join(".claude");
+    fs::remove_dir_all(&stored_claude).expect("failed to remove storage entry");
+
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(text.contains("do not resolve to storage"), "{text}");
+    assert!(
+        text.contains(".cursor — link `../../elsewhere/.cloak/storage/.cursor` resolves outside"),
+        "{text}"
+    );
+    assert!(
+        text.contains(".claude — link `.cloak/storage/.claude` resolves to its storage entry, which is missing"),
+        "{text}"
+    );
+
+    let status = run_cloak(root.path(), &["status"]);
+    let text = String::from_utf8_lossy(&status.stdout);
+    assert!(text.contains(".cursor [wrong target"), "{text}");
+}
+
+#[cfg(unix)]
+#[test]
+fn storage_move_relocates_entries_and_keeps_links_resolving() {
+    let root = TempDir::new("storage-move");
+    let external = TempDir::without_git("storage-move-external");
+    let cursor = root.path().join(".cursor");
+    fs::create_dir_all(&cursor).expect("failed to create .cursor");
+    fs::write(cursor.join("rules.md"), "be nice").expect("failed to write rules");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    let dest = external.path().join("store");
+    assert_success(&run_cloak(
+        root.path(),
+        &["storage", "move", &dest.to_string_lossy()],
+    ));
+
+    let storage = root.path().join(".cloak").join("storage");
+    assert_eq!(
+        fs::read_link(&storage).expect("storage should be a link"),
+        dest
+    );
+    assert!(dest.join(".cursor").join("rules.md").is_file());
+    assert_eq!(
+        fs::read_to_string(cursor.join("rules.md")).expect("link should resolve"),
+        "be nice"
+    );
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["storage", "move", ".cloak/storage"],
+    ));
+    assert!(
+        !storage
+            .symlink_metadata()
+            .expect("storage missing")
+            .file_type()
+            .is_symlink()
+    );
+    assert!(storage.join(".cursor").join("rules.md").is_file());
+    assert_eq!(
+        fs::read_to_string(cursor.join("rules.md")).expect("link should resolve"),
+        "be nice"
+    );
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+}
+
+#[test]
+fn hide_env_detect_writes_export_for_known_tool() {
+    let root = TempDir::new("env-detect");
+    fs::create_dir_all(root.path().join(".codex")).expect("failed to create .codex");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--env-detect", ".codex", ".cursor"],
+    ));
+
+    let stored = root
+        .path()
+        .join(".cloak")
+        .join("storage")
+        .join(".codex")
+        .canonicalize()
+        .expect("stored .codex missing");
+    let env = fs::read_to_string(root.path().join(".cloak").join("env")).expect("env file missing");
+    let expected = format!("export CODEX_HOME='{}'", stored.display());
+    assert!(env.lines().any(|l| l == expected), "{env}");
+    assert_eq!(env.matches("export ").count(), 1, "{env}");
+
+    let printed = run_cloak(root.path(), &["env"]);
+    assert_success(&printed);
+    assert_eq!(String::from_utf8_lossy(&printed.stdout).trim(), expected);
+
+    assert_success(&run_cloak(root.path(), &["unhide", ".codex"]));
+    assert!(!root.path().join(".cloak").join("env").exists());
+}
+
+#[test]
+fn hide_assume_initialized_errors_on_uninitialized_root() {
+    let root = TempDir::new("assume-initialized");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+
+    let out = run_cloak(root.path(), &["hide", "--assume-initialized", ".cursor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    assert!(
+        output_text(&out).contains("cloak is not initialized"),
+        "{}",
+        output_text(&out)
+    );
+    assert!(
+        root.path().join(".cursor").is_dir(),
+        "target must be untouched"
+    );
+    assert!(!root.path().join(".cloak").exists());
+    assert!(!root.path().join(".gitignore").exists());
+
+    assert_success(&run_cloak(root.path(), &["init"]));
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--assume-initialized", ".cursor"],
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn hide_chmod_tightens_stored_entry() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = TempDir::new("hide-chmod");
+    let claude = root.path().join(".claude");
+    fs::create_dir_all(claude.join("hooks")).expect("failed to create .claude");
+    fs::write(claude.join("token"), "secret").expect("failed to write token");
+    fs::set_permissions(claude.join("token"), fs::Permissions::from_mode(0o644))
+        .expect("chmod failed");
+    fs::set_permissions(&claude, fs::Permissions::from_mode(0o750)).expect("chmod failed");
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--chmod", "600", ".claude"],
+    ));
+
+    let mode = |path: &Path| {
+        fs::metadata(path)
+            .expect("stat failed")
+            .permissions()
+            .mode()
+            & 0o777
+    };
+    let stored = root.path().join(".cloak").join("storage").join(".claude");
+    assert_eq!(mode(&stored), 0o700);
+    assert_eq!(mode(&stored.join("hooks")), 0o700);
+    assert_eq!(mode(&stored.join("token")), 0o600);
+
+    let meta = fs::read_to_string(root.path().join(".cloak").join("meta.json"))
+        .expect("failed to read meta.json");
+    let meta: serde_json::Value = serde_json::from_str(&meta).expect("meta is not JSON");
+    assert_eq!(meta["entries"][".claude"]["chmod"], "0600");
+
+    let bad = run_cloak(root.path(), &["hide", "--chmod", "9z", ".idea"]);
+    assert!(!bad.status.success());
+
+    let out = run_cloak(root.path(), &["unhide", ".claude"]);
+    assert_success(&out);
+    assert!(
+        output_text(&out).contains("restored mode 0750"),
+        "{}",
+        output_text(&out)
+    );
+    assert_eq!(mode(&claude), 0o750);
+    assert_eq!(mode(&claude.join("token")), 0o600);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn hide_immutable_blocks_writes_through_link_until_unhide() {
+    let root = TempDir::new("hide-immutable");
+    // Needs CAP_LINUX_IMMUTABLE and a filesystem that supports the flag.
+    let probe = root.path().join("probe");
+    fs::write(&probe, "").expect("failed to write probe");
+    let chattr = |flag: &str, path: &Path| {
+        Command::new("chattr")
+            .arg(flag)
+            .arg(path)
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    if !chattr("+i", &probe) {
+        return;
+    }
+    assert!(chattr("-i", &probe));
+
+    let cursor = root.path().join(".cursor");
+    fs::create_dir_all(&cursor).expect("failed to create .cursor");
+    fs::write(cursor.join("rules.md"), "locked").expect("failed to write rules");
+    let out = run_cloak(root.path(), &["hide", "--immutable", ".cursor"]);
+    assert_success(&out);
+    assert!(
+        output_text(&out).contains("Locked .cursor"),
+        "{}",
+        output_text(&out)
+    );
+
+    assert!(fs::write(cursor.join("rules.md"), "changed").is_err());
+    assert!(fs::write(cursor.join("new.md"), "new").is_err());
+    assert_eq!(
+        fs::read_to_string(cursor.join("rules.md")).unwrap(),
+        "locked"
+    );
+
+    let out = run_cloak(root.path(), &["unhide", ".cursor"]);
+    assert_success(&out);
+    assert!(output_text(&out).contains("cleared immutable flag"));
+    assert!(!cursor.symlink_metadata().unwrap().file_type().is_symlink());
+    fs::write(cursor.join("rules.md"), "changed").expect("restored entry is still locked");
+}
+
+#[test]
+fn list_stale_reports_targets_whose_tool_is_missing() {
+    let root = TempDir::new("list-stale");
+    let bin = TempDir::new("list-stale-bin");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::create_dir_all(root.path().join(".claude")).expect("failed to create .claude");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".claude"]));
+
+    let installed = bin.path().join(if cfg!(windows) {
+        "cloak-it-installed.exe"
+    } else {
+        "cloak-it-installed"
+    });
+    fs::write(&installed, "#!/bin/sh\n").expect("failed to write fake tool");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&installed, fs::Permissions::from_mode(0o755)).expect("chmod failed");
+    }
+    fs::write(
+        root.path().join(".cloak").join("cloak.toml"),
+        "[tool_commands]\n\".cursor\" = \"cloak-it-installed\"\n\".claude\" = \"cloak-it-uninstalled\"\n",
+    )
+    .expect("failed to write cloak.toml");
+
+    let list = |stale: bool| {
+        let mut cmd = Command::new(cloak_bin());
+        cmd.arg("--root").arg(root.path()).arg("list");
+        if stale {
+            cmd.arg("--stale");
+        }
+        let out = cmd
+            .env("PATH", bin.path())
+            .output()
+            .expect("failed to execute cloak");
+        assert_success(&out);
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+
+    assert_eq!(list(false), ".claude\n.cursor\n");
+    let stale = list(true);
+    assert!(stale.contains(".claude"), "{stale}");
+    assert!(stale.contains("cloak-it-uninstalled"), "{stale}");
+    assert!(!stale.contains(".cursor"), "{stale}");
+}
+
+#[test]
+fn unhide_nested_target_requires_parents_when_parent_is_missing() {
+    let root = TempDir::new("unhide-parents");
+    let stored = root
+        .path()
+        .join(".cloak")
+        .join("storage")
+        .join("packages")
+        .join("web");
+    fs::create_dir_all(&stored).expect("failed to create nested storage entry");
+    fs::write(stored.join(".env"), "TOKEN=1").expect("failed to write .env");
+
+    let refused = run_cloak(root.path(), &["unhide", "packages/web/.env"]);
+    assert!(!refused.status.success());
+    assert!(output_text(&refused).contains("--parents"));
+    assert!(
+        stored.join(".env").exists(),
+        "refused unhide must leave storage intact"
+    );
+    assert!(!root.path().join("packages").exists());
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["unhide", "--parents", "packages/web/.env"],
+    ));
+    let restored = root.path().join("packages").join("web").join(".env");
+    assert_eq!(
+        fs::read_to_string(restored).expect("failed to read restored .env"),
+        "TOKEN=1"
+    );
+    assert!(!stored.join(".env").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_flags_external_storage_replaced_by_real_directory() {
+    let root = TempDir::new("doctor-detached");
+    let external = TempDir::without_git("doctor-detached-external");
+    let external_path = external.path().to_string_lossy().into_owned();
+    assert_success(&run_cloak(
+        root.path(),
+        &["init", "--storage", &external_path],
+    ));
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+
+    // Swap the storage link for a real directory holding a copy.
+    let storage = root.path().join(".cloak").join("storage");
+    fs::remove_file(&storage).expect("failed to remove storage link");
+    fs::create_dir_all(storage.join(".cursor")).expect("failed to create local copy");
+
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(text.contains("External storage no longer linked"), "{text}");
+    assert!(
+        text.contains(".cloak/storage — is a real directory"),
+        "{text}"
+    );
+    assert!(text.contains(&external_path), "{text}");
+}
+
+#[test]
+fn hide_from_manifest_reads_package_json_targets() {
+    let root = TempDir::new("manifest-package-json");
+    for dir in [".cursor", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    fs::write(
+        root.path().join("package.json"),
+        r#"{ "name": "demo", "cloak": [".cursor", ".claude"] }"#,
+    )
+    .expect("failed to write package.json");
+
+    assert_success(&run_cloak(root.path(), &["hide", "--from-manifest"]));
+
+    let storage = root.path().join(".cloak").join("storage");
+    assert!(storage.join(".cursor").is_dir());
+    assert!(storage.join(".claude").is_dir());
+}
+
+#[test]
+fn hide_from_manifest_reads_pyproject_tool_table() {
+    let root = TempDir::new("manifest-pyproject");
+    for dir in [".vscode", ".idea"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    fs::write(
+        root.path().join("pyproject.toml"),
+        "[project]\nname = \"demo\"\n\n[tool.cloak]\ntargets = [\".vscode\"]\n",
+    )
+    .expect("failed to write pyproject.toml");
+
+    // Targets given on the command line are hidden alongside the manifest's.
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--from-manifest", ".idea"],
+    ));
+
+    let storage = root.path().join(".cloak").join("storage");
+    assert!(storage.join(".vscode").is_dir());
+    assert!(storage.join(".idea").is_dir());
+
+    fs::write(
+        root.path().join("pyproject.toml"),
+        "[tool.cloak]\ntargets = [\"../x\"]\n",
+    )
+    .expect("failed to rewrite pyproject.toml");
+    let invalid = run_cloak(root.path(), &["hide", "--from-manifest"]);
+    assert!(!invalid.status.success());
+    assert!(output_text(&invalid).contains("path traversal"));
+}
+
+#[test]
+fn hide_skips_missing_listed_targets_but_not_explicit_ones() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let root = TempDir::new("hide-missing-sources");
+    for dir in [".cursor", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    let storage = root.path().join(".cloak").join("storage");
+
+    let explicit = run_cloak(root.path(), &["hide", ".cursor", ".missing"]);
+    assert_eq!(
+        explicit.status.code(),
+        Some(2),
+        "{}",
+        output_text(&explicit)
+    );
+    assert!(output_text(&explicit).contains("target does not exist"));
+    assert!(storage.join(".cursor").is_dir());
+
+    fs::write(
+        root.path().join("package.json"),
+        r#"{ "cloak": [".claude", ".windsurf"] }"#,
+    )
+    .expect("failed to write package.json");
+    let manifest = run_cloak(root.path(), &["hide", "--from-manifest"]);
+    assert_success(&manifest);
+    assert!(output_text(&manifest).contains("skipping .windsurf from the manifest: not found"));
+    assert!(storage.join(".claude").is_dir());
+
+    fs::create_dir_all(root.path().join(".idea")).expect("failed to create .idea");
+    let mut child = Command::new(cloak_bin())
+        .arg("--root")
+        .arg(root.path())
+        .args(["hide", "--stdin0"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn cloak");
+    child
+        .stdin
+        .take()
+        .expect("stdin not piped")
+        .write_all(b".gone\0.idea\0")
+        .expect("failed to write stdin");
+    let stdin = child.wait_with_output().expect("failed to wait for cloak");
+    assert_success(&stdin);
+    assert!(output_text(&stdin).contains("skipping .gone from stdin: not found"));
+    assert!(storage.join(".idea").is_dir());
+}
+
+#[test]
+fn hide_label_round_trips_to_status_and_info() {
+    let root = TempDir::new("hide-label");
+    fs::create_dir_all(root.path().join(".idea")).expect("failed to create .idea");
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".idea", "--label", "JetBrains config, team-shared"],
+    ));
+
+    let status = run_cloak(root.path(), &["status"]);
+    assert_success(&status);
+    assert!(
+        output_text(&status).contains(".idea [linked] — JetBrains config, team-shared"),
+        "{}",
+        output_text(&status)
+    );
+    let info = run_cloak(root.path(), &["info", ".idea", "--json"]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&info.stdout).expect("info --json is not JSON");
+    assert_eq!(json["label"], "JetBrains config, team-shared");
+
+    assert_success(&run_cloak(root.path(), &["label", ".idea", "IDE files"]));
+    let status = run_cloak(root.path(), &["status"]);
+    assert!(output_text(&status).contains(".idea [linked] — IDE files"));
+
+    assert_success(&run_cloak(root.path(), &["label", ".idea", "--clear"]));
+    let status = run_cloak(root.path(), &["status"]);
+    assert!(!output_text(&status).contains("IDE files"));
+
+    let unknown = run_cloak(root.path(), &["label", ".vscode", "nope"]);
+    assert_eq!(unknown.status.code(), Some(2), "{}", output_text(&unknown));
+}
+
+#[test]
+fn status_group_lists_targets_under_their_category() {
+    let root = TempDir::new("status-group");
+    for dir in [".claude", ".cursor", ".vscode", ".mytool"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".claude", ".cursor", ".vscode", ".mytool"],
+    ));
+
+    let out = run_cloak(root.path(), &["status", "--group"]);
+    assert_success(&out);
+    let text = String::from_utf8_lossy(&out.stdout);
+    let expected = "Hidden configs:\n  AI IDEs / Editors\n    .cursor [linked]\n    .vscode [linked]\n  \
+                    AI coding agents\n    .claude [linked]\n  Other\n    .mytool [linked]\n";
+    assert!(text.contains(expected), "unexpected grouping:\n{text}");
+}
+
+#[test]
+fn no_gitignore_leaves_gitignore_untouched() {
+    let root = TempDir::new("no-gitignore");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    let gitignore = root.path().join(".gitignore");
+    fs::write(&gitignore, "node_modules/\n").expect("failed to write .gitignore");
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--no-gitignore", ".cursor"],
+    ));
+    assert!(
+        root.path()
+            .join(".cloak")
+            .join("storage")
+            .join(".cursor")
+            .is_dir()
+    );
+    assert_eq!(
+        fs::read_to_string(&gitignore).expect("failed to read .gitignore"),
+        "node_modules/\n"
+    );
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["unhide", "--no-gitignore", ".cursor"],
+    ));
+    assert!(root.path().join(".cursor").is_dir());
+    assert_eq!(
+        fs::read_to_string(&gitignore).expect("failed to read .gitignore"),
+        "node_modules/\n"
+    );
+
+    // The same behavior can be made the project default.
+    fs::write(
+        root.path().join(".cloak").join("cloak.toml"),
+        "gitignore = false\n",
+    )
+    .expect("failed to write cloak.toml");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    assert_eq!(
+        fs::read_to_string(&gitignore).expect("failed to read .gitignore"),
+        "node_modules/\n"
+    );
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+}
+
+#[test]
+fn hide_verify_passes_for_a_normal_hide() {
+    let root = TempDir::new("hide-verify");
+    let cursor = root.path().join(".cursor");
+    fs::create_dir_all(cursor.join("rules")).expect("failed to create .cursor");
+    fs::write(cursor.join("rules").join("a.md"), "rule").expect("failed to write rule");
+    fs::write(root.path().join(".env"), "TOKEN=1\n").expect("failed to write .env");
+
+    let out = run_cloak(root.path(), &["hide", "--verify", ".cursor", ".env"]);
+    assert_success(&out);
+    assert!(output_text(&out).contains("verified link and content"));
+    assert_eq!(
+        fs::read_to_string(root.path().join(".env")).expect("failed to read through link"),
+        "TOKEN=1\n"
+    );
+    assert!(
+        root.path()
+            .join(".cursor")
+            .symlink_metadata()
+            .expect("missing ghost link")
+            .file_type()
+            .is_symlink()
+    );
+}
+
+#[test]
+fn hide_atomic_rolls_back_every_target_when_one_fails() {
+    let root = TempDir::new("hide-atomic");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".cursor").join("mcp.json"), "{}").expect("failed to write file");
+    fs::create_dir_all(root.path().join(".idea")).expect("failed to create .idea");
+    fs::write(root.path().join(".idea").join("workspace.xml"), "<x/>")
+        .expect("failed to write file");
+    fs::create_dir_all(root.path().join(".vscode")).expect("failed to create .vscode");
+    fs::write(
+        root.path().join(".vscode").join("settings.json"),
+        "{\n  \"editor.tabSize\": 2\n}\n",
+    )
+    .expect("failed to write settings");
+    assert_success(&run_cloak(root.path(), &["init"]));
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).expect("missing .gitignore");
+    // `init` adds its own `.cloak` exclude; the failed hide must leave that as it was.
+    let settings_path = root.path().join(".vscode").join("settings.json");
+    let settings = fs::read_to_string(&settings_path).expect("missing settings.json");
+    let cursor_settings = fs::read_to_string(root.path().join(".cursor").join("settings.json"))
+        .expect("missing .cursor settings.json");
+
+    let out = run_cloak(
+        root.path(),
+        &["hide", "--atomic", ".cursor", ".idea", ".missing"],
+    );
+    assert!(!out.status.success());
+    assert!(output_text(&out).contains("Rolling back"));
+
+    for target in [".cursor", ".idea"] {
+        let path = root.path().join(target);
+        let meta = path
+            .symlink_metadata()
+            .expect("target missing after rollback");
+        assert!(meta.is_dir(), "{target} should be a real directory again");
+        assert!(!root.path().join(".cloak/storage").join(target).exists());
+    }
+    assert_eq!(
+        fs::read_to_string(root.path().join(".cursor").join("mcp.json")).expect("read failed"),
+        "{}"
+    );
+    assert_eq!(
+        fs::read_to_string(root.path().join(".gitignore")).expect("read failed"),
+        gitignore
+    );
+    assert_eq!(
+        fs::read_to_string(&settings_path).expect("read failed"),
+        settings
+    );
+    assert_eq!(
+        fs::read_to_string(root.path().join(".cursor").join("settings.json")).expect("read failed"),
+        cursor_settings
+    );
+    assert!(
+        !fs::read_to_string(root.path().join(".cloak").join("meta.json"))
+            .unwrap_or_default()
+            .contains(".cursor")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn after_command_runs_once_in_root_after_a_successful_batch() {
+    let root = TempDir::new("hide-after");
+    for dir in [".cursor", ".idea"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+
+    let out = run_cloak(
+        root.path(),
+        &[
+            "hide",
+            ".cursor",
+            ".idea",
+            "--after",
+            "echo run >> after.log",
+        ],
+    );
+    assert_success(&out);
+    assert_eq!(
+        fs::read_to_string(root.path().join("after.log")).expect("after command did not run"),
+        "run\n"
+    );
+
+    // A failed batch skips the command unless --after-always is given.
+    let failed = run_cloak(
+        root.path(),
+        &[
+            "unhide",
+            ".cursor",
+            ".missing",
+            "--after",
+            "echo run >> after.log",
+        ],
+    );
+    assert!(!failed.status.success());
+    assert_eq!(
+        fs::read_to_string(root.path().join("after.log")).expect("read failed"),
+        "run\n"
+    );
+
+    let always = run_cloak(
+        root.path(),
+        &[
+            "unhide",
+            ".idea",
+            ".missing",
+            "--after",
+            "touch always",
+            "--after-always",
+        ],
+    );
+    assert!(!always.status.success());
+    assert!(root.path().join("always").exists());
+}
+
+#[test]
+fn hide_stdin0_reads_nul_separated_targets() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let root = TempDir::new("hide-stdin0");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".my notes"), "spaced").expect("failed to write file");
+
+    let mut child = Command::new(cloak_bin())
+        .arg("--root")
+        .arg(root.path())
+        .args(["hide", "--stdin0"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn cloak");
+    child
+        .stdin
+        .take()
+        .expect("stdin not piped")
+        .write_all(b"./.cursor\0.my notes\0")
+        .expect("failed to write stdin");
+    let out = child.wait_with_output().expect("failed to wait for cloak");
+    assert_success(&out);
+
+    let storage = root.path().join(".cloak").join("storage");
+    assert!(storage.join(".cursor").is_dir());
+    assert_eq!(
+        fs::read_to_string(storage.join(".my notes")).expect("spaced target not stored"),
+        "spaced"
+    );
+    assert!(
+        root.path()
+            .join(".my notes")
+            .symlink_metadata()
+            .expect("missing ghost link")
+            .file_type()
+            .is_symlink()
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn hide_refuses_fifo_without_touching_it() {
+    use std::os::unix::fs::FileTypeExt;
+
+    let root = TempDir::new("hide-fifo");
+    let fifo = root.path().join(".pipe");
+    let made = Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .expect("failed to run mkfifo");
+    assert!(made.success(), "mkfifo failed");
+
+    let out = run_cloak(root.path(), &["hide", "--verify", ".pipe"]);
+    assert_eq!(out.status.code(), Some(2), "{}", output_text(&out));
+    assert!(output_text(&out).contains("refusing to hide a FIFO"));
+
+    let meta = fifo.symlink_metadata().expect("FIFO was moved");
+    assert!(meta.file_type().is_fifo());
+    assert!(!root.path().join(".cloak/storage/.pipe").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn repair_normalize_makes_every_link_relative() {
+    use std::os::unix::fs::symlink;
+
+    let root = TempDir::new("repair-normalize");
+    for dir in [".cursor", ".idea", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    fs::write(root.path().join(".env"), "TOKEN=1\n").expect("failed to write .env");
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".idea", ".claude", ".env"],
+    ));
+    let storage = root.path().join(".cloak").join("storage");
+
+    // .cursor stays relative; .idea becomes absolute; .env loses its link;
+    // .claude dangles at where the project used to live.
+    remove_path_entry(&root.path().join(".idea"));
+    symlink(storage.join(".idea"), root.path().join(".idea")).expect("symlink failed");
+    remove_path_entry(&root.path().join(".env"));
+    remove_path_entry(&root.path().join(".claude"));
+    symlink(
+        "/nonexistent/old-home/project/.cloak/storage/.claude",
+        root.path().join(".claude"),
+    )
+    .expect("symlink failed");
+
+    let plain = run_cloak(root.path(), &["repair"]);
+    assert_success(&plain);
+    assert!(
+        fs::read_link(root.path().join(".idea"))
+            .expect("missing .idea link")
+            .is_absolute(),
+        "plain repair should leave a working absolute link alone"
+    );
+
+    let out = run_cloak(root.path(), &["repair", "--normalize"]);
+    assert_success(&out);
+    let text = output_text(&out);
+    assert!(text.contains(".cursor untouched"), "{text}");
+    assert!(text.contains(".idea recreated (absolute link)"), "{text}");
+
+    for target in [".cursor", ".idea", ".claude", ".env"] {
+        assert_eq!(
+            fs::read_link(root.path().join(target)).expect("missing ghost link"),
+            Path::new(".cloak").join("storage").join(target),
+            "{target} should be a canonical relative link"
+        );
+    }
+    assert_eq!(
+        fs::read_to_string(root.path().join(".env")).expect("read through link failed"),
+        "TOKEN=1\n"
+    );
+}
+
+#[test]
+fn storage_verify_flags_empty_unlinked_entry() {
+    let root = TempDir::new("storage-verify");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".cursor").join("mcp.json"), "{}").expect("failed to write file");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    assert_success(&run_cloak(root.path(), &["storage", "verify"]));
+
+    // What an interrupted cross-device move of .claude might leave behind.
+    fs::create_dir_all(root.path().join(".cloak").join("storage").join(".claude"))
+        .expect("failed to create storage entry");
+
+    let out = run_cloak(root.path(), &["storage", "verify"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        text.contains(".claude — empty and unlinked"),
+        "unexpected output:\n{text}"
+    );
+    assert!(!text.contains(".cursor"), "unexpected output:\n{text}");
+}
+
+#[test]
+fn unhide_diff_storage_reports_changes_since_last_commit() {
+    let root = TempDir::new("unhide-diff-storage");
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(root.path())
+            .args([
+                "-c",
+                "user.name=cloak",
+                "-c",
+                "user.email=cloak@example.com",
+            ])
+            .args(args)
+            .output()
+    };
+    if !git(&["init", "-q"]).is_ok_and(|o| o.status.success()) {
+        return;
+    }
+
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".cursor").join("mcp.json"), "{}").expect("failed to write file");
+    fs::create_dir_all(root.path().join(".idea")).expect("failed to create .idea");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".idea"]));
+    fs::write(root.path().join(".idea").join("misc.xml"), "<x/>").expect("failed to write file");
+    let committed = git(&["add", "-A"])
+        .and_then(|_| git(&["commit", "-q", "-m", "hide configs"]))
+        .expect("git commit failed");
+    assert!(committed.status.success(), "{}", output_text(&committed));
+
+    // A tool writes through the ghost link after the commit.
+    fs::write(
+        root.path().join(".cursor").join("mcp.json"),
+        "{\"servers\": []}",
+    )
+    .expect("failed to write through link");
+
+    let out = run_cloak(
+        root.path(),
+        &["unhide", "--diff-storage", ".cursor", ".idea"],
+    );
+    assert_success(&out);
+    let text = output_text(&out);
+    assert!(
+        text.contains("storage content differs from the last commit (1 path(s)):"),
+        "{text}"
+    );
+    assert!(text.contains("M .cloak/storage/.cursor/mcp.json"), "{text}");
+    assert!(
+        text.contains("storage content matches the last commit"),
+        "{text}"
+    );
+}
+
+#[test]
+fn unhide_force_backs_up_conflicting_root_dir() {
+    let root = TempDir::new("unhide-force");
+    let cursor = root.path().join(".cursor");
+    fs::create_dir_all(&cursor).expect("failed to create .cursor");
+    fs::write(cursor.join("stored"), "from storage").expect("failed to write file");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    // A tool replaced the ghost link with its own directory.
+    fs::remove_file(&cursor).expect("failed to remove ghost link");
+    fs::create_dir_all(&cursor).expect("failed to recreate .cursor");
+    fs::write(cursor.join("local"), "from root").expect("failed to write file");
+
+    let refused = run_cloak(root.path(), &["unhide", ".cursor"]);
+    assert!(!refused.status.success());
+    assert!(output_text(&refused).contains("--force"));
+    assert!(
+        cursor.join("local").exists(),
+        "refused unhide must not touch root"
+    );
+
+    assert_success(&run_cloak(root.path(), &["unhide", "--force", ".cursor"]));
+    assert_eq!(
+        fs::read_to_string(cursor.join("stored")).expect("restore failed"),
+        "from storage"
+    );
+    assert_eq!(
+        fs::read_to_string(root.path().join(".cursor.cloak-bak").join("local"))
+            .expect("backup missing"),
+        "from root"
+    );
+    assert!(
+        !root
+            .path()
+            .join(".cloak")
+            .join("storage")
+            .join(".cursor")
+            .exists()
+    );
+}
+
+#[test]
+fn exit_codes_distinguish_validation_and_missing_storage() {
+    let root = TempDir::new("exit-codes");
+
+    let invalid = run_cloak(root.path(), &["hide", "../outside"]);
+    assert_eq!(invalid.status.code(), Some(2), "{}", output_text(&invalid));
+    assert!(output_text(&invalid).contains("path traversal is not allowed"));
+
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    let uninitialized = run_cloak(root.path(), &["hide", "--assume-initialized", ".cursor"]);
+    assert_eq!(
+        uninitialized.status.code(),
+        Some(3),
+        "{}",
+        output_text(&uninitialized)
+    );
+}
+
+#[test]
+fn hide_link_name_links_under_custom_root_name() {
+    let root = TempDir::new("link-name");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".cursor").join("rules"), "r").expect("failed to write rules");
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", "--link-name", ".cursor-active"],
+    ));
+    let link = root.path().join(".cursor-active");
+    assert!(
+        link.symlink_metadata()
+            .expect("missing ghost link")
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(
+        fs::read_to_string(link.join("rules")).expect("link does not resolve"),
+        "r"
+    );
+    assert!(root.path().join(".cursor").symlink_metadata().is_err());
+    assert!(root.path().join(".cloak/storage/.cursor").is_dir());
+    let gitignore =
+        fs::read_to_string(root.path().join(".gitignore")).expect("failed to read .gitignore");
+    assert!(
+        gitignore.lines().any(|l| l == "/.cursor-active"),
+        "{gitignore}"
+    );
+
+    let status = run_cloak(root.path(), &["status"]);
+    let text = String::from_utf8_lossy(&status.stdout);
+    assert!(
+        text.contains(".cursor [linked] via .cursor-active"),
+        "{text}"
+    );
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+
+    assert_success(&run_cloak(root.path(), &["unhide", ".cursor"]));
+    assert!(root.path().join(".cursor").join("rules").is_file());
+    assert!(link.symlink_metadata().is_err());
+    let gitignore =
+        fs::read_to_string(root.path().join(".gitignore")).expect("failed to read .gitignore");
+    assert!(!gitignore.contains(".cursor-active"), "{gitignore}");
+
+    let many = run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".idea", "--link-name", ".x"],
+    );
+    assert_eq!(many.status.code(), Some(2));
+}
+
+#[cfg(unix)]
+#[test]
+fn hide_nested_target_keeps_its_path_in_storage() {
+    let root = TempDir::new("nested");
+    fs::create_dir_all(root.path().join("config/ai")).expect("failed to create config/ai");
+    fs::write(root.path().join("config/ai/rules.md"), "be brief").expect("failed to write");
+    fs::write(root.path().join("config/app.toml"), "").expect("failed to write");
+
+    assert_success(&run_cloak(root.path(), &["hide", "config/ai"]));
+    let storage = root.path().join(".cloak/storage");
+    assert_eq!(
+        fs::read_to_string(storage.join("config/ai/rules.md")).expect("missing nested entry"),
+        "be brief"
+    );
+    let link = root.path().join("config/ai");
+    assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+    assert_eq!(
+        fs::read_link(&link).unwrap(),
+        Path::new("../.cloak/storage/config/ai")
+    );
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).unwrap();
+    assert!(gitignore.lines().any(|l| l == "/config/ai"), "{gitignore}");
+    let text = output_text(&run_cloak(root.path(), &["status"]));
+    assert!(text.contains("config/ai [linked]"), "{text}");
+
+    let inside = run_cloak(root.path(), &["hide", "config/ai/rules.md"]);
+    assert_eq!(inside.status.code(), Some(2), "{}", output_text(&inside));
+    let traversal = run_cloak(root.path(), &["hide", "config/../../x"]);
+    assert_eq!(
+        traversal.status.code(),
+        Some(2),
+        "{}",
+        output_text(&traversal)
+    );
+
+    assert_success(&run_cloak(root.path(), &["unhide", "config/ai"]));
+    assert!(link.symlink_metadata().unwrap().is_dir());
+    assert!(
+        !storage.join("config").exists(),
+        "empty parent left in storage"
+    );
+}
+
+#[test]
+fn hide_flatten_stores_nested_target_under_flat_key() {
+    let root = TempDir::new("flatten");
+    fs::create_dir_all(root.path().join("a/b")).expect("failed to create a/b");
+    fs::write(root.path().join("a/b/.env"), "TOKEN=1\n").expect("failed to write .env");
+
+    assert_success(&run_cloak(root.path(), &["hide", "--flatten", "a/b/.env"]));
+    let storage = root.path().join(".cloak/storage");
+    assert_eq!(
+        fs::read_to_string(storage.join("a__b__.env")).expect("missing flat storage key"),
+        "TOKEN=1\n"
+    );
+    assert!(!storage.join("a").exists());
+    let link = root.path().join("a/b/.env");
+    assert!(
+        link.symlink_metadata()
+            .expect("missing ghost link")
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(
+        fs::read_to_string(&link).expect("link does not resolve"),
+        "TOKEN=1\n"
+    );
+    let text = output_text(&run_cloak(root.path(), &["status"]));
+    assert!(text.contains("a__b__.env [linked] via a/b/.env"), "{text}");
+
+    assert_success(&run_cloak(root.path(), &["unhide", "a/b/.env"]));
+    let restored = root
+        .path()
+        .join("a/b/.env")
+        .symlink_metadata()
+        .expect("nested path was not restored");
+    assert!(restored.is_file());
+    assert_eq!(
+        fs::read_to_string(root.path().join("a/b/.env")).expect("failed to read restored"),
+        "TOKEN=1\n"
+    );
+    assert!(storage.join("a__b__.env").symlink_metadata().is_err());
+    let meta = fs::read_to_string(root.path().join(".cloak/meta.json")).expect("missing meta");
+    assert!(!meta.contains("a__b__.env"), "{meta}");
+}
+
+#[test]
+fn hide_restores_target_when_link_creation_fails() {
+    let root = TempDir::new("hide-link-fails");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".cursor/rules"), "r\n").expect("failed to write rules");
+    // Occupies the link path, so the link cannot be made once .cursor is in storage.
+    fs::write(root.path().join(".cursor-active"), "mine\n").expect("failed to write file");
+
+    let out = run_cloak(
+        root.path(),
+        &["hide", ".cursor", "--link-name", ".cursor-active"],
+    );
+    assert_eq!(out.status.code(), Some(4), "{}", output_text(&out));
+    let text = output_text(&out);
+    assert!(text.contains("rolled back"), "{text}");
+    assert!(text.contains("path already exists"), "{text}");
+
+    assert_eq!(
+        fs::read_to_string(root.path().join(".cursor/rules")).expect(".cursor was not restored"),
+        "r\n"
+    );
+    assert!(
+        !root
+            .path()
+            .join(".cursor")
+            .symlink_metadata()
+            .expect("missing .cursor")
+            .file_type()
+            .is_symlink()
+    );
+    assert_eq!(
+        fs::read_to_string(root.path().join(".cursor-active")).expect("conflicting file gone"),
+        "mine\n"
+    );
+    assert!(!root.path().join(".cloak/storage/.cursor").exists());
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).unwrap_or_default();
+    assert!(!gitignore.contains(".cursor"), "{gitignore}");
+}
+
+#[test]
+fn commands_refuse_a_root_inside_cloak_storage() {
+    let root = TempDir::new("inside-cloak");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    let storage = root.path().join(".cloak").join("storage");
+    for args in [&["status"][..], &["hide", ".cursor"][..]] {
+        let out = run_cloak(&storage, args);
+        assert_eq!(out.status.code(), Some(2), "{}", output_text(&out));
+        assert!(
+            output_text(&out).contains("inside a cloak storage directory"),
+            "{}",
+            output_text(&out)
+        );
+    }
+    assert!(
+        !storage.join(".cloak").exists(),
+        "no nested .cloak may be created"
+    );
+    assert_success(&run_cloak(root.path(), &["status"]));
+}
+
+#[test]
+fn hide_excludes_target_in_jetbrains_module_and_unhide_restores_it() {
+    let root = TempDir::new("jetbrains");
+    let idea = root.path().join(".idea");
+    fs::create_dir_all(&idea).expect("failed to create .idea");
+    fs::write(
+        idea.join("modules.xml"),
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<project version=\"4\">\n  \
+         <component name=\"ProjectModuleManager\">\n    <modules>\n      \
+         <module fileurl=\"file://$PROJECT_DIR$/.idea/demo.iml\" \
+         filepath=\"$PROJECT_DIR$/.idea/demo.iml\" />\n    </modules>\n  </component>\n</project>\n",
+    )
+    .expect("failed to write modules.xml");
+    let module = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<module type=\"WEB_MODULE\" version=\"4\">\n  \
+                  <component name=\"NewModuleRootManager\">\n    \
+                  <content url=\"file://$MODULE_DIR$\" />\n    \
+                  <orderEntry type=\"sourceFolder\" forTests=\"false\" />\n  </component>\n</module>\n";
+    fs::write(idea.join("demo.iml"), module).expect("failed to write demo.iml");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    let hidden = fs::read_to_string(idea.join("demo.iml")).expect("failed to read demo.iml");
+    assert!(
+        hidden.contains("<excludeFolder url=\"file://$MODULE_DIR$/.cursor\" />"),
+        "{hidden}"
+    );
+
+    let out = run_cloak(root.path(), &["info", ".cursor", "--json"]);
+    assert_success(&out);
+    let info: serde_json::Value =
+        serde_json::from_slice(&out.stdout).expect("info output is not JSON");
+    assert_eq!(info["ide_excludes"][".idea"], true);
+
+    assert_success(&run_cloak(root.path(), &["unhide", ".cursor"]));
+    assert_eq!(
+        fs::read_to_string(idea.join("demo.iml")).expect("failed to read demo.iml"),
+        module
+    );
+}
+
+#[test]
+fn prune_lists_unlinked_entries_and_deletes_or_restores_only_when_asked() {
+    let root = TempDir::new("prune");
+    for target in [".cursor", ".claude", ".codex"] {
+        fs::create_dir_all(root.path().join(target)).expect("failed to create target");
+        fs::write(root.path().join(target).join("cfg"), target).expect("failed to write cfg");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".claude", ".codex"],
+    ));
+    remove_path_entry(&root.path().join(".cursor"));
+    remove_path_entry(&root.path().join(".claude"));
+    let storage = root.path().join(".cloak").join("storage");
+
+    let out = run_cloak(root.path(), &["prune"]);
+    assert_success(&out);
+    let text = output_text(&out);
+    assert!(
+        text.contains(".cloak/storage/.cursor") && text.contains(".cloak/storage/.claude"),
+        "{text}"
+    );
+    assert!(!text.contains(".cloak/storage/.codex"), "{text}");
+    assert!(storage.join(".cursor").exists() && storage.join(".claude").exists());
+
+    let out = run_cloak(root.path(), &["prune", "--restore", "--yes", "--delete"]);
+    assert_eq!(out.status.code(), Some(2), "{}", output_text(&out));
+
+    assert_success(&run_cloak(root.path(), &["prune", "--restore", "--yes"]));
+    for target in [".cursor", ".claude"] {
+        let restored = root.path().join(target);
+        assert!(!restored.is_symlink() && restored.is_dir(), "{target}");
+        assert_eq!(
+            fs::read_to_string(restored.join("cfg")).expect("failed to read cfg"),
+            target
+        );
+        assert!(!storage.join(target).exists(), "{target}");
+    }
+
+    remove_path_entry(&root.path().join(".codex"));
+    assert_success(&run_cloak(root.path(), &["prune", "--delete", "--yes"]));
+    assert!(!storage.join(".codex").exists());
+    assert!(!root.path().join(".codex").exists());
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).unwrap_or_default();
+    assert!(!gitignore.contains(".codex"), "{gitignore}");
+
+    let out = run_cloak(root.path(), &["prune"]);
+    assert_success(&out);
+    assert!(output_text(&out).contains("No orphaned storage entries."));
+}
+
+#[test]
+fn mutating_commands_refuse_to_run_while_another_holds_the_lock() {
+    let root = TempDir::new("lock");
+    assert_success(&run_cloak(root.path(), &["init"]));
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+
+    let lock = fs::File::options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(root.path().join(".cloak").join(".lock"))
+        .expect("failed to open lock file");
+    lock.try_lock().expect("failed to take the lock");
+
+    let out = run_cloak(root.path(), &["hide", ".cursor"]);
+    assert_eq!(out.status.code(), Some(4), "{}", output_text(&out));
+    assert!(
+        output_text(&out).contains("another cloak process"),
+        "{}",
+        output_text(&out)
+    );
+    assert!(!root.path().join(".cursor").is_symlink());
+    assert_success(&run_cloak(root.path(), &["status"]));
+
+    drop(lock);
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    assert!(root.path().join(".cursor").is_symlink());
+}
+
+#[test]
+fn quiet_silences_progress_and_verbose_logs_each_step() {
+    let root = TempDir::new("output-level");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::create_dir_all(root.path().join(".vscode")).expect("failed to create .vscode");
+
+    let output = run_cloak(root.path(), &["--quiet", "hide", ".cursor"]);
+    assert_success(&output);
+    assert!(output.stdout.is_empty(), "{}", output_text(&output));
+    assert!(output.stderr.is_empty(), "{}", output_text(&output));
+
+    let output = run_cloak(root.path(), &["-q", "list"]);
+    assert_success(&output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), ".cursor\n");
+
+    let output = run_cloak(root.path(), &["-q", "unhide", ".missing"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty(), "{}", output_text(&output));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Error:"),
+        "{}",
+        output_text(&output)
+    );
+
+    let output = run_cloak(root.path(), &["--verbose", "unhide", ".cursor"]);
+    assert_success(&output);
+    let log = String::from_utf8_lossy(&output.stderr);
+    for step in [
+        "remove_ghost_link",
+        "egest",
+        "gitignore: remove /.cursor",
+        "ide: drop **/.cursor",
+    ] {
+        assert!(log.contains(step), "missing `{step}` in:\n{log}");
+    }
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("[cloak]"));
+
+    let output = run_cloak(root.path(), &["-q", "-v", "status"]);
+    assert_eq!(output.status.code(), Some(2), "{}", output_text(&output));
+}
+
+#[test]
+fn no_color_flag_and_env_strip_ansi_even_when_colors_are_forced() {
+    let root = TempDir::new("no-color");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    let status = |args: &[&str], no_color: Option<&str>| {
+        let mut cmd = Command::new(cloak_bin());
+        cmd.arg("--root")
+            .arg(root.path())
+            .arg("status")
+            .args(args)
+            .env("CLICOLOR_FORCE", "1")
+            .env_remove("NO_COLOR");
+        if let Some(value) = no_color {
+            cmd.env("NO_COLOR", value);
+        }
+        let output = cmd.output().expect("failed to execute cloak");
+        assert_success(&output);
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    assert!(
+        status(&[], None).contains('\u{1b}'),
+        "colors were not forced"
+    );
+    assert!(
+        status(&[], Some("")).contains('\u{1b}'),
+        "empty NO_COLOR must be ignored"
+    );
+    for text in [status(&["--no-color"], None), status(&[], Some("1"))] {
+        assert!(!text.contains('\u{1b}'), "{text:?}");
+        assert!(text.contains(".cursor"), "{text}");
+    }
+}
+
+#[test]
+fn gitignore_is_left_alone_outside_a_git_repository_unless_git_is_passed() {
+    let root = TempDir::without_git("no-git-repo");
+    let gitignore = root.path().join(".gitignore");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::create_dir_all(root.path().join(".claude")).expect("failed to create .claude");
+
+    assert_success(&run_cloak(root.path(), &["init"]));
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    assert!(!gitignore.exists(), "no repository, so no .gitignore");
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+
+    assert_success(&run_cloak(root.path(), &["hide", "--git", ".claude"]));
+    let content = fs::read_to_string(&gitignore).expect("--git should write .gitignore");
+    assert!(content.contains("/.claude"), "{content}");
+    assert!(!content.contains("/.cursor"), "{content}");
+
+    assert_success(&run_cloak(root.path(), &["unhide", "--git", ".claude"]));
+    let content = fs::read_to_string(&gitignore).expect("read failed");
+    assert!(!content.contains("/.claude"), "{content}");
+    assert_success(&run_cloak(root.path(), &["unhide", ".cursor"]));
+
+    // A project nested below the repository's top level is still inside it.
+    let repo = TempDir::new("git-repo-nested");
+    let nested = repo.path().join("packages").join("app");
+    fs::create_dir_all(nested.join(".cursor")).expect("failed to create nested .cursor");
+    assert_success(&run_cloak(&nested, &["hide", ".cursor"]));
+    let content = fs::read_to_string(nested.join(".gitignore")).expect("read failed");
+    assert!(content.contains("/.cursor"), "{content}");
+}
+
+#[test]
+fn local_ignore_keeps_rules_out_of_the_shared_gitignore() {
+    let root = TempDir::new("local-ignore");
+    let exclude = root.path().join(".git").join("info").join("exclude");
+    let gitignore = root.path().join(".gitignore");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::create_dir_all(root.path().join(".claude")).expect("failed to create .claude");
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--local-ignore", ".cursor"],
+    ));
+    let local = fs::read_to_string(&exclude).expect("exclude file not written");
+    assert!(local.contains("# >>> cloak managed\n/.cursor\n"), "{local}");
+    let shared = fs::read_to_string(&gitignore).expect("read .gitignore failed");
+    assert!(!shared.contains("/.cursor"), "{shared}");
+
+    // The config makes it the default; doctor and info see rules in either file.
+    fs::write(
+        root.path().join(".cloak").join("cloak.toml"),
+        "local_ignore = true\n",
+    )
+    .expect("failed to write cloak.toml");
+    assert_success(&run_cloak(root.path(), &["hide", ".claude"]));
+    let local = fs::read_to_string(&exclude).expect("read exclude failed");
+    assert!(local.contains("/.claude"), "{local}");
+    assert!(!fs::read_to_string(&gitignore).unwrap().contains("/.claude"));
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+    let info = run_cloak(root.path(), &["info", ".cursor", "--json"]);
+    assert_success(&info);
+    assert!(
+        String::from_utf8_lossy(&info.stdout).contains("\"gitignore_entry\": true"),
+        "{}",
+        output_text(&info)
+    );
+
+    assert_success(&run_cloak(root.path(), &["unhide", ".cursor", ".claude"]));
+    let local = fs::read_to_string(&exclude).expect("read exclude failed");
+    assert!(!local.contains("cloak managed"), "{local}");
+}
+
+#[test]
+fn export_bundles_every_storage_entry_and_warns_about_broken_links() {
+    let root = TempDir::new("export");
+    fs::create_dir_all(root.path().join(".cursor").join("rules")).expect("mkdir failed");
+    fs::write(
+        root.path().join(".cursor").join("rules").join("a.md"),
+        "rule",
+    )
+    .expect("write failed");
+    fs::write(root.path().join(".env.local"), "KEY=1").expect("write failed");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".env.local"]));
+    remove_path_entry(&root.path().join(".env.local"));
+
+    let bundle = root.path().join("configs.tar.gz");
+    let output = run_cloak(root.path(), &["export", bundle.to_str().unwrap()]);
+    assert_success(&output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("the ghost link .env.local is missing; exporting .env.local"),
+        "{stderr}"
+    );
+
+    let file = fs::File::open(&bundle).expect("bundle not written");
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let mut paths = Vec::new();
+    let mut manifest = String::new();
+    for entry in archive.entries().expect("failed to read bundle") {
+        let mut entry = entry.expect("bad bundle entry");
+        let path = entry.path().unwrap().to_string_lossy().into_owned();
+        if path == "cloak-bundle.json" {
+            std::io::Read::read_to_string(&mut entry, &mut manifest).expect("read failed");
+        }
+        paths.push(path.trim_end_matches('/').to_string());
+    }
+    assert_eq!(paths[0], "cloak-bundle.json");
+    for want in [".cursor", ".cursor/rules/a.md", ".env.local"] {
+        assert!(paths.iter().any(|p| p == want), "{want} missing: {paths:?}");
+    }
+    assert!(manifest.contains("\"link\": \".cursor\""), "{manifest}");
+}
+
+#[test]
+fn import_links_bundled_entries_and_skips_existing_root_paths() {
+    let source = TempDir::new("import-src");
+    fs::create_dir_all(source.path().join(".cursor")).expect("mkdir failed");
+    fs::write(source.path().join(".cursor").join("rules.md"), "rule").expect("write failed");
+    fs::write(source.path().join(".env.local"), "KEY=1").expect("write failed");
+    assert_success(&run_cloak(
+        source.path(),
+        &["hide", ".cursor", ".env.local"],
+    ));
+    let bundle = source.path().join("configs.tar.gz");
+    assert_success(&run_cloak(
+        source.path(),
+        &["export", bundle.to_str().unwrap()],
+    ));
+
+    let root = TempDir::new("import-dest");
+    fs::write(root.path().join(".env.local"), "MINE=1").expect("write failed");
+    let output = run_cloak(root.path(), &["import", bundle.to_str().unwrap()]);
+    assert_success(&output);
+    let text = output_text(&output);
+    assert!(
+        text.contains(".env.local (already exists at the root)"),
+        "{text}"
+    );
+
+    let link = root.path().join(".cursor");
+    assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+    assert_eq!(
+        fs::read_to_string(link.join("rules.md")).expect("link does not resolve"),
+        "rule"
+    );
+    assert_eq!(
+        fs::read_to_string(root.path().join(".env.local")).unwrap(),
+        "MINE=1"
+    );
+    assert!(
+        root.path()
+            .join(".cloak/storage/.env.local")
+            .symlink_metadata()
+            .is_err()
+    );
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).unwrap();
+    assert!(gitignore.lines().any(|l| l == "/.cursor"), "{gitignore}");
+    let leftovers: Vec<_> = fs::read_dir(root.path().join(".cloak"))
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(".import-"))
+        .collect();
+    assert!(leftovers.is_empty(), "staging dir left behind");
+
+    let output = run_cloak(root.path(), &["import", bundle.to_str().unwrap()]);
+    assert_success(&output);
+    assert!(output_text(&output).contains(".cursor (already exists at the root)"));
+}
+
+#[test]
+fn tidy_except_and_tidy_exclude_leave_configs_visible_as_skipped() {
+    let root = TempDir::new("tidy-except");
+    for dir in [".vscode", ".idea", ".cursor", ".cloak"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create dir");
+    }
+    fs::write(
+        root.path().join(".cloak/cloak.toml"),
+        "tidy_exclude = [\".idea\"]\n",
+    )
+    .expect("failed to write cloak.toml");
+
+    let report = root.path().join("report.json");
+    let output = run_cloak(
+        root.path(),
+        &[
+            "tidy",
+            "--yes",
+            "--except",
+            ".vscode",
+            "--report",
+            report.to_str().unwrap(),
+        ],
+    );
+    assert_success(&output);
+    let text = output_text(&output);
+    assert!(text.contains("Skipping .vscode (excluded)"), "{text}");
+    assert!(text.contains("Skipping .idea (excluded)"), "{text}");
+
+    for name in [".vscode", ".idea"] {
+        let meta = root.path().join(name).symlink_metadata().unwrap();
+        assert!(meta.is_dir(), "{name} was hidden");
+    }
+    assert!(
+        root.path()
+            .join(".cursor")
+            .symlink_metadata()
+            .unwrap()
+            .file_type()
+            .is_symlink()
+    );
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(report["succeeded"], 1);
+    assert_eq!(report["skipped"], 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn hide_force_recreates_stale_ghost_links_but_never_real_files() {
+    use std::os::unix::fs::symlink;
+
+    let root = TempDir::new("hide-force");
+    fs::create_dir_all(root.path().join(".cursor")).expect("mkdir failed");
+    fs::write(root.path().join(".cursor/rules.md"), "rule").expect("write failed");
+    fs::create_dir_all(root.path().join(".claude")).expect("mkdir failed");
+    fs::write(root.path().join(".env"), "KEY=1").expect("write failed");
+    fs::create_dir_all(root.path().join("elsewhere")).expect("mkdir failed");
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".claude", ".env"],
+    ));
+
+    let cursor = root.path().join(".cursor");
+    remove_path_entry(&cursor);
+    symlink("elsewhere", &cursor).expect("symlink failed");
+    let claude = root.path().join(".claude");
+    remove_path_entry(&claude);
+    symlink(".cloak/storage/.gone", &claude).expect("symlink failed");
+
+    let output = run_cloak(root.path(), &["hide", ".cursor", ".claude"]);
+    assert_success(&output);
+    assert!(output_text(&output).contains(".cursor (already hidden)"));
+    assert!(fs::read_to_string(cursor.join("rules.md")).is_err());
+
+    let output = run_cloak(root.path(), &["hide", "--force", ".cursor", ".claude"]);
+    assert_success(&output);
+    let text = output_text(&output);
+    assert!(
+        text.contains("recreated the misdirected ghost link"),
+        "{text}"
+    );
+    assert!(text.contains("recreated the dangling ghost link"), "{text}");
+    assert_eq!(fs::read_to_string(cursor.join("rules.md")).unwrap(), "rule");
+    assert!(claude.is_dir());
+    assert!(root.path().join("elsewhere").is_dir());
+
+    let output = run_cloak(root.path(), &["hide", "--force", ".cursor"]);
+    assert_success(&output);
+    assert!(output_text(&output).contains(".cursor (already hidden)"));
+
+    let env = root.path().join(".env");
+    remove_path_entry(&env);
+    fs::write(&env, "MINE=1").expect("write failed");
+    assert!(
+        !run_cloak(root.path(), &["hide", "--force", ".env"])
+            .status
+            .success()
+    );
+    assert_eq!(fs::read_to_string(&env).unwrap(), "MINE=1");
+    assert_eq!(
+        fs::read_to_string(root.path().join(".cloak/storage/.env")).unwrap(),
+        "KEY=1"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn meta_records_original_mode_and_status_reports_entries_missing_from_storage() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = TempDir::new("meta-manifest");
+    let env = root.path().join(".env.local");
+    fs::write(&env, "KEY=1").expect("write failed");
+    fs::set_permissions(&env, fs::Permissions::from_mode(0o640)).expect("chmod failed");
+    fs::create_dir_all(root.path().join(".cursor")).expect("mkdir failed");
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--chmod", "600", ".env.local", ".cursor"],
+    ));
+
+    let info = run_cloak(root.path(), &["info", ".env.local", "--json"]);
+    assert_success(&info);
+    let info: serde_json::Value = serde_json::from_slice(&info.stdout).unwrap();
+    assert_eq!(info["original_mode"], "0640");
+    assert_eq!(info["chmod"], "0600");
+
+    fs::remove_dir_all(root.path().join(".cloak/storage/.cursor")).expect("rm failed");
+    let output = run_cloak(root.path(), &["status"]);
+    assert_success(&output);
+    let text = output_text(&output);
+    assert!(
+        text.contains("Recorded as hidden but missing from storage:\n  .cursor"),
+        "{text}"
+    );
+
+    let output = run_cloak(root.path(), &["status", "--json"]);
+    assert_success(&output);
+    let items: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let items = items.as_array().unwrap();
+    let cursor: Vec<_> = items.iter().filter(|i| i["name"] == ".cursor").collect();
+    assert_eq!(cursor.len(), 1, "{items:?}");
+    assert_eq!(cursor[0]["storage_exists"], false);
+    assert_eq!(cursor[0]["recorded"], true);
+    assert_eq!(cursor[0]["link_status"], "broken");
+}
+
+#[test]
+fn status_format_json_has_a_stable_schema_and_table_stays_the_default() {
+    let root = TempDir::new("status-format");
+    for dir in [".cursor", ".idea"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".idea"]));
+    fs::remove_dir_all(root.path().join(".cloak/storage/.idea")).expect("failed to remove");
+
+    let out = run_cloak(root.path(), &["status", "--format", "json"]);
+    assert_success(&out);
+    let items: serde_json::Value = serde_json::from_slice(&out.stdout).expect("not JSON");
+    let keys = [
+        "name",
+        "link",
+        "link_path",
+        "link_status",
+        "link_target",
+        "storage_path",
+        "storage_exists",
+        "recorded",
+        "broken",
+        "orphaned",
+    ];
+    for item in items.as_array().unwrap() {
+        let object = item.as_object().unwrap();
+        assert_eq!(object.len(), keys.len(), "{item}");
+        for key in keys {
+            assert!(object.contains_key(key), "{key} missing from {item}");
+        }
+    }
+    let find = |name: &str| {
+        items
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["name"] == name)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(find(".cursor")["broken"], false);
+    assert_eq!(find(".cursor")["orphaned"], false);
+    assert_eq!(find(".idea")["broken"], true);
+    assert_eq!(find(".idea")["orphaned"], true);
+    assert_eq!(
+        run_cloak(root.path(), &["status", "--json"]).stdout,
+        out.stdout
+    );
+
+    let table = run_cloak(root.path(), &["status", "--format", "table"]);
+    assert_success(&table);
+    assert_eq!(table.stdout, run_cloak(root.path(), &["status"]).stdout);
+    assert!(
+        !run_cloak(root.path(), &["status", "--format", "json", "--tree"])
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn dry_run_names_init_ide_ignore_and_env_edits() {
+    let root = TempDir::new("dry-run-edits");
+    fs::create_dir_all(root.path().join(".claude")).expect("mkdir failed");
+    fs::create_dir_all(root.path().join(".vscode")).expect("mkdir failed");
+    fs::write(root.path().join(".vscode/settings.json"), "{}\n").expect("write failed");
+
+    let preview = run_cloak(
+        root.path(),
+        &[
+            "hide",
+            "--dry-run",
+            "--env-detect",
+            "--local-ignore",
+            ".claude",
+        ],
+    );
+    assert_success(&preview);
+    let text = output_text(&preview);
+    for step in [
+        "would create .cloak/storage and .cloak/README.md",
+        "would add the .cloak rules to .gitignore",
+        "would add **/.claude to .vscode/settings.json",
+        "would add /.claude to .git/info/exclude",
+        "would export CLAUDE_CONFIG_DIR in .cloak/env",
+    ] {
+        assert!(text.contains(step), "missing `{step}`:\n{text}");
+    }
+    assert!(!root.path().join(".cloak").exists());
+
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--env-detect", "--local-ignore", ".claude"],
+    ));
+    let again = run_cloak(root.path(), &["hide", "--dry-run", "--force", ".claude"]);
+    assert!(
+        !output_text(&again).contains("would add"),
+        "{}",
+        output_text(&again)
+    );
+
+    let preview = run_cloak(root.path(), &["unhide", "--dry-run", ".claude"]);
+    assert_success(&preview);
+    let text = output_text(&preview);
+    for step in [
+        "would remove **/.claude from .vscode/settings.json",
+        "would remove /.claude from .git/info/exclude",
+        "would drop CLAUDE_CONFIG_DIR from .cloak/env",
+    ] {
+        assert!(text.contains(step), "missing `{step}`:\n{text}");
+    }
+    assert!(root.path().join(".cloak/env").exists());
+}
+
+#[test]
+fn unhide_all_ends_with_a_summary_and_skips_records_missing_from_storage() {
+    let root = TempDir::new("unhide-all-summary");
+    for dir in [".cursor", ".claude", ".idea"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".claude", ".idea"],
+    ));
+    fs::remove_dir_all(root.path().join(".cloak/storage/.idea")).expect("rm failed");
+    remove_path_entry(&root.path().join(".claude"));
+    fs::create_dir_all(root.path().join(".claude")).expect("failed to recreate .claude");
+
+    let out = run_cloak(root.path(), &["unhide", "--all"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = output_text(&out);
+    assert!(
+        text.contains(".idea (recorded in meta.json but missing from storage)"),
+        "{text}"
+    );
+    assert!(
+        text.contains("Summary: 1 restored, 1 skipped, 1 failed"),
+        "{text}"
+    );
+    assert!(root.path().join(".cursor").is_dir());
+}
+
+#[test]
+fn hide_known_hides_every_known_config_present_without_prompting() {
+    let root = TempDir::new("hide-known");
+    for dir in [".cursor", ".idea", ".vscode", ".mytool", ".cloak"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create dir");
+    }
+    fs::write(
+        root.path().join(".cloak/cloak.toml"),
+        "tidy_exclude = [\".vscode\"]\n",
+    )
+    .expect("failed to write cloak.toml");
+
+    assert_success(&run_cloak(root.path(), &["hide", "--known"]));
+    for name in [".cursor", ".idea"] {
+        let meta = root.path().join(name).symlink_metadata().unwrap();
+        assert!(meta.file_type().is_symlink(), "{name} was not hidden");
+    }
+    for name in [".vscode", ".mytool"] {
+        let meta = root.path().join(name).symlink_metadata().unwrap();
+        assert!(meta.is_dir(), "{name} was hidden");
+    }
+
+    let again = run_cloak(root.path(), &["hide", "--known"]);
+    assert_success(&again);
+    assert!(output_text(&again).contains("No known dotfiles/configs found to hide."));
+}
+
+#[test]
+fn relink_restores_links_ide_excludes_and_ignore_rules_after_a_clone() {
+    let root = TempDir::new("relink");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::create_dir_all(root.path().join(".vscode")).expect("failed to create .vscode");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+
+    // A fresh clone has the committed storage but none of the local state.
+    remove_path_entry(&root.path().join(".cursor"));
+    fs::remove_file(root.path().join(".gitignore")).expect("failed to remove .gitignore");
+    fs::remove_file(root.path().join(".vscode/settings.json"))
+        .expect("failed to remove settings.json");
+
+    let out = run_cloak(root.path(), &["relink"]);
+    assert_success(&out);
+    let text = output_text(&out);
+    assert!(text.contains(".cursor created"), "{text}");
+    assert!(text.contains("+ IDE excludes"), "{text}");
+    assert!(text.contains("+ ignore rule for /.cursor"), "{text}");
+
+    let link = root.path().join(".cursor").symlink_metadata().unwrap();
+    assert!(link.file_type().is_symlink());
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).unwrap();
+    assert!(gitignore.lines().any(|l| l == "/.cursor"), "{gitignore}");
+    assert!(gitignore.contains(".cloak/*"), "{gitignore}");
+    let settings = fs::read_to_string(root.path().join(".vscode/settings.json")).unwrap();
+    assert!(settings.contains(".cursor"), "{settings}");
+
+    let again = run_cloak(root.path(), &["relink"]);
+    assert_success(&again);
+    let text = output_text(&again);
+    assert!(text.contains(".cursor untouched"), "{text}");
+    assert!(!text.contains("+ "), "{text}");
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_reports_foreign_symlinks_and_missing_ide_excludes() {
+    let root = TempDir::new("doctor-foreign");
+    for dir in [".cursor", ".claude", ".vscode"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create dir");
+    }
+    let elsewhere = TempDir::without_git("doctor-foreign-elsewhere");
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".claude"]));
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+
+    remove_path_entry(&root.path().join(".cursor"));
+    std::os::unix::fs::symlink(elsewhere.path(), root.path().join(".cursor"))
+        .expect("symlink failed");
+    fs::write(root.path().join(".vscode/settings.json"), "{}\n").expect("failed to reset settings");
+
+    let out = run_cloak(root.path(), &["doctor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    let text = String::from_utf8_lossy(&out.stdout);
+    for expected in [
+        "Ghost links that do not resolve to storage",
+        ".cursor — is a foreign symlink to",
+        "Hidden targets the IDE still shows",
+        ".claude — is not excluded in .vscode",
+    ] {
+        assert!(text.contains(expected), "missing `{expected}`:\n{text}");
+    }
+    assert!(text.contains("[warning] .claude"), "{text}");
+    let all = output_text(&out);
+    assert!(all.contains("doctor found 1 problem(s)"), "{all}");
+}
+
+#[test]
+fn doctor_fix_removes_dangling_links_prunes_rules_and_readds_excludes() {
+    let root = TempDir::new("doctor-fix-extra");
+    for dir in [".cursor", ".claude", ".vscode"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create dir");
+    }
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".claude"]));
+
+    // Purge .claude's storage by hand and drop every IDE exclude.
+    fs::remove_dir_all(root.path().join(".cloak/storage/.claude"))
+        .expect("failed to purge storage entry");
+    fs::write(root.path().join(".vscode/settings.json"), "{}\n").expect("failed to reset settings");
+    assert!(!run_cloak(root.path(), &["doctor"]).status.success());
+
+    let out = run_cloak(root.path(), &["doctor", "--fix"]);
+    assert_success(&out);
+    let text = output_text(&out);
+    for expected in [
+        "[fixed] .claude — removed the dangling link to `.cloak/storage/.claude`",
+        "[fixed] .claude — pruned the stale gitignore rule `/.claude`",
+        "[fixed] .cursor — re-added the IDE excludes in .vscode",
+        "[fixed] .claude — dropped the IDE excludes left for `.claude`",
+        "Fixed 4 problem(s).",
+    ] {
+        assert!(text.contains(expected), "missing `{expected}`:\n{text}");
+    }
+
+    assert!(root.path().join(".claude").symlink_metadata().is_err());
+    let gitignore = fs::read_to_string(root.path().join(".gitignore")).unwrap();
+    assert!(!gitignore.lines().any(|l| l == "/.claude"), "{gitignore}");
+    assert!(gitignore.lines().any(|l| l == "/.cursor"), "{gitignore}");
+    let settings = fs::read_to_string(root.path().join(".vscode/settings.json")).unwrap();
+    assert!(settings.contains("**/.cursor"), "{settings}");
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+}
+
+#[cfg(unix)]
+#[test]
+fn storage_move_to_a_non_empty_directory_keeps_every_link() {
+    let root = TempDir::new("storage-move-refused");
+    let external = TempDir::without_git("storage-move-refused-external");
+    fs::write(external.path().join("taken.txt"), "x").expect("failed to write file");
+    for dir in [".cursor", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+        fs::write(root.path().join(dir).join("rules.md"), dir).expect("failed to write rules");
+    }
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".claude"]));
+
+    let out = run_cloak(
+        root.path(),
+        &["storage", "move", &external.path().to_string_lossy()],
+    );
+    assert!(!out.status.success(), "{}", output_text(&out));
+    assert!(output_text(&out).contains("destination is not empty"));
+
+    for dir in [".cursor", ".claude"] {
+        let link = root.path().join(dir);
+        assert!(link.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(link.join("rules.md")).unwrap(), dir);
+    }
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn refused_unhide_leaves_the_immutable_flag_set() {
+    let root = TempDir::new("unhide-immutable-refused");
+    let probe = root.path().join("probe");
+    fs::write(&probe, "").expect("failed to write probe");
+    let chattr = |flag: &str, path: &Path| {
+        Command::new("chattr")
+            .arg(flag)
+            .arg(path)
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+    if !chattr("+i", &probe) {
+        return;
+    }
+    assert!(chattr("-i", &probe));
+
+    let cursor = root.path().join(".cursor");
+    fs::create_dir_all(&cursor).expect("failed to create .cursor");
+    assert_success(&run_cloak(root.path(), &["hide", "--immutable", ".cursor"]));
+    remove_path_entry(&cursor);
+    fs::create_dir_all(&cursor).expect("failed to recreate .cursor");
+
+    let out = run_cloak(root.path(), &["unhide", ".cursor"]);
+    assert_eq!(out.status.code(), Some(4), "{}", output_text(&out));
+    let stored = root.path().join(".cloak/storage/.cursor");
+    assert!(
+        fs::write(stored.join("new.md"), "new").is_err(),
+        "a refused unhide cleared the immutable flag"
+    );
+
+    let out = run_cloak(root.path(), &["unhide", "--force", ".cursor"]);
+    assert_success(&out);
+    assert!(output_text(&out).contains("cleared immutable flag"));
+}
+
+#[test]
+fn status_lists_recorded_targets_and_flags_unrecorded_storage() {
+    let root = TempDir::new("status-recorded");
+    for dir in [".cursor", ".claude"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+    }
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor", ".claude"]));
+
+    // A fresh clone brings storage but not meta.json.
+    let meta_path = root.path().join(".cloak/meta.json");
+    let meta = fs::read_to_string(&meta_path).expect("failed to read meta.json");
+    let mut meta: serde_json::Value = serde_json::from_str(&meta).expect("meta is not JSON");
+    meta["entries"]
+        .as_object_mut()
+        .expect("entries is not an object")
+        .remove(".claude");
+    fs::write(&meta_path, meta.to_string()).expect("failed to write meta.json");
+
+    let out = run_cloak(root.path(), &["status"]);
+    assert_success(&out);
+    let text = output_text(&out);
+    let (hidden, unrecorded) = text
+        .split_once("In storage but not recorded in .cloak/meta.json:")
+        .unwrap_or_else(|| panic!("no unrecorded section:\n{text}"));
+    assert!(hidden.contains(".cursor [linked]"), "{text}");
+    assert!(!hidden.contains(".claude"), "{text}");
+    assert!(unrecorded.contains(".claude [linked]"), "{text}");
+
+    let out = run_cloak(root.path(), &["relink"]);
+    assert_success(&out);
+    assert!(output_text(&out).contains("+ recorded in .cloak/meta.json"));
+    let text = output_text(&run_cloak(root.path(), &["status"]));
+    assert!(text.contains(".claude [linked]"), "{text}");
+    assert!(!text.contains("not recorded"), "{text}");
+}
+
+#[test]
+fn info_resolves_a_flattened_target_by_its_root_path() {
+    let root = TempDir::new("info-flatten");
+    fs::create_dir_all(root.path().join("a/b")).expect("failed to create a/b");
+    fs::write(root.path().join("a/b/.env"), "TOKEN=1\n").expect("failed to write .env");
+    assert_success(&run_cloak(root.path(), &["hide", "--flatten", "a/b/.env"]));
+
+    let out = run_cloak(root.path(), &["info", "--json", "a/b/.env"]);
+    assert_success(&out);
+    let info: serde_json::Value =
+        serde_json::from_slice(&out.stdout).expect("info --json is not JSON");
+    assert_eq!(info["link_state"], "linked");
+    assert_eq!(info["storage_exists"], true);
+    assert_eq!(
+        info["storage_path"],
+        root.path()
+            .join(".cloak/storage/a__b__.env")
+            .to_string_lossy()
+            .as_ref()
+    );
+    assert_eq!(info["size_bytes"], 8);
+}
+
+#[test]
+fn cloak_toml_accepts_manage_gitignore_and_rejects_unknown_keys() {
+    let root = TempDir::new("toml-manage-gitignore");
+    fs::create_dir_all(root.path().join(".cloak")).expect("failed to create .cloak");
+    fs::create_dir_all(root.path().join(".cursor")).expect("failed to create .cursor");
+    fs::write(root.path().join(".gitignore"), "node_modules/\n").expect("failed to write");
+    fs::write(
+        root.path().join(".cloak/cloak.toml"),
+        "manage_gitignore = false\n",
+    )
+    .expect("failed to write cloak.toml");
+
+    assert_success(&run_cloak(root.path(), &["hide", ".cursor"]));
+    assert_eq!(
+        fs::read_to_string(root.path().join(".gitignore")).expect("read failed"),
+        "node_modules/\n"
+    );
+
+    fs::write(
+        root.path().join(".cloak/cloak.toml"),
+        "manage_gitignor = false\n",
+    )
+    .expect("failed to write cloak.toml");
+    let out = run_cloak(root.path(), &["unhide", ".cursor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    assert!(
+        output_text(&out).contains("unknown setting `manage_gitignor`"),
+        "{}",
+        output_text(&out)
+    );
+
+    fs::write(
+        root.path().join(".cloak/cloak.toml"),
+        "gitignore = true\nmanage_gitignore = false\n",
+    )
+    .expect("failed to write cloak.toml");
+    let out = run_cloak(root.path(), &["unhide", ".cursor"]);
+    assert!(!out.status.success(), "{}", output_text(&out));
+    assert!(
+        output_text(&out).contains("disagree"),
+        "{}",
+        output_text(&out)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn storage_regroup_moves_known_entries_into_categories_and_keeps_links() {
+    let root = TempDir::new("storage-regroup");
+    for dir in [".cursor", ".claude", ".mytool", ".codex"] {
+        fs::create_dir_all(root.path().join(dir)).expect("failed to create target");
+        fs::write(root.path().join(dir).join("rules.md"), dir).expect("failed to write");
+    }
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", ".cursor", ".claude", ".mytool"],
+    ));
+    assert_success(&run_cloak(
+        root.path(),
+        &["hide", "--link-name", ".codex-active", ".codex"],
+    ));
+
+    let out = run_cloak(root.path(), &["storage", "regroup"]);
+    assert_success(&out);
+    let text = output_text(&out);
+    assert!(text.contains("Regrouped 2 entries"), "{text}");
+    assert!(
+        text.contains(".codex skipped: has a custom link name"),
+        "{text}"
+    );
+
+    let storage = root.path().join(".cloak/storage");
+    for (link, stored) in [
+        (".cursor", "editors/.cursor"),
+        (".claude", "agents/.claude"),
+        (".mytool", ".mytool"),
+        (".codex-active", ".codex"),
+    ] {
+        assert!(storage.join(stored).join("rules.md").is_file(), "{stored}");
+        let path = root.path().join(link);
+        assert!(path.symlink_metadata().unwrap().file_type().is_symlink());
+        assert!(
+            fs::canonicalize(&path)
+                .unwrap()
+                .ends_with(Path::new(".cloak/storage").join(stored)),
+            "{link}"
+        );
+        assert!(path.join("rules.md").is_file(), "{link} does not resolve");
+    }
+    assert!(!storage.join(".cursor").exists());
+    assert_success(&run_cloak(root.path(), &["doctor"]));
+
+    // Running it again changes nothing.
+    let again = run_cloak(root.path(), &["storage", "regroup"]);
+    assert_success(&again);
+    assert!(
+        output_text(&again).contains("Storage is already grouped."),
+        "{}",
+        output_text(&again)
+    );
+    assert!(storage.join("agents/.claude/rules.md").is_file());
+
+    // Unhiding restores to the root path and clears the emptied category directory.
+    assert_success(&run_cloak(root.path(), &["unhide", ".claude"]));
+    assert!(root.path().join(".claude/rules.md").is_file());
+    assert!(!storage.join("agents").exists());
+}